@@ -18,8 +18,22 @@ struct ParamBundle {
     float blend;
     float cutoff;
     float resonance;
+    // OSC  (l Rust  ParamBundle::default() Ɠ)
+    float osc1_level    = 1.0f;
+    float osc2_level    = 1.0f;
+    float osc3_level    = 0.0f;
+    float osc1_ratio    = 1.0f;
+    float osc2_ratio    = 1.0f;
+    float osc3_ratio    = 2.0f;
+    float fm_index      = 5.0f;  // OSC2  OSC1 ϒ鋭x
+    float mix_mode_f    = 0.0f;  // 0.0=Add, 1.0=FM
+    // ̃ru[gēKp
+    float vibrato_depth = 0.0f;  // 0.0=, 1.0=ǂ
+    float vibrato_rate  = 1.0f;  // 1.0=ǂ
 };
 
+static_assert(sizeof(ParamBundle) == 17 * sizeof(float), "ParamBundle must match the Rust #[repr(C)] layout");
+
 /*
   RustBridge NX
   - DLL ̃[h^֐V{̉
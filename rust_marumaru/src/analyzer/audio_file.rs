@@ -0,0 +1,351 @@
+// src/analyzer/audio_file.rs
+
+use crate::log_message_internal;
+use super::error::AnalyzeError;
+use super::resample::resample_rate;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// デコード済みの音声 (チャンネルごとのサンプル列, -1.0〜1.0)
+#[derive(Debug, Clone)]
+pub struct DecodedAudio {
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl DecodedAudio {
+    /// 1チャンネルあたりのサンプル数
+    pub fn num_frames(&self) -> usize {
+        self.channels.first().map_or(0, |channel| channel.len())
+    }
+
+    /// 全チャンネルを平均してモノラルにする
+    pub fn downmix(&self) -> Vec<f32> {
+        let num_channels = self.channels.len().max(1) as f32;
+        (0..self.num_frames())
+            .map(|i| self.channels.iter().map(|channel| channel[i]).sum::<f32>() / num_channels)
+            .collect()
+    }
+
+    /// 全チャンネルを target_rate へ帯域制限リサンプリングする
+    pub fn resample(&self, target_rate: u32) -> DecodedAudio {
+        DecodedAudio {
+            sample_rate: if target_rate == 0 { self.sample_rate } else { target_rate },
+            channels: self.channels.iter()
+                .map(|channel| resample_rate(channel, self.sample_rate, target_rate))
+                .collect(),
+        }
+    }
+}
+
+/// インターリーブされたサンプル列をチャンネルごとに分ける
+fn deinterleave(interleaved: &[f32], num_channels: usize) -> Vec<Vec<f32>> {
+    let num_channels = num_channels.max(1);
+    (0..num_channels)
+        .map(|c| interleaved.iter().skip(c).step_by(num_channels).cloned().collect())
+        .collect()
+}
+
+/// WAV (8/16/24/32bit 整数, 32bit 浮動小数, WAVE_FORMAT_EXTENSIBLE) を読み込む
+fn decode_wav(path: &Path) -> Result<DecodedAudio, AnalyzeError> {
+    let mut reader = hound::WavReader::open(path)
+        .map_err(|e| AnalyzeError::Decode(format!("Failed to open WAV file: {}", e)))?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader.samples::<i32>().map(|s| s.map(|v| v as f32 * scale)).collect::<Result<_, _>>()
+        }
+    }
+    .map_err(|e| AnalyzeError::Decode(format!("Failed to decode WAV file: {}", e)))?;
+
+    Ok(DecodedAudio {
+        sample_rate: spec.sample_rate,
+        channels: deinterleave(&interleaved, spec.channels as usize),
+    })
+}
+
+/// FLAC を読み込む
+fn decode_flac(path: &Path) -> Result<DecodedAudio, AnalyzeError> {
+    let mut reader = claxon::FlacReader::open(path)
+        .map_err(|e| AnalyzeError::Decode(format!("Failed to open FLAC file: {}", e)))?;
+    let info = reader.streaminfo();
+    let scale = 1.0 / (1i64 << (info.bits_per_sample - 1)) as f32;
+    let interleaved: Vec<f32> = reader.samples()
+        .map(|s| s.map(|v| v as f32 * scale))
+        .collect::<Result<_, _>>()
+        .map_err(|e| AnalyzeError::Decode(format!("Failed to decode FLAC file: {}", e)))?;
+
+    Ok(DecodedAudio {
+        sample_rate: info.sample_rate,
+        channels: deinterleave(&interleaved, info.channels as usize),
+    })
+}
+
+/// 80bit 拡張精度浮動小数 (AIFF の sampleRate) を f64 に変換する
+fn extended_to_f64(bytes: &[u8]) -> f64 {
+    let sign = if bytes[0] & 0x80 != 0 { -1.0 } else { 1.0 };
+    let exponent = (((bytes[0] & 0x7f) as i32) << 8) | bytes[1] as i32;
+    let mantissa = u64::from_be_bytes([bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7], bytes[8], bytes[9]]);
+    if exponent == 0 && mantissa == 0 {
+        return 0.0;
+    }
+    sign * mantissa as f64 * 2f64.powi(exponent - 16383 - 63)
+}
+
+/// AIFF / AIFF-C のサンプル形式
+#[derive(Debug, Clone, Copy)]
+enum AiffEncoding {
+    BigEndianInt,    // AIFF, AIFF-C "NONE" / "twos"
+    LittleEndianInt, // AIFF-C "sowt"
+    Float32,         // AIFF-C "fl32"
+    Float64,         // AIFF-C "fl64"
+}
+
+impl AiffEncoding {
+    fn bytes_per_sample(self, bits: usize) -> usize {
+        match self {
+            AiffEncoding::BigEndianInt | AiffEncoding::LittleEndianInt => bits.div_ceil(8),
+            AiffEncoding::Float32 => 4,
+            AiffEncoding::Float64 => 8,
+        }
+    }
+
+    /// 1サンプル分のバイト列を -1.0〜1.0 に変換する (整数は左詰めの符号付き)
+    fn decode(self, bytes: &[u8]) -> f32 {
+        match self {
+            AiffEncoding::Float32 => f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            AiffEncoding::Float64 => f64::from_be_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+            ]) as f32,
+            AiffEncoding::BigEndianInt | AiffEncoding::LittleEndianInt => {
+                let little_endian = matches!(self, AiffEncoding::LittleEndianInt);
+                let mut value: i64 = 0;
+                for k in 0..bytes.len() {
+                    let b = if little_endian { bytes[bytes.len() - 1 - k] } else { bytes[k] };
+                    value = (value << 8) | b as i64;
+                }
+                let total_bits = 8 * bytes.len() as u32;
+                let value = (value << (64 - total_bits)) >> (64 - total_bits); // 符号拡張
+                value as f32 / (1i64 << (total_bits - 1)) as f32
+            }
+        }
+    }
+}
+
+/// AIFF / AIFF-C (NONE, sowt, fl32, fl64) を読み込む
+fn decode_aiff(path: &Path) -> Result<DecodedAudio, AnalyzeError> {
+    let mut data = Vec::new();
+    File::open(path)
+        .and_then(|file| BufReader::new(file).read_to_end(&mut data))
+        .map_err(|e| AnalyzeError::Decode(format!("Failed to open AIFF file: {}", e)))?;
+    if data.len() < 12 || &data[0..4] != b"FORM" || !(&data[8..12] == b"AIFF" || &data[8..12] == b"AIFC") {
+        return Err(AnalyzeError::Decode("Invalid AIFF header.".to_string()));
+    }
+    let is_aifc = &data[8..12] == b"AIFC";
+
+    // 1. COMM と SSND チャンクを探す
+    let mut comm: Option<&[u8]> = None;
+    let mut ssnd: Option<&[u8]> = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = u32::from_be_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as usize;
+        let body = &data[pos + 8..(pos + 8 + size).min(data.len())];
+        match id {
+            b"COMM" => comm = Some(body),
+            b"SSND" => ssnd = Some(body),
+            _ => {}
+        }
+        pos += 8 + size + (size & 1); // チャンクは偶数バイト境界にそろえられている
+    }
+    let comm = comm.filter(|c| c.len() >= 18).ok_or_else(|| AnalyzeError::Decode("AIFF file has no COMM chunk.".to_string()))?;
+    let ssnd = ssnd.filter(|s| s.len() >= 8).ok_or_else(|| AnalyzeError::Decode("AIFF file has no SSND chunk.".to_string()))?;
+
+    // 2. フォーマットを読む
+    let num_channels = i16::from_be_bytes([comm[0], comm[1]]).max(1) as usize;
+    let num_frames = u32::from_be_bytes([comm[2], comm[3], comm[4], comm[5]]) as usize;
+    let bits = i16::from_be_bytes([comm[6], comm[7]]);
+    let sample_rate = extended_to_f64(&comm[8..18]).round() as u32;
+    let compression: &[u8] = if is_aifc && comm.len() >= 22 { &comm[18..22] } else { b"NONE" };
+
+    let offset = u32::from_be_bytes([ssnd[0], ssnd[1], ssnd[2], ssnd[3]]) as usize;
+    let sound = ssnd.get(8 + offset..).unwrap_or(&[]);
+
+    // 3. サンプルを読む
+    let encoding = match compression {
+        b"NONE" | b"twos" => AiffEncoding::BigEndianInt,
+        b"sowt" => AiffEncoding::LittleEndianInt,
+        b"fl32" | b"FL32" => AiffEncoding::Float32,
+        b"fl64" | b"FL64" => AiffEncoding::Float64,
+        other => {
+            return Err(AnalyzeError::UnsupportedFormat(format!("AIFF-C compression {}", String::from_utf8_lossy(other))));
+        }
+    };
+    // 整数サンプルは 32bit まで (壊れた COMM の値でシフトがあふれないように)
+    let is_int = matches!(encoding, AiffEncoding::BigEndianInt | AiffEncoding::LittleEndianInt);
+    if is_int && !(1..=32).contains(&bits) {
+        return Err(AnalyzeError::Decode(format!("Unsupported AIFF sample size: {} bits.", bits)));
+    }
+    let interleaved: Vec<f32> = sound.chunks_exact(encoding.bytes_per_sample(bits.max(1) as usize))
+        .take(num_frames * num_channels)
+        .map(|bytes| encoding.decode(bytes))
+        .collect();
+
+    Ok(DecodedAudio {
+        sample_rate,
+        channels: deinterleave(&interleaved, num_channels),
+    })
+}
+
+/// 音声ファイルを読み込む (形式は先頭のシグネチャで判定する)
+/// - WAV: 8/16/24/32bit 整数, 32bit 浮動小数, WAVE_FORMAT_EXTENSIBLE
+/// - AIFF / AIFF-C, FLAC
+pub fn decode_file(path: &Path) -> Result<DecodedAudio, AnalyzeError> {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map_err(|e| AnalyzeError::Decode(format!("Failed to open audio file: {}", e)))?;
+
+    let decoded = match &magic {
+        b"RIFF" => decode_wav(path)?,
+        b"FORM" => decode_aiff(path)?,
+        b"fLaC" => decode_flac(path)?,
+        _ => return Err(AnalyzeError::UnsupportedFormat(path.display().to_string())),
+    };
+    if decoded.num_frames() == 0 || decoded.sample_rate == 0 {
+        return Err(AnalyzeError::Decode(format!("Audio file contains no samples: {}", path.display())));
+    }
+    log_message_internal("Rust", &format!(
+        "Decoded {}: {} Hz, {} ch, {} frames.",
+        path.display(),
+        decoded.sample_rate,
+        decoded.channels.len(),
+        decoded.num_frames()
+    ));
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テスト用の一時ファイルパス
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("marumaru_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_decode_wav_bit_depths() {
+        for (bits, format) in [
+            (8, hound::SampleFormat::Int),
+            (16, hound::SampleFormat::Int),
+            (24, hound::SampleFormat::Int),
+            (32, hound::SampleFormat::Int),
+            (32, hound::SampleFormat::Float),
+        ] {
+            // 1. Arrange: 0.5 と -0.5 を交互に書いたステレオWAV
+            let path = temp_path(&format!("{}_{:?}.wav", bits, format));
+            let spec = hound::WavSpec { channels: 2, sample_rate: 44100, bits_per_sample: bits, sample_format: format };
+            let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+            for _ in 0..10 {
+                match format {
+                    hound::SampleFormat::Float => {
+                        writer.write_sample(0.5f32).unwrap();
+                        writer.write_sample(-0.5f32).unwrap();
+                    }
+                    hound::SampleFormat::Int => {
+                        let half = 1i32 << (bits - 2);
+                        writer.write_sample(half).unwrap();
+                        writer.write_sample(-half).unwrap();
+                    }
+                }
+            }
+            writer.finalize().unwrap();
+
+            // 2. Act
+            let decoded = decode_file(&path).unwrap();
+            std::fs::remove_file(&path).ok();
+
+            // 3. Assert
+            assert_eq!(decoded.sample_rate, 44100);
+            assert_eq!(decoded.channels.len(), 2);
+            assert_eq!(decoded.num_frames(), 10);
+            assert!((decoded.channels[0][0] - 0.5).abs() < 1e-2, "{} bit: {}", bits, decoded.channels[0][0]);
+            assert!((decoded.channels[1][0] + 0.5).abs() < 1e-2, "{} bit: {}", bits, decoded.channels[1][0]);
+            assert!(decoded.downmix().iter().all(|s| s.abs() < 1e-2));
+        }
+    }
+
+    /// 48kHz, モノラルの AIFF を書き出す (sound は SSND のサンプル部分)
+    fn write_aiff(path: &Path, bits: i16, num_frames: u32, sound: &[u8]) {
+        let mut comm = Vec::new();
+        comm.extend_from_slice(&1i16.to_be_bytes());
+        comm.extend_from_slice(&num_frames.to_be_bytes());
+        comm.extend_from_slice(&bits.to_be_bytes());
+        comm.extend_from_slice(&[0x40, 0x0E, 0xBB, 0x80, 0, 0, 0, 0, 0, 0]); // 48000.0
+        let mut ssnd = vec![0u8; 8];
+        ssnd.extend_from_slice(sound);
+        let mut body = b"AIFF".to_vec();
+        for (id, chunk) in [(b"COMM", &comm), (b"SSND", &ssnd)] {
+            body.extend_from_slice(id);
+            body.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            body.extend_from_slice(chunk);
+            if chunk.len() % 2 == 1 {
+                body.push(0);
+            }
+        }
+        let mut file = b"FORM".to_vec();
+        file.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        file.extend_from_slice(&body);
+        std::fs::write(path, file).unwrap();
+    }
+
+    #[test]
+    fn test_decode_aiff_24bit() {
+        // 1. Arrange: 48kHz, 24bit, モノラルのAIFF (3サンプル)
+        let path = temp_path("24bit.aiff");
+        let samples: [i32; 3] = [1 << 22, -(1 << 22), 0];
+        let sound: Vec<u8> = samples.iter().flat_map(|s| s.to_be_bytes()[1..].to_vec()).collect();
+        write_aiff(&path, 24, samples.len() as u32, &sound);
+
+        // 2. Act
+        let decoded = decode_file(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        // 3. Assert
+        assert_eq!(decoded.sample_rate, 48000);
+        assert_eq!(decoded.channels, vec![vec![0.5, -0.5, 0.0]]);
+    }
+
+    #[test]
+    fn test_decode_aiff_rejects_malformed_sample_size() {
+        for bits in [0i16, -8, 100, 32767] {
+            // 1. Arrange: COMM のサンプルサイズが壊れたAIFF
+            let path = temp_path(&format!("bits{}.aiff", bits));
+            write_aiff(&path, bits, 4, &[0x7F; 64]);
+
+            // 2. Act
+            let decoded = decode_file(&path);
+            std::fs::remove_file(&path).ok();
+
+            // 3. Assert: パニックせずにデコードエラーを返す
+            assert!(matches!(decoded, Err(AnalyzeError::Decode(_))), "bits = {}: {:?}", bits, decoded.err());
+        }
+    }
+
+    #[test]
+    fn test_resample_preserves_duration() {
+        // 1. Arrange: 44.1kHz で1秒のモノラル音声
+        let audio = DecodedAudio { sample_rate: 44100, channels: vec![vec![0.0; 44100]] };
+
+        // 2. Act
+        let resampled = audio.resample(48000);
+
+        // 3. Assert
+        assert_eq!(resampled.sample_rate, 48000);
+        assert_eq!(resampled.num_frames(), 48000);
+    }
+}
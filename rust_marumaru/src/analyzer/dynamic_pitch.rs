@@ -1,69 +1,159 @@
 // src/analyzer/dynamic_pitch.rs
 
-/// 線形補間を使ってオーディオ波形から特定の位置のサンプル値を取得するヘルパー関数
-fn sample_linear(wave: &[f32], index_f: f32) -> f32 {
-    if wave.is_empty() || index_f < 0.0 || index_f >= (wave.len() - 1) as f32 {
-        return 0.0;
-    }
-    let idx0 = index_f.floor() as usize;
-    let idx1 = idx0 + 1;
-    let frac = index_f - idx0 as f32;
-    wave[idx0] * (1.0 - frac) + wave[idx1] * frac
+use crate::log_message_internal;
+use super::f0_estimator::{self, F0Track};
+use super::error::AnalyzeError;
+use super::mode_time;
+use super::progress::{AnalysisProgress, AnalysisStage};
+use super::resample::sinc_interpolate;
+use super::types::AnalysisParams;
+
+/// DynamicPitchSync の結果
+#[derive(Debug, Clone)]
+pub struct PitchSyncResult {
+    pub audio: Vec<f32>,                // ピッチを F_target に平坦化した音声
+    pub target_f0: f32,                 // 平坦化の基準ピッチ F_target [Hz]
+    pub track: F0Track,                 // 平坦化後に再推定したF0
+    pub source_cycles: Vec<(f32, f32)>, // 平坦化に使った元音声の周期境界 (他チャンネルを同じく伸縮するため)
+    pub cycle_heads: Vec<(f32, f32)>,   // 平坦化後に再検出した周期境界 (先頭位置, 長さ)
+    pub pitch_contour: Vec<f32>,        // 取り除いたピッチ変化 [cent] (元のF0カーブと同じフレーム)
 }
 
-/// DynamicPitchSyncを適用してウェーブテーブルのピッチ揺れを正規化する
-pub fn apply_pitch_sync(
-    tables: &[Vec<f32>],
-    f0_curve: &[f32],
-) -> Result<Vec<Vec<f32>>, String> {
-    println!("[INFO] DynamicPitchSync started.");
+/// 有声フレームの平均F0 (F_target) と、それに対する各フレームのずれ [cent] を求める
+/// - 無声フレーム (F0 = 0) のずれは 0 とする
+pub fn pitch_contour(f0_curve: &[f32]) -> Option<(f32, Vec<f32>)> {
+    let voiced: Vec<f32> = f0_curve.iter().cloned().filter(|&f| f > 0.0).collect();
+    if voiced.is_empty() {
+        return None;
+    }
+    let target_f0 = voiced.iter().sum::<f32>() / voiced.len() as f32;
+    let contour = f0_curve.iter()
+        .map(|&f| if f > 0.0 { 1200.0 * (f / target_f0).log2() } else { 0.0 })
+        .collect();
+    Some((target_f0, contour))
+}
 
-    // F0カーブが無効な場合は何もしない
-    let average_f0: f32 = f0_curve.iter().filter(|&&f| f > 0.0).sum::<f32>()
-        / f0_curve.iter().filter(|&&f| f > 0.0).count() as f32;
-    if average_f0.is_nan() || average_f0 < 1.0 {
-        println!("[WARN] Invalid F0 curve for DynamicPitchSync. Skipping.");
-        return Ok(tables.to_vec());
+/// 音声を t' = ∫(F0(t) / F_target) dt で時間伸縮し、ピッチを F_target に平坦化する
+/// - F0(t) は周期境界ごとの実測値 (= sample_rate / 周期長) を使うため、各周期の先頭は
+///   出力上で F_target の周期のちょうど整数倍の位置に並ぶ (周期頭の位相がそろう)
+/// - 最初の周期より前と最後の周期より後は伸縮しない
+/// - 元信号を速く読む (縮める) 区間は、エイリアスを防ぐため補間カーネルの帯域を下げる
+pub fn warp_to_target_pitch(
+    audio: &[f32],
+    cycle_boundaries: &[(f32, f32)],
+    target_period: f32,
+) -> Vec<f32> {
+    if audio.is_empty() || cycle_boundaries.is_empty() || target_period <= 0.0 {
+        return audio.to_vec();
     }
 
-    let mut final_tables = Vec::new();
+    // 1. 入力上の節点 (周期の先頭) と、伸縮後の時刻 τ を対応づける
+    //    (先頭の前に 0、末尾の後に音声の終端を加え、そこは1:1で写す)
+    let first = cycle_boundaries[0].0;
+    let (last_start, last_len) = cycle_boundaries[cycle_boundaries.len() - 1];
+    let mut knots: Vec<(f32, f32)> = Vec::with_capacity(cycle_boundaries.len() + 3);
+    knots.push((0.0, 0.0));
+    for (k, &(start, _)) in cycle_boundaries.iter().enumerate() {
+        knots.push((start, first + k as f32 * target_period));
+    }
+    let warped_end = first + cycle_boundaries.len() as f32 * target_period;
+    knots.push((last_start + last_len, warped_end));
+    let input_end = audio.len() as f32;
+    let tail = (input_end - (last_start + last_len)).max(0.0);
+    knots.push((input_end, warped_end + tail));
 
-    for table in tables {
-        let table_len = table.len();
-        if table_len == 0 {
-            final_tables.push(Vec::new());
-            continue;
+    // 2. 出力の各サンプル n について τ(t) = n となる入力位置 t を求めて読み出す
+    let output_len = (warped_end + tail).floor() as usize;
+    let mut output = Vec::with_capacity(output_len);
+    let mut k = 0;
+    for n in 0..output_len {
+        let n = n as f32;
+        while k + 2 < knots.len() && knots[k + 1].1 <= n {
+            k += 1;
         }
+        let (t0, tau0) = knots[k];
+        let (t1, tau1) = knots[k + 1];
+        let step = if tau1 > tau0 { (t1 - t0) / (tau1 - tau0) } else { 1.0 };
+        let position = t0 + (n - tau0) * step;
+        output.push(sinc_interpolate(audio, position, (1.0 / step).min(1.0)));
+    }
+    output
+}
 
-        let mut rescaled_table = vec![0.0; table_len];
-        let mut current_pos = 0.0; // 元のテーブルを読む位置
+/// DynamicPitchSync を適用する
+/// 1. 平均ピッチ F_target を求める
+/// 2. 周期境界を検出し、t' = ∫(F0(t)/F_target)dt で音声を時間伸縮してビブラートやグライドを取り除く
+/// 3. 平坦化後の音声で F0 を再推定し、周期境界を再検出する (周期頭は相互相関で揃える)
+pub fn apply_pitch_sync(
+    audio: &[f32],
+    sample_rate: u32,
+    f0_curve: &[f32],
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<PitchSyncResult, AnalyzeError> {
 
-        // F0カーブの1フレームがウェーブテーブルの何サンプルに対応するか
-        // HOP_SIZE(512) / sample_rate * average_f0 * table_len
-        // を簡略化して計算
-        let f0_frames_per_table = f0_curve.len() as f32;
+    let (target_f0, contour) = pitch_contour(f0_curve)
+        .ok_or(AnalyzeError::NoF0Found)?;
 
-        for i in 0..table_len {
-            // 現在位置(i)がF0カーブのどのインデックスに対応するか
-            let f0_idx_f = (i as f32 / table_len as f32) * f0_frames_per_table;
-            let f0_idx = f0_idx_f.round() as usize;
+    let boundaries = mode_time::detect_cycle_boundaries(audio, sample_rate, f0_curve, params)?;
+    progress.update(0.1)?;
+    let warped = warp_to_target_pitch(audio, &boundaries, sample_rate as f32 / target_f0);
+    log_message_internal("Rust", &format!(
+        "Audio warped to {:.2} Hz ({} -> {} samples).",
+        target_f0,
+        audio.len(),
+        warped.len()
+    ));
 
-            let current_f0 = f0_curve.get(f0_idx).cloned().unwrap_or(average_f0);
+    let track = f0_estimator::estimate_f0_curve(
+        &warped, sample_rate, params, &progress.stage(AnalysisStage::PitchSync, 0.3, 0.9)?,
+    )?;
+    progress.update(0.9)?;
+    let cycle_heads = mode_time::detect_cycle_boundaries(&warped, sample_rate, &track.f0_curve, params)?;
 
-            // 読み出し速度をピッチのズレに応じて調整
-            // ピッチが高い -> 速く読む, ピッチが低い -> 遅く読む
-            let speed_ratio = if current_f0 > 0.0 { current_f0 / average_f0 } else { 1.0 };
+    log_message_internal("Rust", &format!("DynamicPitchSync finished. Re-detected {} cycles.", cycle_heads.len()));
+    Ok(PitchSyncResult {
+        audio: warped,
+        target_f0,
+        track,
+        source_cycles: boundaries,
+        cycle_heads,
+        pitch_contour: contour,
+    })
+}
 
-            rescaled_table[i] = sample_linear(table, current_pos);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
 
-            current_pos += speed_ratio;
-            if current_pos >= table_len as f32 {
-                current_pos -= table_len as f32; // ループさせる
-            }
-        }
-        final_tables.push(rescaled_table);
-    }
+    #[test]
+    fn test_pitch_sync_removes_vibrato() {
+        // 1. Arrange: 220Hz ±50cent, 5Hz のビブラートを持つ信号
+        const SAMPLE_RATE: u32 = 48000;
+        let params = AnalysisParams::default();
+        let mut phase = 0.0f32;
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let freq = 220.0 * 2f32.powf(50.0 / 1200.0 * (2.0 * PI * 5.0 * t).sin());
+                phase += 2.0 * PI * freq / SAMPLE_RATE as f32;
+                phase.sin() + 0.5 * (2.0 * phase).sin()
+            })
+            .collect();
+        let track = f0_estimator::estimate_f0_curve(&signal, SAMPLE_RATE, &params, &AnalysisProgress::default()).unwrap();
+
+        // 2. Act
+        let result = apply_pitch_sync(&signal, SAMPLE_RATE, &track.f0_curve, &params, &AnalysisProgress::default()).unwrap();
 
-    println!("[INFO] DynamicPitchSync finished.");
-    Ok(final_tables)
-}
\ No newline at end of file
+        // 3. Assert: 取り除いたピッチ変化は ±50cent 程度、平坦化後の揺れは数cent以内
+        let removed = result.pitch_contour.iter().map(|c| c.abs()).fold(0.0, f32::max);
+        assert!(removed > 35.0, "removed contour depth = {}", removed);
+        let voiced: Vec<f32> = result.track.f0_curve.iter().cloned().filter(|&f| f > 0.0).collect();
+        let residual = voiced[2..voiced.len() - 2].iter()
+            .map(|&f| (1200.0 * (f / result.target_f0).log2()).abs())
+            .fold(0.0, f32::max);
+        assert!(residual < 8.0, "residual deviation = {} cent", residual);
+        assert!(!result.cycle_heads.is_empty());
+    }
+}
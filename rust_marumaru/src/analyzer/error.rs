@@ -0,0 +1,113 @@
+// src/analyzer/error.rs
+
+use std::fmt;
+
+/// 解析 (と FFI の呼び出し) が失敗した理由
+/// - code() の値は FFI (mm_last_error_code) に公開しているため、既存の値を変えないこと (追加は末尾へ)
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnalyzeError {
+    InvalidArgument(String),                          // FFI の引数が不正 (null ポインタ, UTF-8 でないパスなど)
+    InvalidParams(String),                            // 解析パラメータが不正
+    EmptyInput,                                       // 音声が空, またはチャンネルがない
+    InputTooShort { needed: usize, actual: usize },   // 解析に必要な長さ [サンプル] に足りない
+    NoF0Found,                                        // 有効なF0 (有声フレーム) が見つからない
+    NoCyclesExtracted,                                // 周期を切り出せない
+    Decode(String),                                   // 音声ファイルを開けない・読めない
+    UnsupportedFormat(String),                        // 対応していないファイル形式
+    ChannelMismatch(String),                          // チャンネル数・長さがチャンネルモードに合わない
+    AnalysisFailed(String),                           // モード解析などの内部処理の失敗
+    Cancelled,                                        // キャンセルされた
+    Io(String),                                       // ファイルの読み書き (開く・作る・書き込む) の失敗
+    Preset(String),                                   // プリセットの形式が不正 (ZIP・メタデータ・データ長)
+    State(String),                                    // セッション状態のバイナリが壊れている・新しすぎる
+    Wavetable(String),                                // ウェーブテーブルの形式が不正, またはフレームがない
+    Sampler(String),                                  // サンプラー用に書き出せる内容がない
+    Midi(String),                                     // MIDI ファイルを解析できない
+    NoResultLoaded,                                   // 解析結果がロードされていない
+    JobNotFound,                                      // ジョブが存在しない (破棄済みを含む)
+    JobRunning,                                       // ジョブがまだ実行中
+    LockFailed,                                       // 内部状態のロックに失敗した
+}
+
+impl AnalyzeError {
+    /// FFI に返す安定したエラーコード (0 はエラーなし)
+    pub fn code(&self) -> i32 {
+        match self {
+            AnalyzeError::InvalidArgument(_) => 1,
+            AnalyzeError::InvalidParams(_) => 2,
+            AnalyzeError::EmptyInput => 3,
+            AnalyzeError::InputTooShort { .. } => 4,
+            AnalyzeError::NoF0Found => 5,
+            AnalyzeError::NoCyclesExtracted => 6,
+            AnalyzeError::Decode(_) => 7,
+            AnalyzeError::UnsupportedFormat(_) => 8,
+            AnalyzeError::ChannelMismatch(_) => 9,
+            AnalyzeError::AnalysisFailed(_) => 10,
+            AnalyzeError::Cancelled => 11,
+            AnalyzeError::Io(_) => 12,
+            AnalyzeError::Preset(_) => 13,
+            AnalyzeError::State(_) => 14,
+            AnalyzeError::Wavetable(_) => 15,
+            AnalyzeError::Sampler(_) => 16,
+            AnalyzeError::Midi(_) => 17,
+            AnalyzeError::NoResultLoaded => 18,
+            AnalyzeError::JobNotFound => 19,
+            AnalyzeError::JobRunning => 20,
+            AnalyzeError::LockFailed => 21,
+        }
+    }
+}
+
+impl fmt::Display for AnalyzeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalyzeError::InvalidArgument(message)
+            | AnalyzeError::InvalidParams(message)
+            | AnalyzeError::Decode(message)
+            | AnalyzeError::ChannelMismatch(message)
+            | AnalyzeError::AnalysisFailed(message)
+            | AnalyzeError::Io(message)
+            | AnalyzeError::Preset(message)
+            | AnalyzeError::State(message)
+            | AnalyzeError::Wavetable(message)
+            | AnalyzeError::Sampler(message)
+            | AnalyzeError::Midi(message) => write!(f, "{}", message),
+            AnalyzeError::UnsupportedFormat(what) => write!(f, "Unsupported audio file format: {}", what),
+            AnalyzeError::EmptyInput => write!(f, "Input audio is empty."),
+            AnalyzeError::InputTooShort { needed, actual } => {
+                write!(f, "Audio is too short for analysis (need {} samples, got {}).", needed, actual)
+            }
+            AnalyzeError::NoF0Found => write!(f, "No valid F0 was found in the audio."),
+            AnalyzeError::NoCyclesExtracted => write!(f, "No cycles could be extracted from the audio."),
+            AnalyzeError::Cancelled => write!(f, "Analysis was cancelled."),
+            AnalyzeError::NoResultLoaded => write!(f, "No analysis result is loaded."),
+            AnalyzeError::JobNotFound => write!(f, "The job does not exist."),
+            AnalyzeError::JobRunning => write!(f, "The job is still running."),
+            AnalyzeError::LockFailed => write!(f, "Could not lock the plugin state."),
+        }
+    }
+}
+
+impl std::error::Error for AnalyzeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_stable_and_messages_are_readable() {
+        // 1. Arrange
+        let too_short = AnalyzeError::InputTooShort { needed: 4096, actual: 100 };
+
+        // 2. Act
+        let message = too_short.to_string();
+
+        // 3. Assert
+        assert_eq!(too_short.code(), 4);
+        assert_eq!(AnalyzeError::NoF0Found.code(), 5);
+        assert_eq!(AnalyzeError::Cancelled.code(), 11);
+        assert_eq!(AnalyzeError::Preset(String::new()).code(), 13);
+        assert_eq!(AnalyzeError::LockFailed.code(), 21);
+        assert_eq!(message, "Audio is too short for analysis (need 4096 samples, got 100).");
+    }
+}
@@ -1,60 +1,310 @@
 // src/analyzer/f0_estimator.rs
 
+use crate::log_message_internal;
 use rustfft::{FftPlanner, num_complex::Complex};
 use pitch_detection::detector::{yin::YINDetector, PitchDetector};
 use splines::{Spline, Key, Interpolation};
+use super::error::AnalyzeError;
+use super::progress::AnalysisProgress;
+use super::types::{AnalysisParams, PitchEstimatorKind};
 
 
-// --- 解析パラメータ ---
-const FRAME_SIZE   : usize = 2048;
-const HOP_SIZE     : usize = 512;
-const PADDING_SIZE : usize = FRAME_SIZE / 2; // YIN用
+// --- 融合パラメータ ---
+const OCTAVE_TOLERANCE : f32 = 0.1;  // オクターブ換算でこの範囲内なら同じピッチとみなす
+const HPS_HARMONICS    : usize = 5;  // HPSで掛け合わせる倍音数
+const HPS_ZERO_PADDING : usize = 4;  // HPSの周波数分解能を上げるためのゼロ詰め倍率
 
-fn cepstrum_estimator(frame: &[f32], sample_rate: u32, planner: &mut FftPlanner<f32>) -> (f32, f32) {
-    let frame_size = frame.len();
-    let window: Vec<f32> = (0..frame_size)
-        .map(|i| 0.5 * (1.0 - (2.0 * std::f32::consts::PI * i as f32 / (frame_size - 1) as f32).cos()))
-        .collect();
-    let mut buffer: Vec<Complex<f32>> = frame.iter()
-        .zip(window.iter())
-        .map(|(&sample, &win)| Complex::new(sample * win, 0.0))
-        .collect();
-    let fft_forward = planner.plan_fft_forward(frame_size);
-    fft_forward.process(&mut buffer);
-    
-    // ★★★ 修正点: FFT後の結果をFFT_SIZEで割って正規化する ★★★
-    let normalized_buffer: Vec<Complex<f32>> = buffer.iter()
-        .map(|c| *c / frame_size as f32)
-        .collect();
+// --- Viterbi平滑化パラメータ ---
+const TRANSITION_WEIGHT   : f32 = 20.0; // 相対周波数差の二乗 (Δf/f)^2 に掛ける遷移コスト
+const VOICING_SWITCH_COST : f32 = 0.5;  // 有声⇔無声の切り替えコスト
+const OCTAVE_PENALTY      : f32 = 0.5;  // オクターブ違いの候補に掛ける信頼度の減衰率
 
-    let log_power_spectrum: Vec<Complex<f32>> = normalized_buffer.iter()
-        .map(|c| Complex::new((c.norm_sqr() + 1e-12).log10(), 0.0))
-        .collect();
+
+/// 1フレーム分のピッチ推定結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchCandidate {
+    pub frequency: f32,  // 推定周波数 [Hz]
+    pub confidence: f32, // 信頼度 (0〜1)
+}
+
+/// F0推定器の共通インターフェース
+/// - 新しい推定器 (pYIN, SWIPE など) はこのトレイトを実装して estimate_f0_curve_with に渡す
+pub trait PitchEstimator {
+    /// ログ表示用の名前
+    fn name(&self) -> &'static str;
+
+    /// 1フレームからピッチを推定する (推定できない場合は None)
+    fn estimate(&mut self, frame: &[f32], sample_rate: u32) -> Option<PitchCandidate>;
+}
+
+
+/// YIN (自己差分法) による推定器
+pub struct YinEstimator {
+    detector: YINDetector<f32>,
+    power_threshold: f32,
+    clarity_threshold: f32,
+}
+
+impl YinEstimator {
+    pub fn new(params: &AnalysisParams, frame_size: usize) -> Self {
+        YinEstimator {
+            detector: YINDetector::<f32>::new(frame_size, frame_size / 2),
+            power_threshold: params.yin_power_threshold,
+            clarity_threshold: params.yin_clarity_threshold,
+        }
+    }
+}
+
+impl PitchEstimator for YinEstimator {
+    fn name(&self) -> &'static str { "YIN" }
+
+    fn estimate(&mut self, frame: &[f32], sample_rate: u32) -> Option<PitchCandidate> {
+        self.detector
+            .get_pitch(frame, sample_rate as usize, self.power_threshold, self.clarity_threshold)
+            .map(|p| PitchCandidate { frequency: p.frequency, confidence: p.clarity.clamp(0.0, 1.0) })
+    }
+}
+
+
+/// ケプストラム法による推定器
+pub struct CepstrumEstimator {
+    planner: FftPlanner<f32>,
+    window: Vec<f32>,
+    min_f0: f32,
+    max_f0: f32,
+}
+
+impl CepstrumEstimator {
+    pub fn new(params: &AnalysisParams, frame_size: usize) -> Self {
+        CepstrumEstimator {
+            planner: FftPlanner::new(),
+            window: params.window.generate(frame_size),
+            min_f0: params.min_f0,
+            max_f0: params.max_f0,
+        }
+    }
+}
+
+impl PitchEstimator for CepstrumEstimator {
+    fn name(&self) -> &'static str { "Cepstrum" }
+
+    fn estimate(&mut self, frame: &[f32], sample_rate: u32) -> Option<PitchCandidate> {
+        let frame_size = frame.len();
+        let mut buffer: Vec<Complex<f32>> = frame.iter()
+            .zip(self.window.iter())
+            .map(|(&sample, &win)| Complex::new(sample * win, 0.0))
+            .collect();
+        let fft_forward = self.planner.plan_fft_forward(frame_size);
+        fft_forward.process(&mut buffer);
         
-    let mut cepstrum_buffer = log_power_spectrum;
-    let fft_inverse = planner.plan_fft_inverse(frame_size);
-    fft_inverse.process(&mut cepstrum_buffer);
-    
-    // NOTE: iFFT後の結果もFFT_SIZEで割って正規化する必要がありますが、
-    // ここでは比率しか見ていないため、省略します。
-    // 信頼度を計算する際には最終的に正規化に近くなります。
-    
-    let min_period = (sample_rate as f32 / 1000.0).floor() as usize;
-    let max_period = (sample_rate as f32 / 80.0).ceil() as usize;
-    let search_range_start = min_period.max(1);
-    let search_range_end = max_period.min(cepstrum_buffer.len() / 2);
-    let peak = cepstrum_buffer[search_range_start..search_range_end]
-        .iter()
-        .enumerate()
-        .max_by(|a, b| a.1.re.partial_cmp(&b.1.re).unwrap());
-    if let Some((index, &value)) = peak {
+        // ★★★ 修正点: FFT後の結果をFFT_SIZEで割って正規化する ★★★
+        let normalized_buffer: Vec<Complex<f32>> = buffer.iter()
+            .map(|c| *c / frame_size as f32)
+            .collect();
+
+        let log_power_spectrum: Vec<Complex<f32>> = normalized_buffer.iter()
+            .map(|c| Complex::new((c.norm_sqr() + 1e-12).log10(), 0.0))
+            .collect();
+            
+        let mut cepstrum_buffer = log_power_spectrum;
+        let fft_inverse = self.planner.plan_fft_inverse(frame_size);
+        fft_inverse.process(&mut cepstrum_buffer);
+        
+        // NOTE: iFFT後の結果もFFT_SIZEで割って正規化する必要がありますが、
+        // ここでは比率しか見ていないため、省略します。
+        // 信頼度を計算する際には最終的に正規化に近くなります。
+        
+        let min_period = (sample_rate as f32 / self.max_f0).floor() as usize;
+        let max_period = (sample_rate as f32 / self.min_f0).ceil() as usize;
+        let search_range_start = min_period.max(1);
+        let search_range_end = max_period.min(cepstrum_buffer.len() / 2);
+        if search_range_start >= search_range_end {
+            return None;
+        }
+        let (index, value) = cepstrum_buffer[search_range_start..search_range_end]
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.re.partial_cmp(&b.1.re).unwrap_or(std::cmp::Ordering::Equal))?;
         let period = index + search_range_start;
         let f0 = sample_rate as f32 / period as f32;
         // FFT後の正規化で値が小さくなったため、ここで scaling factor を調整
-        let confidence = (value.re / frame_size as f32).max(0.0).min(1.0); 
-        (f0, confidence)
-    } else {
-        (0.0, 0.0)
+        let confidence = (value.re / frame_size as f32).clamp(0.0, 1.0); 
+        Some(PitchCandidate { frequency: f0, confidence })
+    }
+}
+
+
+/// ハーモニック積スペクトル (HPS) による推定器
+pub struct HpsEstimator {
+    planner: FftPlanner<f32>,
+    window: Vec<f32>,
+    min_f0: f32,
+    max_f0: f32,
+}
+
+impl HpsEstimator {
+    pub fn new(params: &AnalysisParams, frame_size: usize) -> Self {
+        HpsEstimator {
+            planner: FftPlanner::new(),
+            window: params.window.generate(frame_size),
+            min_f0: params.min_f0,
+            max_f0: params.max_f0,
+        }
+    }
+}
+
+/// ピーク近傍を対数振幅で放物線補間して小数ビン位置を返す
+fn interpolate_peak(spectrum: &[f32], bin: usize) -> f32 {
+    if bin == 0 || bin + 1 >= spectrum.len() {
+        return bin as f32;
+    }
+    let log = |m: f32| (m + 1e-12).ln();
+    let (y0, y1, y2) = (log(spectrum[bin - 1]), log(spectrum[bin]), log(spectrum[bin + 1]));
+    let denom = y0 - 2.0 * y1 + y2;
+    if denom.abs() < 1e-12 {
+        return bin as f32;
+    }
+    bin as f32 + (0.5 * (y0 - y2) / denom).clamp(-0.5, 0.5)
+}
+
+/// 指定ビンの近傍 (±radius) で最大値を持つビンを返す
+fn local_max_bin(spectrum: &[f32], center: usize, radius: usize) -> usize {
+    let lo = center.saturating_sub(radius);
+    let hi = (center + radius).min(spectrum.len() - 1);
+    (lo..=hi)
+        .max_by(|&a, &b| spectrum[a].partial_cmp(&spectrum[b]).unwrap_or(std::cmp::Ordering::Equal))
+        .unwrap_or(center)
+}
+
+impl PitchEstimator for HpsEstimator {
+    fn name(&self) -> &'static str { "HPS" }
+
+    fn estimate(&mut self, frame: &[f32], sample_rate: u32) -> Option<PitchCandidate> {
+        // 1. ゼロ詰めした振幅スペクトルを求める
+        let fft_size = frame.len() * HPS_ZERO_PADDING;
+        let mut buffer: Vec<Complex<f32>> = vec![Complex::default(); fft_size];
+        for (dst, (&sample, &win)) in buffer.iter_mut().zip(frame.iter().zip(self.window.iter())) {
+            *dst = Complex::new(sample * win, 0.0);
+        }
+        self.planner.plan_fft_forward(fft_size).process(&mut buffer);
+        let magnitude: Vec<f32> = buffer[..fft_size / 2].iter().map(|c| c.norm()).collect();
+
+        let max_mag = magnitude.iter().cloned().fold(0.0, f32::max);
+        if max_mag < 1e-9 {
+            return None;
+        }
+        let bin_hz = sample_rate as f32 / fft_size as f32;
+        let min_bin = ((self.min_f0 / bin_hz).floor() as usize).max(1);
+        let max_bin = ((self.max_f0 / bin_hz).ceil() as usize).min(magnitude.len() / HPS_HARMONICS);
+        if min_bin >= max_bin {
+            return None;
+        }
+
+        // 2. 対数領域で倍音を掛け合わせ (= 足し合わせ)、最大となる基音ビンを探す
+        let floor = max_mag * 1e-3;
+        let log_mag: Vec<f32> = magnitude.iter().map(|&m| (m + floor).ln()).collect();
+        let hps: Vec<f32> = (min_bin..max_bin)
+            .map(|k| (1..=HPS_HARMONICS).map(|h| log_mag[k * h]).sum())
+            .collect();
+        let (peak_offset, _) = hps.iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))?;
+        let mut f0 = (min_bin + peak_offset) as f32 * bin_hz;
+
+        // 3. サブオクターブ誤りの補正: 奇数次倍音がほぼ無い場合は1オクターブ上が真の基音
+        let harmonic_energy = |f: f32, parity: usize| -> f32 {
+            (1..=HPS_HARMONICS)
+                .filter(|h| h % 2 == parity)
+                .map(|h| {
+                    let bin = (f * h as f32 / bin_hz).round() as usize;
+                    if bin + 2 < magnitude.len() { magnitude[local_max_bin(&magnitude, bin, 2)].powi(2) } else { 0.0 }
+                })
+                .sum()
+        };
+        while f0 * 2.0 <= self.max_f0 && harmonic_energy(f0, 1) < 0.1 * harmonic_energy(f0, 0) {
+            f0 *= 2.0;
+        }
+
+        // 4. 各倍音ピークの補間位置から基音周波数を精密化し、信頼度 (倍音エネルギー比) を求める
+        let mut weighted_f0 = 0.0;
+        let mut weight_sum = 0.0;
+        let mut harmonic_power = 0.0;
+        for h in 1..=HPS_HARMONICS {
+            let center = (f0 * h as f32 / bin_hz).round() as usize;
+            let lobe = 2 * HPS_ZERO_PADDING; // 窓のメインローブ半幅 (ビン)
+            if center + lobe >= magnitude.len() {
+                break;
+            }
+            let peak_bin = local_max_bin(&magnitude, center, lobe);
+            let refined = interpolate_peak(&magnitude, peak_bin) * bin_hz / h as f32;
+            weighted_f0 += refined * magnitude[peak_bin];
+            weight_sum += magnitude[peak_bin];
+            let lo = peak_bin.saturating_sub(lobe);
+            let hi = (peak_bin + lobe).min(magnitude.len() - 1);
+            harmonic_power += magnitude[lo..=hi].iter().map(|m| m * m).sum::<f32>();
+        }
+        if weight_sum <= 0.0 {
+            return None;
+        }
+        let band_end = (((HPS_HARMONICS as f32 + 0.5) * f0 / bin_hz) as usize).min(magnitude.len());
+        let band_start = ((0.5 * f0 / bin_hz) as usize).min(band_end);
+        let band_power: f32 = magnitude[band_start..band_end].iter().map(|m| m * m).sum();
+        let confidence = if band_power > 0.0 { (harmonic_power / band_power).clamp(0.0, 1.0) } else { 0.0 };
+
+        Some(PitchCandidate { frequency: weighted_f0 / weight_sum, confidence })
+    }
+}
+
+
+/// パラメータで指定された推定器の組を生成する
+/// - フレーム長は min_f0 に応じて伸ばした値 (AnalysisParams::f0_frame_size) を使う
+pub fn build_estimators(params: &AnalysisParams, sample_rate: u32) -> Vec<Box<dyn PitchEstimator>> {
+    let frame_size = params.f0_frame_size(sample_rate);
+    params.pitch_estimators.iter()
+        .map(|kind| -> Box<dyn PitchEstimator> {
+            match kind {
+                PitchEstimatorKind::Yin => Box::new(YinEstimator::new(params, frame_size)),
+                PitchEstimatorKind::Cepstrum => Box::new(CepstrumEstimator::new(params, frame_size)),
+                PitchEstimatorKind::Hps => Box::new(HpsEstimator::new(params, frame_size)),
+            }
+        })
+        .collect()
+}
+
+/// 複数推定器の結果を信頼度で加重平均する (オクターブ誤り補正つき)
+/// - 最も信頼度の高い候補を基準とし、他の候補は 2^k 倍のずれを基準側へ折り返す
+/// - 折り返しても基準と一致しない候補は融合から除外し、その分だけ信頼度を下げる
+///   F0 = Σ(F_i * C_i) / Σ(C_i),  C = Σ_一致(C_i^2) / Σ_全体(C_i)
+pub fn fuse_candidates(candidates: &[PitchCandidate]) -> PitchCandidate {
+    let valid: Vec<&PitchCandidate> = candidates.iter()
+        .filter(|c| c.frequency.is_finite() && c.frequency > 0.0 && c.confidence > 0.0)
+        .collect();
+    let reference = match valid.iter().max_by(|a, b| {
+        a.confidence.partial_cmp(&b.confidence).unwrap_or(std::cmp::Ordering::Equal)
+    }) {
+        Some(c) => **c,
+        None => return PitchCandidate { frequency: 0.0, confidence: 0.0 },
+    };
+
+    let total_confidence: f32 = valid.iter().map(|c| c.confidence).sum();
+    let mut weighted_f0 = 0.0;
+    let mut agree_confidence = 0.0;
+    let mut agree_confidence_sq = 0.0;
+    for candidate in valid {
+        let octaves = (candidate.frequency / reference.frequency).log2();
+        let shift = octaves.round();
+        if (octaves - shift).abs() > OCTAVE_TOLERANCE {
+            continue;
+        }
+        let corrected = candidate.frequency / 2f32.powf(shift);
+        weighted_f0 += corrected * candidate.confidence;
+        agree_confidence += candidate.confidence;
+        agree_confidence_sq += candidate.confidence * candidate.confidence;
+    }
+
+    PitchCandidate {
+        frequency: weighted_f0 / agree_confidence,
+        confidence: (agree_confidence_sq / total_confidence).clamp(0.0, 1.0),
     }
 }
 
@@ -90,17 +340,17 @@ fn post_process_f0_curve(f0_curve: &mut [f32]) {
                     ];
                     let spline = Spline::from_vec(points);
 
-                    for j in start..end {
+                    for (j, f0) in f0_curve.iter_mut().enumerate().take(end).skip(start) {
                         if let Some(val) = spline.sample(j as f32) {
-                            f0_curve[j] = val;
+                            *f0 = val;
                         }
                     }
                 } else if p1_idx < p2_idx {
                     let p1 = f0_curve[p1_idx];
                     let p2 = f0_curve[p2_idx];
-                    for j in start..end {
+                    for (j, f0) in f0_curve.iter_mut().enumerate().take(end).skip(start) {
                         let t = (j - p1_idx) as f32 / (p2_idx - p1_idx) as f32;
-                        f0_curve[j] = p1 * (1.0 - t) + p2 * t;
+                        *f0 = p1 * (1.0 - t) + p2 * t;
                     }
                 }
             }
@@ -109,54 +359,259 @@ fn post_process_f0_curve(f0_curve: &mut [f32]) {
     }
 }
 
+/// F0推定の結果 (フレーム単位)
+#[derive(Debug, Clone, PartialEq)]
+pub struct F0Track {
+    pub f0_curve: Vec<f32>,   // 平滑化・補間後のF0 [Hz] (前後端の無声区間は0)
+    pub confidence: Vec<f32>, // 融合後の信頼度
+    pub voicing: Vec<bool>,   // 有声(true) / 無声(false) の判定
+}
+
+/// 1フレーム分のViterbi状態 (周波数と観測コスト) を列挙する
+/// - 信頼度が voicing_threshold 未満の候補は欠損扱いとして状態に含めない
+/// - オクターブ誤りを後段で修正できるよう、±1オクターブの候補も減衰した信頼度で加える
+fn expand_states(
+    raw: &[PitchCandidate],
+    fused: PitchCandidate,
+    params: &AnalysisParams,
+) -> Vec<(f32, f32)> {
+    let mut states = Vec::new();
+    for candidate in std::iter::once(&fused).chain(raw.iter()) {
+        for (ratio, penalty) in [(1.0, 1.0), (0.5, OCTAVE_PENALTY), (2.0, OCTAVE_PENALTY)] {
+            let frequency = candidate.frequency * ratio;
+            let confidence = candidate.confidence * penalty;
+            if confidence < params.voicing_threshold
+                || frequency < params.min_f0
+                || frequency > params.max_f0
+            {
+                continue;
+            }
+            states.push((frequency, 1.0 - confidence));
+        }
+    }
+    states
+}
+
+/// Viterbi法でフレームごとの候補から最も滑らかなF0経路を選ぶ
+/// - 状態: 各フレームの候補周波数 + 無声状態 (None)
+/// - 遷移コスト: 有声間は TRANSITION_WEIGHT * (Δf / f)^2、有声⇔無声は VOICING_SWITCH_COST
+/// - 観測コスト: 有声は 1 - 信頼度、無声は 1 - voicing_threshold
+pub fn viterbi_track(
+    raw_candidates: &[Vec<PitchCandidate>],
+    fused: &[PitchCandidate],
+    params: &AnalysisParams,
+) -> Vec<Option<f32>> {
+    let unvoiced_cost = 1.0 - params.voicing_threshold;
+    let frames: Vec<Vec<(f32, f32)>> = raw_candidates.iter()
+        .zip(fused.iter())
+        .map(|(raw, &fused)| expand_states(raw, fused, params))
+        .collect();
+    if frames.is_empty() {
+        return Vec::new();
+    }
+
+    let transition = |from: Option<f32>, to: Option<f32>| -> f32 {
+        match (from, to) {
+            (Some(a), Some(b)) => {
+                let relative = (b - a) / (0.5 * (a + b));
+                TRANSITION_WEIGHT * relative * relative
+            }
+            (None, None) => 0.0,
+            _ => VOICING_SWITCH_COST,
+        }
+    };
+    // 状態リストの末尾を無声状態とする
+    let state_freq = |frame: &[(f32, f32)], i: usize| -> Option<f32> { frame.get(i).map(|s| s.0) };
+    let state_cost = |frame: &[(f32, f32)], i: usize| -> f32 { frame.get(i).map_or(unvoiced_cost, |s| s.1) };
+
+    let mut cost: Vec<f32> = (0..=frames[0].len()).map(|i| state_cost(&frames[0], i)).collect();
+    let mut backpointers: Vec<Vec<usize>> = Vec::with_capacity(frames.len());
+    backpointers.push(vec![0; cost.len()]);
+
+    for t in 1..frames.len() {
+        let (prev, curr) = (&frames[t - 1], &frames[t]);
+        let mut next_cost = Vec::with_capacity(curr.len() + 1);
+        let mut pointers = Vec::with_capacity(curr.len() + 1);
+        for j in 0..=curr.len() {
+            let to = state_freq(curr, j);
+            let (best_i, best) = cost.iter()
+                .enumerate()
+                .map(|(i, &c)| (i, c + transition(state_freq(prev, i), to)))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                .unwrap_or((0, 0.0));
+            next_cost.push(best + state_cost(curr, j));
+            pointers.push(best_i);
+        }
+        cost = next_cost;
+        backpointers.push(pointers);
+    }
+
+    // 終端から経路を逆にたどる
+    let mut state = cost.iter()
+        .enumerate()
+        .min_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(i, _)| i)
+        .unwrap_or(0);
+    let mut path = vec![None; frames.len()];
+    for t in (0..frames.len()).rev() {
+        path[t] = state_freq(&frames[t], state);
+        state = backpointers[t][state];
+    }
+    path
+}
+
 /// F0の時系列データ（カーブ）を推定する
 pub fn estimate_f0_curve(
     audio: &[f32],
-    sample_rate: u32
-) -> Result<(Vec<f32>, Vec<f32>), String> {
-    println!("[INFO] F0 estimation started with robust fusion logic.");
-    if audio.len() < FRAME_SIZE {
-        return Err("Audio data is too short for F0 estimation.".to_string());
-    }
-    let mut yin_detector = YINDetector::<f32>::new(FRAME_SIZE, PADDING_SIZE);
-    let mut fft_planner: FftPlanner<f32> = FftPlanner::new();
+    sample_rate: u32,
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<F0Track, AnalyzeError> {
+    let mut estimators = build_estimators(params, sample_rate);
+    estimate_f0_curve_with(audio, sample_rate, params, &mut estimators, progress)
+}
+
+/// 任意の推定器の組でF0カーブを推定する
+/// - 推定器は build_estimators と同じフレーム長 (AnalysisParams::f0_frame_size) を前提とする
+pub fn estimate_f0_curve_with(
+    audio: &[f32],
+    sample_rate: u32,
+    params: &AnalysisParams,
+    estimators: &mut [Box<dyn PitchEstimator>],
+    progress: &AnalysisProgress,
+) -> Result<F0Track, AnalyzeError> {
+    let names: Vec<&str> = estimators.iter().map(|e| e.name()).collect();
+    log_message_internal("Rust", &format!("F0 estimation started with weighted fusion of {:?}.", names));
+    let frame_size = params.f0_frame_size(sample_rate);
+    if params.max_f0 >= sample_rate as f32 / 2.0 {
+        return Err(AnalyzeError::InvalidParams(format!(
+            "max_f0 ({} Hz) must be below the Nyquist frequency ({} Hz).",
+            params.max_f0,
+            sample_rate as f32 / 2.0
+        )));
+    }
+    if audio.len() < frame_size {
+        return Err(AnalyzeError::InputTooShort { needed: frame_size, actual: audio.len() });
+    }
+    if estimators.is_empty() {
+        return Err(AnalyzeError::InvalidParams("No pitch estimator is configured.".to_string()));
+    }
     
-    let mut f0_curve = Vec::new();
-    let mut confidence_curve = Vec::new();
-    let frames = audio.windows(FRAME_SIZE).step_by(HOP_SIZE);
+    let mut raw_candidates = Vec::new();
+    let mut fused_candidates = Vec::new();
+    let frames = audio.windows(frame_size).step_by(params.hop_size);
+    let num_frames = (audio.len() - frame_size) / params.hop_size + 1;
     
-    for frame in frames {
-        let yin_pitch = yin_detector.get_pitch(frame, sample_rate as usize, 0.1, 0.0);
-        
-        let (final_f0, final_confidence);
-
-        if let Some(p) = yin_pitch {
-            if p.clarity > 0.7 {
-                final_f0 = p.frequency;
-                final_confidence = p.clarity;
-            } else {
-                let (f0_cep, c_cep) = cepstrum_estimator(frame, sample_rate, &mut fft_planner);
-                if c_cep > p.clarity {
-                    final_f0 = f0_cep;
-                    final_confidence = c_cep;
-                } else {
-                    final_f0 = p.frequency;
-                    final_confidence = p.clarity;
-                }
-            }
-        } else {
-            let (f0_cep, c_cep) = cepstrum_estimator(frame, sample_rate, &mut fft_planner);
-            final_f0 = f0_cep;
-            final_confidence = c_cep;
-        }
-
-        f0_curve.push(final_f0);
-        confidence_curve.push(final_confidence);
+    for (i, frame) in frames.enumerate() {
+        progress.frame(i, num_frames)?;
+        let candidates: Vec<PitchCandidate> = estimators.iter_mut()
+            .filter_map(|estimator| estimator.estimate(frame, sample_rate))
+            .filter(|c| c.frequency >= params.min_f0 && c.frequency <= params.max_f0)
+            .collect();
+        fused_candidates.push(fuse_candidates(&candidates));
+        raw_candidates.push(candidates);
     }
 
-    println!("[INFO] Post-processing F0 curve with spline interpolation...");
+    let path = viterbi_track(&raw_candidates, &fused_candidates, params);
+    let voicing: Vec<bool> = path.iter().map(|f| f.is_some()).collect();
+    let mut f0_curve: Vec<f32> = path.iter().map(|f| f.unwrap_or(0.0)).collect();
+    let confidence: Vec<f32> = fused_candidates.iter().map(|c| c.confidence).collect();
+
     post_process_f0_curve(&mut f0_curve);
     
-    println!("[INFO] F0 estimation finished. Generated {} frames.", f0_curve.len());
-    Ok((f0_curve, confidence_curve))
-}
\ No newline at end of file
+    log_message_internal("Rust", &format!(
+        "F0 estimation finished. Generated {} frames ({} voiced).",
+        f0_curve.len(),
+        voicing.iter().filter(|&&v| v).count()
+    ));
+    Ok(F0Track { f0_curve, confidence, voicing })
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    #[test]
+    fn test_hps_on_harmonic_tone() {
+        // 1. Arrange: 基音220Hzに倍音を重ねた信号
+        let params = AnalysisParams::default();
+        let freq = 220.0;
+        let frame: Vec<f32> = (0..params.fft_size)
+            .map(|i| {
+                let phase = 2.0 * PI * freq * i as f32 / SAMPLE_RATE as f32;
+                (1..=6).map(|h| (h as f32 * phase).sin() / h as f32).sum()
+            })
+            .collect();
+
+        // 2. Act
+        let candidate = HpsEstimator::new(&params, params.fft_size).estimate(&frame, SAMPLE_RATE).unwrap();
+
+        // 3. Assert
+        assert!((candidate.frequency - freq).abs() < 2.0, "HPS estimated {} Hz", candidate.frequency);
+        assert!(candidate.confidence > 0.5);
+    }
+
+    #[test]
+    fn test_fusion_corrects_octave_error() {
+        // 1. Arrange: 低信頼度の推定器が1オクターブ下を返したケース
+        let candidates = [
+            PitchCandidate { frequency: 440.0, confidence: 0.9 },
+            PitchCandidate { frequency: 221.0, confidence: 0.3 },
+        ];
+
+        // 2. Act
+        let fused = fuse_candidates(&candidates);
+
+        // 3. Assert: 440Hz側に折り返して加重平均される
+        let expected = (440.0 * 0.9 + 442.0 * 0.3) / 1.2;
+        assert!((fused.frequency - expected).abs() < 1e-3);
+        assert!((fused.confidence - (0.81 + 0.09) / 1.2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_viterbi_rejects_octave_jump_and_low_confidence() {
+        // 1. Arrange: 220Hzが続く中に1フレームだけ440Hzの誤推定と、信頼度の低いフレームが混ざる
+        let params = AnalysisParams::default();
+        let mut fused: Vec<PitchCandidate> = (0..10)
+            .map(|_| PitchCandidate { frequency: 220.0, confidence: 0.9 })
+            .collect();
+        fused[4] = PitchCandidate { frequency: 440.0, confidence: 0.7 };
+        fused[8] = PitchCandidate { frequency: 300.0, confidence: 0.1 };
+        fused[9] = PitchCandidate { frequency: 310.0, confidence: 0.1 };
+        let raw: Vec<Vec<PitchCandidate>> = fused.iter().map(|&c| vec![c]).collect();
+
+        // 2. Act
+        let path = viterbi_track(&raw, &fused, &params);
+
+        // 3. Assert
+        assert_eq!(path[4], Some(220.0), "オクターブ誤りが補正されていません");
+        assert!(path[..8].iter().all(|f| *f == Some(220.0)));
+        assert_eq!(path[8], None, "信頼度0.2未満のフレームは無声になるべきです");
+        assert_eq!(path[9], None);
+    }
+
+    #[test]
+    fn test_sub_bass_tracking_with_scaled_frame() {
+        // 1. Arrange: 96kHzで30Hzのサブベース (デフォルトの2048サンプルでは1周期も入らない)
+        let sample_rate = 96000;
+        let freq = 30.0;
+        let params = AnalysisParams { min_f0: 20.0, max_f0: 200.0, ..Default::default() };
+        let signal: Vec<f32> = (0..sample_rate as usize)
+            .map(|i| {
+                let phase = 2.0 * PI * freq * i as f32 / sample_rate as f32;
+                phase.sin() + 0.5 * (2.0 * phase).sin()
+            })
+            .collect();
+
+        // 2. Act
+        let track = estimate_f0_curve(&signal, sample_rate, &params, &AnalysisProgress::default()).unwrap();
+
+        // 3. Assert
+        assert!(params.f0_frame_size(sample_rate) >= 3 * (sample_rate as f32 / params.min_f0) as usize);
+        let voiced: Vec<f32> = track.f0_curve.iter().cloned().filter(|&f| f > 0.0).collect();
+        let average = voiced.iter().sum::<f32>() / voiced.len() as f32;
+        assert!((average - freq).abs() < 1.0, "sub-bass estimated {} Hz", average);
+    }
+}
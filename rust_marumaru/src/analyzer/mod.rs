@@ -9,75 +9,358 @@ pub mod mode_freq;
 pub mod mode_hybrid;
 pub mod dynamic_pitch;
 pub mod quality;
+pub mod profile;
+pub mod resample;
+pub mod audio_file;
+pub mod progress;
+pub mod error;
 
 // ★ 修正点: 未使用の型を削除
-pub use self::types::{AnalysisResult};
+pub use self::types::{
+    AnalysisDomain, AnalysisParams, ChannelMode, CycleAlignment, AnalysisProfile, AnalysisResult, ModeCandidate,
+    ModeDecision, ModeReason, ModeSelection, MultichannelAnalysisResult, PitchEstimatorKind, QualityFlags, QualityMetrics,
+    SectionQuality, WindowType,
+};
+pub use self::error::AnalyzeError;
+pub use self::progress::{AnalysisProgress, AnalysisStage, CancelToken, ProgressCallback};
+use crate::log_message_internal;
 
 
+/// 解析に使うパラメータを返す
+/// - Autoの場合のみ、入力音から判定したプロファイルのプリセットを適用する
+/// - 明示したプロファイルのプリセットは AnalysisParams::from_profile で適用済みとみなし、呼び出し側の設定をそのまま使う
+fn resolve_profile(audio: &[f32], sample_rate: u32, params: &AnalysisParams) -> AnalysisParams {
+    let mut resolved = params.clone();
+    if params.profile == AnalysisProfile::Auto {
+        let detected = profile::detect_profile(audio, sample_rate, params);
+        log_message_internal("Rust", &format!("Auto profile detected: {:?}", detected));
+        resolved.apply_profile(detected);
+    }
+    resolved
+}
+
+
+/// 周期性の閾値からモードを選択する
+fn select_mode_by_periodicity(periodicity: f32, params: &AnalysisParams) -> ModeDecision {
+    let (mode, reason) = match periodicity {
+        p if p > params.time_mode_threshold => (AnalysisDomain::Time, ModeReason::HighPeriodicity),
+        p if p > params.hybrid_mode_threshold => (AnalysisDomain::Hybrid, ModeReason::MediumPeriodicity),
+        _ => (AnalysisDomain::Freq, ModeReason::LowPeriodicity),
+    };
+    ModeDecision { mode, reason, periodicity }
+}
+
+/// モード解析に渡す信号一式
+struct ModeInput<'a> {
+    original_audio: &'a [f32], // 品質検査の比較対象 (前処理前の原音)
+    original_f0: &'a [f32],    // 原音の時間軸でのF0カーブ (品質検査の再合成に使う)
+    original_voicing: &'a [bool], // original_f0 の有声/無声判定 (品質検査のNaN率に使う)
+    audio: &'a [f32],          // テーブルを切り出す信号 (前処理済み, DynamicPitchSync後)
+    f0_curve: &'a [f32],       // audio の時間軸でのF0カーブ
+}
+
+/// 解析全体 (0.0〜1.0) のうち各段階に割り当てる区間
+/// - ModeAnalysis 以降 (モード解析と品質検査) はチャンネル・モードごとに等分する
+const PREPROCESS_END: f32 = 0.05;
+const F0_END: f32 = 0.35;
+const PITCH_SYNC_END: f32 = 0.55;
+const MODE_SHARE: f32 = 0.7; // run_mode 1回分のうちモード解析の割合 (残りは品質検査)
+
+/// 指定モードでテーブルを生成し、品質検査まで行う
+fn run_mode(
+    mode: AnalysisDomain,
+    input: &ModeInput,
+    sample_rate: u32,
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<ModeCandidate, AnalyzeError> {
+    let mode_progress = progress.stage(AnalysisStage::ModeAnalysis, 0.0, MODE_SHARE)?;
+    let tables = match mode {
+        AnalysisDomain::Time => mode_time::analyze_time_domain(input.audio, sample_rate, input.f0_curve, params, &mode_progress)?,
+        AnalysisDomain::Hybrid => mode_hybrid::analyze_hybrid(input.audio, sample_rate, input.f0_curve, params, &mode_progress)?,
+        AnalysisDomain::Freq => mode_freq::analyze_freq_domain(input.audio, sample_rate, input.f0_curve, params, &mode_progress)?,
+    };
+
+    // 品質検査 (テーブルを原音のピッチ変化で再生し、原音と比較する)
+    let quality_progress = progress.stage(AnalysisStage::Quality, MODE_SHARE, 1.0)?;
+    let quality = quality::inspect_quality(
+        input.original_audio, &tables, input.original_f0, input.original_voicing, sample_rate, params, &quality_progress,
+    )?;
+
+    Ok(ModeCandidate { mode, tables, quality })
+}
+
+/// 全チャンネルで共有するピッチ解析の結果 (基準信号から一度だけ求める)
+struct SharedPitch {
+    track: f0_estimator::F0Track,                      // 原音の時間軸でのF0
+    target_f0: f32,                                    // DynamicPitchSync の基準ピッチ
+    pitch_contour: Vec<f32>,                           // target_f0 に対するピッチ変化 [cent]
+    synced: Option<dynamic_pitch::PitchSyncResult>,    // DynamicPitchSync の結果 (無効時は None)
+}
+
+/// 前処理済みの基準信号からF0を推定し、DynamicPitchSync まで行う
+fn analyze_shared_pitch(
+    processed_audio: &[f32],
+    sample_rate: u32,
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<SharedPitch, AnalyzeError> {
+    // 2. F0推定
+    let f0_progress = progress.stage(AnalysisStage::F0, PREPROCESS_END, F0_END)?;
+    let track = f0_estimator::estimate_f0_curve(processed_audio, sample_rate, params, &f0_progress)?;
+
+    // 3. DynamicPitchSync (ピッチを平均に平坦化し、F0を再推定する)
+    let (target_f0, pitch_contour) = dynamic_pitch::pitch_contour(&track.f0_curve)
+        .unwrap_or((0.0, vec![0.0; track.f0_curve.len()]));
+    let sync_progress = progress.stage(AnalysisStage::PitchSync, F0_END, PITCH_SYNC_END)?;
+    // ピッチや周期が取れない音 (ノイズ・金属音など) は平坦化せずに解析を続ける (キャンセルのみ中断する)
+    let synced = if params.pitch_sync {
+        match dynamic_pitch::apply_pitch_sync(processed_audio, sample_rate, &track.f0_curve, params, &sync_progress) {
+            Ok(synced) => Some(synced),
+            Err(AnalyzeError::Cancelled) => return Err(AnalyzeError::Cancelled),
+            Err(e) => {
+                log_message_internal("Rust", &format!("DynamicPitchSync skipped: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    Ok(SharedPitch { track, target_f0, pitch_contour, synced })
+}
+
 /// 音声データを解析するメイン関数（最終版）
 pub fn analyze_audio(
     audio_slice: &[f32],
     sample_rate: u32,
-    core_end_ratio: f32, 
-    release_start_ratio: f32, 
-) -> Result<AnalysisResult, String> {
+    params: &AnalysisParams,
+) -> Result<AnalysisResult, AnalyzeError> {
+    analyze_audio_with_progress(audio_slice, sample_rate, params, &AnalysisProgress::default())
+}
+
+/// 進捗を通知しながら音声データを解析する (キャンセルされた場合は AnalyzeError::Cancelled を返す)
+pub fn analyze_audio_with_progress(
+    audio_slice: &[f32],
+    sample_rate: u32,
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<AnalysisResult, AnalyzeError> {
+    params.validate()?;
+
+    // 0. プロファイルの解決
+    let preprocess_progress = progress.stage(AnalysisStage::Preprocess, 0.0, PREPROCESS_END)?;
+    let params = &resolve_profile(audio_slice, sample_rate, params);
 
     // 1. 前処理
-    println!("Applying preprocessing...");
-    let processed_audio = preprocess::apply_all_preprocessing(audio_slice)?;
+    preprocess_progress.update(0.5)?;
+    let processed_audio = preprocess::apply_all_preprocessing(audio_slice, params)?;
 
-    // 2. F0推定
-    println!("Estimating F0 curve...");
-    let (f0_curve, confidence) = f0_estimator::estimate_f0_curve(&processed_audio, sample_rate)?;
-    
-    // 3. 音響指標計算 (周期性 = F0の平均信頼度)
-    let periodicity = if !confidence.is_empty() {
-        confidence.iter().sum::<f32>() / confidence.len() as f32
+    // 2-3. F0推定と DynamicPitchSync
+    let pitch = analyze_shared_pitch(&processed_audio, sample_rate, params, progress)?;
+    let analysis_audio = pitch.synced.as_ref().map_or(&processed_audio[..], |synced| &synced.audio[..]);
+
+    let signal_progress = progress.stage(AnalysisStage::ModeAnalysis, PITCH_SYNC_END, 1.0)?;
+    analyze_signal(audio_slice, analysis_audio, &pitch, sample_rate, params, &signal_progress)
+}
+
+/// 複数チャンネルの音声を解析する
+/// - channels: チャンネルごとのサンプル列 (すべて同じ長さ)
+/// - F0推定と DynamicPitchSync は Mid (全チャンネルの平均) で一度だけ行い、
+///   各チャンネルは同じF0カーブ・同じ時間伸縮でテーブル化する (チャンネル間の位相を保つ)
+pub fn analyze_multichannel(
+    channels: &[&[f32]],
+    sample_rate: u32,
+    params: &AnalysisParams,
+) -> Result<MultichannelAnalysisResult, AnalyzeError> {
+    analyze_multichannel_with_progress(channels, sample_rate, params, &AnalysisProgress::default())
+}
+
+/// 進捗を通知しながら複数チャンネルの音声を解析する (キャンセルされた場合は AnalyzeError::Cancelled を返す)
+pub fn analyze_multichannel_with_progress(
+    channels: &[&[f32]],
+    sample_rate: u32,
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<MultichannelAnalysisResult, AnalyzeError> {
+    params.validate()?;
+    let num_samples = channels.first().map_or(0, |channel| channel.len());
+    if channels.is_empty() || num_samples == 0 {
+        return Err(AnalyzeError::EmptyInput);
+    }
+    if channels.iter().any(|channel| channel.len() != num_samples) {
+        return Err(AnalyzeError::ChannelMismatch("All channels must have the same length.".to_string()));
+    }
+    if params.channel_mode == ChannelMode::MidSide && channels.len() != 2 {
+        return Err(AnalyzeError::ChannelMismatch(format!("MidSide analysis requires 2 channels (got {}).", channels.len())));
+    }
+
+    // 0. Mid (全チャンネルの平均) を基準信号とし、プロファイルを解決する
+    let mid: Vec<f32> = (0..num_samples)
+        .map(|i| channels.iter().map(|channel| channel[i]).sum::<f32>() / channels.len() as f32)
+        .collect();
+    let preprocess_progress = progress.stage(AnalysisStage::Preprocess, 0.0, PREPROCESS_END)?;
+    let params = &resolve_profile(&mid, sample_rate, params);
+
+    // 1-3. 基準信号の前処理・F0推定・DynamicPitchSync
+    preprocess_progress.update(0.5)?;
+    let processed_mid = preprocess::apply_all_preprocessing(&mid, params)?;
+    let pitch = analyze_shared_pitch(&processed_mid, sample_rate, params, progress)?;
+
+    // 4. チャンネルモードに応じた解析対象の信号を用意する
+    let signals: Vec<Vec<f32>> = match params.channel_mode {
+        ChannelMode::MidOnly => vec![mid],
+        ChannelMode::PerChannel => channels.iter().map(|channel| channel.to_vec()).collect(),
+        ChannelMode::MidSide => {
+            let side = channels[0].iter().zip(channels[1]).map(|(&l, &r)| 0.5 * (l - r)).collect();
+            vec![mid, side]
+        }
+    };
+
+    // 5. 各信号を基準信号と同じF0・時間伸縮で解析する
+    let channels_progress = progress.stage(AnalysisStage::ModeAnalysis, PITCH_SYNC_END, 1.0)?;
+    let mut results = Vec::with_capacity(signals.len());
+    for (index, signal) in signals.iter().enumerate() {
+        log_message_internal("Rust", &format!("Analyzing channel {} of {} ({:?}).", index + 1, signals.len(), params.channel_mode));
+        let channel_progress = channels_progress.part(index, signals.len())?;
+        let processed = preprocess::apply_all_preprocessing(signal, params)?;
+        let analysis_audio = match &pitch.synced {
+            Some(synced) => dynamic_pitch::warp_to_target_pitch(
+                &processed,
+                &synced.source_cycles,
+                sample_rate as f32 / synced.target_f0,
+            ),
+            None => processed,
+        };
+        results.push(analyze_signal(signal, &analysis_audio, &pitch, sample_rate, params, &channel_progress)?);
+    }
+
+    Ok(MultichannelAnalysisResult { channel_mode: params.channel_mode, channels: results })
+}
+
+/// 音声ファイルを読み込んで解析する (WAV / AIFF / FLAC)
+/// - analysis_sample_rate が指定されていれば、その周波数へリサンプリングしてから解析する
+/// - モノラルのファイルはそのまま、複数チャンネルのファイルは channel_mode に従って解析する
+///   (MidOnly はダウンミックスした1チャンネルの結果になる)
+pub fn analyze_file<P: AsRef<std::path::Path>>(
+    path: P,
+    params: &AnalysisParams,
+) -> Result<MultichannelAnalysisResult, AnalyzeError> {
+    analyze_file_with_progress(path, params, &AnalysisProgress::default())
+}
+
+/// 進捗を通知しながら音声ファイルを解析する (キャンセルされた場合は AnalyzeError::Cancelled を返す)
+pub fn analyze_file_with_progress<P: AsRef<std::path::Path>>(
+    path: P,
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<MultichannelAnalysisResult, AnalyzeError> {
+    params.validate()?;
+    let mut decoded = audio_file::decode_file(path.as_ref())?;
+    if let Some(rate) = params.analysis_sample_rate {
+        if rate != decoded.sample_rate {
+            progress.check_cancelled()?;
+            log_message_internal("Rust", &format!("Resampling {} Hz -> {} Hz.", decoded.sample_rate, rate));
+            decoded = decoded.resample(rate);
+        }
+    }
+
+    if decoded.channels.len() == 1 {
+        let result = analyze_audio_with_progress(&decoded.channels[0], decoded.sample_rate, params, progress)?;
+        return Ok(MultichannelAnalysisResult { channel_mode: ChannelMode::MidOnly, channels: vec![result] });
+    }
+    let channels: Vec<&[f32]> = decoded.channels.iter().map(|channel| &channel[..]).collect();
+    analyze_multichannel_with_progress(&channels, decoded.sample_rate, params, progress)
+}
+
+/// 1本の信号をテーブル化する (モード判定・品質検査・セクション分割)
+/// - audio_slice: 前処理前の原音 (品質検査とゲインカーブに使う)
+/// - analysis_audio: テーブルを切り出す信号 (前処理済み, DynamicPitchSync 後)
+fn analyze_signal(
+    audio_slice: &[f32],
+    analysis_audio: &[f32],
+    pitch: &SharedPitch,
+    sample_rate: u32,
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<AnalysisResult, AnalyzeError> {
+    let (analysis_f0, analysis_confidence) = match &pitch.synced {
+        Some(result) => (&result.track.f0_curve[..], &result.track.confidence[..]),
+        None => (&pitch.track.f0_curve[..], &pitch.track.confidence[..]),
+    };
+    let mode_input = ModeInput {
+        original_audio: audio_slice,
+        original_f0: &pitch.track.f0_curve,
+        original_voicing: &pitch.track.voicing,
+        audio: analysis_audio,
+        f0_curve: analysis_f0,
+    };
+
+    // 4. 音響指標計算 (周期性 = テーブルを切り出す信号でのF0の平均信頼度)
+    let periodicity = if !analysis_confidence.is_empty() {
+        analysis_confidence.iter().sum::<f32>() / analysis_confidence.len() as f32
     } else {
         0.0 // F0が全く検出できなかった場合
     };
-    println!("[INFO] Acoustic feature calculated. Periodicity = {:.3}", periodicity);
+    log_message_internal("Rust", &format!("Acoustic feature calculated. Periodicity = {:.3}", periodicity));
 
-    // 4. モード判定と実行
-    let tables = match periodicity {
-        p if p > 0.6 => {
-            println!("[INFO] Mode selected: Time Domain (High Periodicity)");
-            mode_time::analyze_time_domain(&processed_audio, sample_rate, &f0_curve)?
+    // 5. モード判定と実行 (+ 品質検査)
+    let forced = |mode| ModeDecision { mode, reason: ModeReason::Forced, periodicity };
+    let (mode_decision, mode_candidates) = match params.mode_selection {
+        ModeSelection::RankAll => {
+            let modes = [AnalysisDomain::Time, AnalysisDomain::Hybrid, AnalysisDomain::Freq];
+            let mut candidates = Vec::new();
+            for (index, mode) in modes.into_iter().enumerate() {
+                match run_mode(mode, &mode_input, sample_rate, params, &progress.part(index, modes.len())?) {
+                    Ok(candidate) => candidates.push(candidate),
+                    Err(AnalyzeError::Cancelled) => return Err(AnalyzeError::Cancelled),
+                    Err(e) => log_message_internal("Rust", &format!("Warning: {:?} mode failed during ranking: {}", mode, e)),
+                }
+            }
+            candidates.sort_by(|a, b| {
+                b.quality.score().partial_cmp(&a.quality.score()).unwrap_or(std::cmp::Ordering::Equal)
+            });
+            let best = candidates.first()
+                .ok_or_else(|| AnalyzeError::AnalysisFailed("All analysis modes failed.".to_string()))?;
+            let decision = ModeDecision { mode: best.mode, reason: ModeReason::BestQuality, periodicity };
+            (decision, candidates)
         },
-        p if p > 0.35 => {
-            println!("[INFO] Mode selected: Hybrid (Medium Periodicity)");
-            mode_hybrid::analyze_hybrid(&processed_audio, sample_rate, &f0_curve)?
+        selection => {
+            let decision = match selection {
+                ModeSelection::Time => forced(AnalysisDomain::Time),
+                ModeSelection::Hybrid => forced(AnalysisDomain::Hybrid),
+                ModeSelection::Freq => forced(AnalysisDomain::Freq),
+                _ => select_mode_by_periodicity(periodicity, params),
+            };
+            let candidate = run_mode(decision.mode, &mode_input, sample_rate, params, progress)?;
+            (decision, vec![candidate])
         },
-        _ => {
-            println!("[INFO] Mode selected: Frequency Domain (Low Periodicity)");
-            mode_freq::analyze_freq_domain(&processed_audio, sample_rate)?
-        }
     };
-    
-    // 5. DynamicPitchSync (ピッチ揺れ正規化)
-    let final_tables = dynamic_pitch::apply_pitch_sync(&tables, &f0_curve)?;
+    log_message_internal("Rust", &format!("Mode selected: {}", mode_decision.describe()));
+
+    let final_tables = mode_candidates[0].tables.clone();
+    let quality_metrics = mode_candidates[0].quality.clone();
     
     // ★ 5.5. 振幅プロファイルの抽出と分割ロジック
     // 5.5.1. 振幅プロファイル（RMS）の抽出
     let mut amp_curve = Vec::new();
-    let window_size = 512; 
+    let window_size = params.hop_size;
     for chunk in audio_slice.chunks(window_size) { 
         let rms = chunk.iter().map(|&s| s * s).sum::<f32>() / chunk.len() as f32;
         amp_curve.push(rms.sqrt());
     }
     
     // 5.5.2. ゲインカーブをリサンプリングし、波形と同じ長さに正規化
-    let total_len = final_tables.get(0).map_or(0, |t| t.len());
+    let total_len = final_tables.first().map_or(0, |t| t.len());
     let resampled_gain = if total_len > 0 && !amp_curve.is_empty() {
         // RMSカーブを波形長に線形リサンプリング
         let mut resampled = vec![0.0; total_len];
         let amp_curve_len = amp_curve.len() as f32;
-        for i in 0..total_len {
+        for (i, out) in resampled.iter_mut().enumerate() {
             let index_f = i as f32 / total_len as f32 * amp_curve_len;
             let idx0 = index_f.floor() as usize;
             let idx1 = (idx0 + 1).min(amp_curve.len().saturating_sub(1));
             let frac = index_f - idx0 as f32;
-            resampled[i] = amp_curve[idx0] * (1.0 - frac) + amp_curve[idx1] * frac;
+            *out = amp_curve[idx0] * (1.0 - frac) + amp_curve[idx1] * frac;
         }
         resampled
     } else {
@@ -85,8 +368,8 @@ pub fn analyze_audio(
     };
 
     // 5.5.3. Core/Loop/Releaseのインデックスを計算
-    let core_end_ratio = core_end_ratio.clamp(0.0, 1.0);
-    let release_start_ratio = release_start_ratio.clamp(0.0, 1.0);
+    let core_end_ratio = params.core_end_ratio.clamp(0.0, 1.0);
+    let release_start_ratio = params.release_start_ratio.clamp(0.0, 1.0);
     
     let core_end_idx = (total_len as f32 * core_end_ratio).round() as usize;
     let release_start_idx = (total_len as f32 * release_start_ratio).round() as usize;
@@ -95,8 +378,8 @@ pub fn analyze_audio(
     let safe_release_start_idx = release_start_idx.max(core_end_idx);
 
     // 5.5.4. 波形とゲインカーブを分割
-    let main_table = final_tables.get(0)
-        .ok_or_else(|| "Final table is empty. Cannot split sections.".to_string())?;
+    let main_table = final_tables.first()
+        .ok_or_else(|| AnalyzeError::AnalysisFailed("Final table is empty. Cannot split sections.".to_string()))?;
 
     let core_wave = main_table[0..safe_core_end_idx.min(total_len)].to_vec();
     let loop_wave = main_table[safe_core_end_idx.min(total_len)..safe_release_start_idx.min(total_len)].to_vec();
@@ -109,25 +392,30 @@ pub fn analyze_audio(
     // ★ 5.5.5. 「必ずゼロから始まる」条件を強制 (Coreゲインカーブの最初の10サンプルを滑らかにゼロから立ち上げる)
     if !core_gain.is_empty() {
         let fade_len = core_gain.len().min(10);
-        for i in 0..fade_len {
+        for (i, gain) in core_gain.iter_mut().take(fade_len).enumerate() {
             let t = i as f32 / fade_len as f32;
-            core_gain[i] *= t; 
-            if i == 0 { core_gain[i] = 0.0; } // 念のため最初のサンプルはゼロ保証
+            *gain *= t; 
+            if i == 0 { *gain = 0.0; } // 念のため最初のサンプルはゼロ保証
         }
     }
     
-    // 6. 品質検査
-    let quality_metrics = quality::inspect_quality(
-        audio_slice,
-        &final_tables,
-        &f0_curve,
-        sample_rate
-    )?;
-    
+    // 5.6. 原音の周期境界 (DynamicPitchSync を使った場合は平坦化に使った境界)
+    let cycle_boundaries = match &pitch.synced {
+        Some(result) => result.source_cycles.clone(),
+        None => mode_time::detect_cycle_boundaries(audio_slice, sample_rate, &pitch.track.f0_curve, params)
+            .unwrap_or_default(),
+    };
+
     // 最終的な解析結果を返す
     Ok(AnalysisResult {
-        f0_curve,
-        confidence,
+        sample_rate,
+        f0_curve: pitch.track.f0_curve.clone(),
+        confidence: pitch.track.confidence.clone(),
+        voicing: pitch.track.voicing.clone(),
+        target_f0: pitch.target_f0,
+        pitch_contour: pitch.pitch_contour.clone(),
+        pitch_contour_rate: sample_rate as f32 / params.hop_size as f32,
+        cycle_boundaries,
         tables: final_tables,
         core_wave,
         loop_wave,
@@ -136,5 +424,34 @@ pub fn analyze_audio(
         loop_gain,
         release_gain,
         quality: quality_metrics,
+        profile: params.profile,
+        mode_decision,
+        mode_candidates,
     })
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_pitch_skips_pitch_sync_on_noise() {
+        // 1. Arrange: 周期のないノイズ (デフォルトでは DynamicPitchSync が有効)
+        const SAMPLE_RATE: u32 = 48000;
+        let params = AnalysisParams::default();
+        let mut seed = 1u32;
+        let noise: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+            })
+            .collect();
+        assert!(params.pitch_sync);
+
+        // 2. Act
+        let pitch = analyze_shared_pitch(&noise, SAMPLE_RATE, &params, &AnalysisProgress::default());
+
+        // 3. Assert: DynamicPitchSync の失敗で解析全体を止めず、平坦化なしで続ける
+        let pitch = pitch.expect("DynamicPitchSync の失敗で解析が中断されました");
+        assert!(pitch.synced.is_none());
+    }
+}
@@ -1,79 +1,292 @@
 // src/analyzer/mode_freq.rs
 
+use crate::log_message_internal;
 use rustfft::{FftPlanner, num_complex::Complex};
 use std::f32::consts::PI;
+use super::error::AnalyzeError;
+use super::progress::{AnalysisProgress, AnalysisStage};
+use super::types::AnalysisParams;
 
-// STFTのパラメータ
-const FFT_SIZE: usize = 2048;
-const HOP_SIZE: usize = 512; // 75% overlap
+// --- 位相ボコーダのパラメータ ---
+const PEAK_FLOOR_DB : f32 = -80.0; // フレーム最大値からこのdB以下のピークは無視する
 
-/// 周波数領域での音声解析を行う (スペクトル平均化 + 位相ロック実装版)
-pub fn analyze_freq_domain(
+/// 位相を -π〜π に折り返す
+fn wrap_phase(phase: f32) -> f32 {
+    (phase + PI).rem_euclid(2.0 * PI) - PI
+}
+
+/// 1フレーム分の位相ボコーダ解析結果
+struct PvFrame {
+    magnitude: Vec<f32>,  // 振幅スペクトル (0〜N/2)
+    phase: Vec<f32>,      // フレーム中心を基準とした位相
+    inst_freq: Vec<f32>,  // 瞬時周波数 [Hz]
+}
+
+/// ゼロ位相窓 (フレーム中心を原点に回転) でSTFTを行い、前フレームとの位相差から瞬時周波数を求める
+/// - φ'(k,n) = φ(k,n-1) + ω_k * hop との差分を -π〜π に折り返して ω_k を補正する
+fn phase_vocoder_analysis(
     audio: &[f32],
-    _sample_rate: u32,
-) -> Result<Vec<Vec<f32>>, String> {
-    println!("[INFO] Frequency domain analysis with Spectrum Averaging + Phase Locking started.");
+    sample_rate: u32,
+    frame_size: usize,
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<Vec<PvFrame>, AnalyzeError> {
+    let hop = params.hop_size;
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(frame_size);
+    let window = params.window.generate(frame_size);
+    let num_bins = frame_size / 2 + 1;
 
-    if audio.len() < FFT_SIZE {
-        return Err("Audio data is too short for frequency domain analysis.".to_string());
+    let num_frames = (audio.len().saturating_sub(frame_size)) / hop + 1;
+    let mut frames: Vec<PvFrame> = Vec::new();
+    for (i, frame) in audio.windows(frame_size).step_by(hop).enumerate() {
+        progress.frame(i, num_frames)?;
+        let mut buffer: Vec<Complex<f32>> = frame.iter()
+            .zip(window.iter())
+            .map(|(&sample, &win)| Complex::new(sample * win, 0.0))
+            .collect();
+        buffer.rotate_left(frame_size / 2);
+        fft.process(&mut buffer);
+
+        let magnitude: Vec<f32> = buffer[..num_bins].iter().map(|c| c.norm()).collect();
+        let phase: Vec<f32> = buffer[..num_bins].iter().map(|c| c.arg()).collect();
+        let inst_freq: Vec<f32> = (0..num_bins)
+            .map(|k| {
+                let omega = 2.0 * PI * k as f32 / frame_size as f32;
+                match frames.last() {
+                    Some(prev) => {
+                        let deviation = wrap_phase(phase[k] - prev.phase[k] - omega * hop as f32);
+                        (omega + deviation / hop as f32) * sample_rate as f32 / (2.0 * PI)
+                    }
+                    None => k as f32 * sample_rate as f32 / frame_size as f32,
+                }
+            })
+            .collect();
+        frames.push(PvFrame { magnitude, phase, inst_freq });
     }
+    Ok(frames)
+}
 
-    let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(FFT_SIZE);
-    let ifft = planner.plan_fft_inverse(FFT_SIZE);
+/// スペクトルピークを検出し、各ビンを最寄りのピークの領域に割り当てる (Identity Phase Locking の領域分割)
+/// - 戻り値: (ピークのビン番号, 領域 [start, end))
+fn find_peak_regions(magnitude: &[f32]) -> Vec<(usize, usize, usize)> {
+    let max_mag = magnitude.iter().cloned().fold(0.0, f32::max);
+    let floor = max_mag * 10f32.powf(PEAK_FLOOR_DB / 20.0);
+    let peaks: Vec<usize> = (1..magnitude.len().saturating_sub(1))
+        .filter(|&k| magnitude[k] > floor && magnitude[k] > magnitude[k - 1] && magnitude[k] >= magnitude[k + 1])
+        .collect();
 
-    let window: Vec<f32> = (0..FFT_SIZE)
-        .map(|i| 0.5 * (1.0 - (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos()))
+    // 隣り合うピークの間で振幅が最小となるビンを領域の境界とする
+    let mut regions = Vec::with_capacity(peaks.len());
+    let mut start = 0;
+    for (i, &peak) in peaks.iter().enumerate() {
+        let end = match peaks.get(i + 1) {
+            Some(&next) => (peak..next)
+                .min_by(|&a, &b| magnitude[a].partial_cmp(&magnitude[b]).unwrap_or(std::cmp::Ordering::Equal))
+                .unwrap_or(peak) + 1,
+            None => magnitude.len(),
+        };
+        regions.push((peak, start, end));
+        start = end;
+    }
+    regions
+}
+
+/// f0 付近で最も強いピーク領域を返す
+fn nearest_peak<'a>(
+    frame: &PvFrame,
+    regions: &'a [(usize, usize, usize)],
+    target: f32,
+    f0: f32,
+) -> Option<&'a (usize, usize, usize)> {
+    regions.iter()
+        .filter(|(peak, _, _)| (frame.inst_freq[*peak] - target).abs() < 0.5 * f0)
+        .max_by(|a, b| frame.magnitude[a.0].partial_cmp(&frame.magnitude[b.0]).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+/// 1フレームから倍音ごとの複素係数 (振幅, 基音に対する相対位相) を取り出す
+/// - 振幅はピーク領域全体のエネルギー、位相はピークビンの位相 (領域内のビンはピークに位相ロックされる)
+/// - 基音が上昇ゼロクロス (sin) から始まるよう、各倍音の位相を h 倍で回転させて周期の先頭を揃える
+/// - 基音の周波数と位相は reference から取る (帯域分割後の高域のように基音を含まない信号にも使えるように)
+fn extract_harmonics(
+    frame: &PvFrame,
+    reference: &PvFrame,
+    f0: f32,
+    max_harmonics: usize,
+) -> Option<Vec<Complex<f32>>> {
+    // 基音の瞬時周波数で F0 を精密化する
+    let reference_regions = find_peak_regions(&reference.magnitude);
+    let fundamental = nearest_peak(reference, &reference_regions, f0, f0)?;
+    let f1 = reference.inst_freq[fundamental.0];
+    let phase_shift = -PI / 2.0 - reference.phase[fundamental.0];
+
+    let regions = find_peak_regions(&frame.magnitude);
+    let harmonics = (1..=max_harmonics)
+        .map(|h| match nearest_peak(frame, &regions, h as f32 * f1, f1) {
+            Some(&(peak, start, end)) => {
+                let amplitude = frame.magnitude[start..end].iter().map(|m| m * m).sum::<f32>().sqrt();
+                let phase = wrap_phase(frame.phase[peak] + h as f32 * phase_shift);
+                Complex::from_polar(amplitude, phase)
+            }
+            None => Complex::default(),
+        })
         .collect();
+    Some(harmonics)
+}
+
+/// 倍音係数から1周期分の波形 (長さ table_len) を合成する
+fn synthesize_cycle(harmonics: &[Complex<f32>], table_len: usize, planner: &mut FftPlanner<f32>) -> Vec<f32> {
+    let mut spectrum = vec![Complex::default(); table_len];
+    for (i, &coeff) in harmonics.iter().enumerate().take(table_len / 2 - 1) {
+        let h = i + 1;
+        spectrum[h] = coeff * (table_len as f32 / 2.0);
+        spectrum[table_len - h] = spectrum[h].conj();
+    }
+    planner.plan_fft_inverse(table_len).process(&mut spectrum);
+    spectrum.iter().map(|c| c.re / table_len as f32).collect()
+}
 
-    let frames = audio.windows(FFT_SIZE).step_by(HOP_SIZE);
-    let spectrogram: Vec<Vec<Complex<f32>>> = frames.map(|frame| {
+/// 全フレームの振幅スペクトルを平均し、位相をそろえた1周期分のテーブル (長さ table_len) を作る
+/// - F0 が得られない場合の代替: table_len サンプルを1周期とみなし、各ビンを倍音として扱う
+fn averaged_spectrum_table(audio: &[f32], table_len: usize, params: &AnalysisParams, planner: &mut FftPlanner<f32>) -> Vec<f32> {
+    let mut padded = audio.to_vec();
+    if padded.len() < table_len {
+        padded.resize(table_len, 0.0);
+    }
+    let fft = planner.plan_fft_forward(table_len);
+    let window = params.window.generate(table_len);
+
+    let num_bins = table_len / 2 + 1;
+    let mut avg_magnitudes = vec![0.0; num_bins];
+    let mut frame_count = 0;
+    for frame in padded.windows(table_len).step_by(params.hop_size) {
         let mut buffer: Vec<Complex<f32>> = frame.iter()
             .zip(window.iter())
             .map(|(&sample, &win)| Complex::new(sample * win, 0.0))
             .collect();
         fft.process(&mut buffer);
-        buffer
-    }).collect();
-
-    if spectrogram.is_empty() {
-        return Err("Could not generate spectrogram from the audio.".to_string());
+        for (avg, bin) in avg_magnitudes.iter_mut().zip(buffer.iter()) {
+            *avg += bin.norm();
+        }
+        frame_count += 1;
     }
 
-    let mut avg_magnitudes = vec![0.0; FFT_SIZE / 2 + 1];
-    for frame_spec in &spectrogram {
-        for i in 0..=FFT_SIZE / 2 {
-            avg_magnitudes[i] += frame_spec[i].norm();
+    // 直流を除き、すべての成分を sin の位相 (-π/2) にそろえて合成する
+    let harmonics: Vec<Complex<f32>> = avg_magnitudes[1..num_bins - 1].iter()
+        .map(|&mag| Complex::from_polar(mag / frame_count as f32, -PI / 2.0))
+        .collect();
+    let mut table = synthesize_cycle(&harmonics, table_len, planner);
+    let max_abs = table.iter().map(|&s| s.abs()).fold(0.0, f32::max);
+    if max_abs > 1e-6 {
+        for sample in table.iter_mut() {
+            *sample /= max_abs;
         }
     }
-    for mag in &mut avg_magnitudes {
-        *mag /= spectrogram.len() as f32;
+    table
+}
+
+/// 周波数領域での音声解析を行う (位相ボコーダ + Identity Phase Locking)
+/// - 各フレームの倍音を検出周期 (F0) に同期させた1周期へ変換し、target_cycle_len でテーブル化する
+/// - freq_table_count 個の区間に分けて平均し、時間変化するテーブル列を返す
+pub fn analyze_freq_domain(
+    audio: &[f32],
+    sample_rate: u32,
+    f0_curve: &[f32],
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<Vec<Vec<f32>>, AnalyzeError> {
+    analyze_freq_domain_with_reference(audio, audio, sample_rate, f0_curve, params, progress)
+}
+
+/// 基音の位相基準を別の信号から取って周波数領域解析を行う
+/// - reference_audio は audio と同じ長さ・同じ時間軸であること (Hybridモードでは帯域分割前の信号を渡す)
+pub fn analyze_freq_domain_with_reference(
+    audio: &[f32],
+    reference_audio: &[f32],
+    sample_rate: u32,
+    f0_curve: &[f32],
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<Vec<Vec<f32>>, AnalyzeError> {
+
+    // 低いF0でも倍音が分離できるよう、F0推定と同じく min_f0 に応じてフレーム長を伸ばす
+    let frame_size = params.f0_frame_size(sample_rate);
+    if audio.len() < frame_size {
+        return Err(AnalyzeError::InputTooShort { needed: frame_size, actual: audio.len() });
+    }
+    if reference_audio.len() != audio.len() {
+        return Err(AnalyzeError::AnalysisFailed("Reference audio must have the same length as the analyzed audio.".to_string()));
     }
 
-    let mut final_spectrum = vec![Complex::default(); FFT_SIZE];
-    let locked_phase = -PI / 2.0;
+    // 1. 位相ボコーダ解析 (瞬時周波数の推定)
+    let reference_progress = if std::ptr::eq(audio, reference_audio) { 1.0 } else { 0.5 };
+    let frames = phase_vocoder_analysis(audio, sample_rate, frame_size, params,
+        &progress.stage(AnalysisStage::ModeAnalysis, 0.0, 0.9 * reference_progress)?)?;
+    if frames.is_empty() {
+        return Err(AnalyzeError::AnalysisFailed("Could not generate spectrogram from the audio.".to_string()));
+    }
+    let reference_frames = if std::ptr::eq(audio, reference_audio) {
+        None
+    } else {
+        Some(phase_vocoder_analysis(reference_audio, sample_rate, frame_size, params,
+            &progress.stage(AnalysisStage::ModeAnalysis, 0.45, 0.9)?)?)
+    };
 
-    for i in 0..=FFT_SIZE / 2 {
-        let new_complex = Complex::from_polar(avg_magnitudes[i], locked_phase);
-        final_spectrum[i] = new_complex;
-        if i > 0 && i < FFT_SIZE / 2 {
-            final_spectrum[FFT_SIZE - i] = new_complex.conj();
-        }
+    // 2. フレームごとに倍音係数を取り出す (無声フレームは除外)
+    let table_len = params.target_cycle_len;
+    let harmonic_frames: Vec<(usize, Vec<Complex<f32>>)> = frames.iter()
+        .enumerate()
+        .filter_map(|(i, frame)| {
+            let f0 = f0_curve.get(i).or(f0_curve.last()).cloned().unwrap_or(0.0);
+            if f0 <= 0.0 {
+                return None;
+            }
+            let max_harmonics = ((sample_rate as f32 / 2.0 / f0) as usize).min(table_len / 2 - 1);
+            let reference = reference_frames.as_ref().map_or(frame, |r| &r[i]);
+            extract_harmonics(frame, reference, f0, max_harmonics).map(|h| (i, h))
+        })
+        .collect();
+    if harmonic_frames.is_empty() {
+        // 有声フレームがない音 (ノイズ・打楽器など) は周期に同期できないため、固定長の平均スペクトルから作る
+        log_message_internal("Rust", "No voiced frames for frequency domain analysis. Using the averaged spectrum table.");
+        return Ok(vec![averaged_spectrum_table(audio, table_len, params, &mut FftPlanner::new())]);
     }
 
-    ifft.process(&mut final_spectrum);
-    // ★ 修正点: analyze_freq_domain本体のiFFT後にも正規化を追加
-    let mut final_table: Vec<f32> = final_spectrum.iter().map(|c| c.re / FFT_SIZE as f32).collect();
+    // 3. 区間ごとに倍音係数を平均し、1周期のテーブルを合成する
+    let mut planner = FftPlanner::new();
+    let segment_count = params.freq_table_count.max(1);
+    let mut tables = Vec::with_capacity(segment_count);
+    for segment in 0..segment_count {
+        progress.update(0.9 + 0.1 * segment as f32 / segment_count as f32)?;
+        let begin = segment * frames.len() / segment_count;
+        let end = (segment + 1) * frames.len() / segment_count;
+        let members: Vec<&Vec<Complex<f32>>> = harmonic_frames.iter()
+            .filter(|(i, _)| *i >= begin && *i < end)
+            .map(|(_, h)| h)
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        let num_harmonics = members.iter().map(|h| h.len()).max().unwrap_or(0);
+        let mut averaged = vec![Complex::default(); num_harmonics];
+        for harmonics in &members {
+            for (acc, coeff) in averaged.iter_mut().zip(harmonics.iter()) {
+                *acc += coeff / members.len() as f32;
+            }
+        }
 
-    let max_abs = final_table.iter().map(|&s| s.abs()).fold(0.0, f32::max);
-    if max_abs > 1e-6 {
-        for sample in final_table.iter_mut() {
-            *sample /= max_abs;
+        let mut table = synthesize_cycle(&averaged, table_len, &mut planner);
+        let max_abs = table.iter().map(|&s| s.abs()).fold(0.0, f32::max);
+        if max_abs > 1e-6 {
+            for sample in table.iter_mut() {
+                *sample /= max_abs;
+            }
         }
+        tables.push(table);
     }
 
-    println!("[INFO] Frequency domain analysis finished. Generated 1 wavetable.");
-    Ok(vec![final_table])
+    log_message_internal("Rust", &format!("Frequency domain analysis finished. Generated {} wavetable(s).", tables.len()));
+    Ok(tables)
 }
 
 
@@ -81,13 +294,14 @@ pub fn analyze_freq_domain(
 mod tests {
     use super::*;
 
-    fn stft_istft_roundtrip(audio: &[f32]) -> Vec<f32> {
+    fn stft_istft_roundtrip(audio: &[f32], params: &AnalysisParams) -> Vec<f32> {
+        let fft_size = params.fft_size;
         let mut planner = FftPlanner::new();
-        let fft = planner.plan_fft_forward(FFT_SIZE);
-        let ifft = planner.plan_fft_inverse(FFT_SIZE);
-        let window: Vec<f32> = (0..FFT_SIZE).map(|i| 0.5 * (1.0 - (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos())).collect();
+        let fft = planner.plan_fft_forward(fft_size);
+        let ifft = planner.plan_fft_inverse(fft_size);
+        let window = params.window.generate(fft_size);
 
-        let frames = audio.windows(FFT_SIZE).step_by(HOP_SIZE);
+        let frames = audio.windows(fft_size).step_by(params.hop_size);
         let spectrogram: Vec<Vec<Complex<f32>>> = frames.map(|frame| {
             let mut buffer: Vec<Complex<f32>> = frame.iter().zip(&window).map(|(&s, &w)| Complex::new(s * w, 0.0)).collect();
             fft.process(&mut buffer);
@@ -98,10 +312,10 @@ mod tests {
         let mut window_sum = vec![0.0; audio.len()];
         for (i, mut frame) in spectrogram.into_iter().enumerate() {
             ifft.process(&mut frame);
-            let start = i * HOP_SIZE;
-            for j in 0..FFT_SIZE {
+            let start = i * params.hop_size;
+            for j in 0..fft_size {
                 if start + j < output_audio.len() {
-                    output_audio[start + j] += (frame[j].re / FFT_SIZE as f32) * window[j];
+                    output_audio[start + j] += (frame[j].re / fft_size as f32) * window[j];
                     window_sum[start + j] += window[j].powi(2);
                 }
             }
@@ -127,11 +341,12 @@ mod tests {
             signal.push(sample);
         }
 
-        let reconstructed_signal = stft_istft_roundtrip(&signal);
+        let params = AnalysisParams::default();
+        let reconstructed_signal = stft_istft_roundtrip(&signal, &params);
 
         let mut signal_power = 0.0;
         let mut error_power = 0.0;
-        for i in FFT_SIZE..SIGNAL_LEN - FFT_SIZE { // 信号の末尾も不安定なので無視する
+        for i in params.fft_size..SIGNAL_LEN - params.fft_size { // 信号の末尾も不安定なので無視する
             signal_power += signal[i].powi(2);
             error_power += (signal[i] - reconstructed_signal[i]).powi(2);
         }
@@ -144,4 +359,89 @@ mod tests {
             snr
         );
     }
-}
\ No newline at end of file
+
+    /// 周期を揃えた比較 (巡回シフトの中で最大の正規化相関)
+    fn best_cyclic_correlation(a: &[f32], b: &[f32]) -> f32 {
+        (0..a.len())
+            .map(|shift| {
+                let dot: f32 = (0..a.len()).map(|j| a[j] * b[(j + shift) % b.len()]).sum();
+                let energy = (a.iter().map(|x| x * x).sum::<f32>() * b.iter().map(|x| x * x).sum::<f32>()).sqrt();
+                dot / energy
+            })
+            .fold(f32::MIN, f32::max)
+    }
+
+    #[test]
+    fn test_phase_vocoder_preserves_harmonic_shape() {
+        // 1. Arrange: 位相の異なる3つの倍音を持つ220Hzの信号
+        const SAMPLE_RATE: u32 = 48000;
+        let params = AnalysisParams::default();
+        let shape = |p: f32| p.sin() + 0.5 * (2.0 * p + 1.0).sin() + 0.3 * (3.0 * p - 0.5).sin();
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| shape(2.0 * PI * 220.0 * i as f32 / SAMPLE_RATE as f32))
+            .collect();
+        let f0_curve = vec![220.0; SAMPLE_RATE as usize / params.hop_size];
+
+        // 2. Act
+        let tables = analyze_freq_domain(&signal, SAMPLE_RATE, &f0_curve, &params, &AnalysisProgress::default()).unwrap();
+
+        // 3. Assert: テーブル長は target_cycle_len で、倍音の相対位相まで保たれている
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].len(), params.target_cycle_len);
+        let reference: Vec<f32> = (0..params.target_cycle_len)
+            .map(|j| shape(2.0 * PI * j as f32 / params.target_cycle_len as f32))
+            .collect();
+        let corr = best_cyclic_correlation(&tables[0], &reference);
+        assert!(corr > 0.99, "cycle correlation = {}", corr);
+    }
+
+    #[test]
+    fn test_time_varying_tables() {
+        // 1. Arrange: 前半は純音、後半は2倍音が加わる信号
+        const SAMPLE_RATE: u32 = 48000;
+        let params = AnalysisParams { freq_table_count: 2, ..Default::default() };
+        let half = SAMPLE_RATE as usize / 2;
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                let p = 2.0 * PI * 220.0 * i as f32 / SAMPLE_RATE as f32;
+                if i < half { p.sin() } else { p.sin() + 0.8 * (2.0 * p).sin() }
+            })
+            .collect();
+        let f0_curve = vec![220.0; SAMPLE_RATE as usize / params.hop_size];
+
+        // 2. Act
+        let tables = analyze_freq_domain(&signal, SAMPLE_RATE, &f0_curve, &params, &AnalysisProgress::default()).unwrap();
+
+        // 3. Assert: 区間ごとに異なる波形が得られる
+        assert_eq!(tables.len(), 2);
+        let sine: Vec<f32> = (0..params.target_cycle_len)
+            .map(|j| (2.0 * PI * j as f32 / params.target_cycle_len as f32).sin())
+            .collect();
+        assert!(best_cyclic_correlation(&tables[0], &sine) > best_cyclic_correlation(&tables[1], &sine));
+    }
+
+    #[test]
+    fn test_unvoiced_input_falls_back_to_averaged_spectrum() {
+        // 1. Arrange: 周期のないノイズと、すべて無声のF0カーブ
+        const SAMPLE_RATE: u32 = 48000;
+        let params = AnalysisParams::default();
+        let mut seed = 1u32;
+        let noise: Vec<f32> = (0..SAMPLE_RATE as usize / 2)
+            .map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+            })
+            .collect();
+        let f0_curve = vec![0.0; noise.len() / params.hop_size];
+
+        // 2. Act
+        let tables = analyze_freq_domain(&noise, SAMPLE_RATE, &f0_curve, &params, &AnalysisProgress::default());
+
+        // 3. Assert: エラーにせず、正規化された1枚のテーブルを返す
+        let tables = tables.expect("無声の入力で Freq モードが失敗しました");
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].len(), params.target_cycle_len);
+        let peak = tables[0].iter().map(|s| s.abs()).fold(0.0, f32::max);
+        assert!((peak - 1.0).abs() < 1e-4, "peak = {}", peak);
+    }
+}
@@ -1,93 +1,191 @@
 // src/analyzer/mode_hybrid.rs
 
-// (関数の部分は変更なしなので省略)
+use crate::log_message_internal;
+use super::error::AnalyzeError;
 use super::mode_time;
 use super::mode_freq;
+use super::progress::{AnalysisProgress, AnalysisStage};
+use super::resample::resample_cycle;
+use super::types::AnalysisParams;
+use rustfft::{FftPlanner, num_complex::Complex};
 use std::f32::consts::PI;
 
-fn apply_filter(audio: &[f32], sample_rate: u32, cutoff_freq: f32, is_highpass: bool) -> Vec<f32> {
-    let omega = 2.0 * PI * cutoff_freq / sample_rate as f32;
-    let alpha = omega.sin() / (2.0 * 0.707); // Q=0.707 (Butterworth)
-
-    let b0 = (1.0 - omega.cos()) / 2.0;
-    let b1 = 1.0 - omega.cos();
-    let b2 = (1.0 - omega.cos()) / 2.0;
-    let a0 = 1.0 + alpha;
-    let a1 = -2.0 * omega.cos();
-    let a2 = 1.0 - alpha;
-
-    let (b0, b1, b2, a1, a2) = (b0/a0, b1/a0, b2/a0, a1/a0, a2/a0);
-
-    let mut out = vec![0.0; audio.len()];
-    let mut x1 = 0.0; let mut x2 = 0.0;
-    let mut y1 = 0.0; let mut y2 = 0.0;
-
-    for i in 0..audio.len() {
-        let x0 = audio[i];
-        let y0 = b0*x0 + b1*x1 + b2*x2 - a1*y1 - a2*y2;
-        out[i] = y0;
-        x2 = x1; x1 = x0;
-        y2 = y1; y1 = y0;
+// --- クロスオーバーのパラメータ ---
+const TRANSITION_RATIO     : f32 = 0.5; // 遷移帯域幅 (クロスオーバー周波数に対する比率)
+const BLACKMAN_WIDTH_FACTOR: f32 = 5.5; // Blackman窓の遷移帯域幅 ≒ 5.5 * fs / タップ数
+const OLA_FFT_FACTOR       : usize = 4; // overlap-add の FFT 長 (タップ数に対する倍率, 2の冪に切り上げる)
+
+/// 窓付きsincによる線形位相ローパスFIRの係数を設計する (タップ数は奇数)
+fn design_lowpass_fir(sample_rate: u32, cutoff_freq: f32) -> Vec<f32> {
+    let transition = cutoff_freq * TRANSITION_RATIO;
+    let taps = ((BLACKMAN_WIDTH_FACTOR * sample_rate as f32 / transition).ceil() as usize) | 1;
+    let center = (taps / 2) as f32;
+    let normalized_cutoff = 2.0 * cutoff_freq / sample_rate as f32;
+
+    let mut kernel: Vec<f32> = (0..taps)
+        .map(|n| {
+            let x = n as f32 - center;
+            let sinc = if x == 0.0 { 1.0 } else { (PI * normalized_cutoff * x).sin() / (PI * normalized_cutoff * x) };
+            let w = 2.0 * PI * n as f32 / (taps - 1) as f32;
+            let window = 0.42 - 0.5 * w.cos() + 0.08 * (2.0 * w).cos();
+            normalized_cutoff * sinc * window
+        })
+        .collect();
+    // DCゲインを1に揃える
+    let dc_gain: f32 = kernel.iter().sum();
+    for coeff in kernel.iter_mut() {
+        *coeff /= dc_gain;
     }
+    kernel
+}
 
-    if is_highpass {
-        for i in 0..audio.len() {
-            out[i] = audio[i] - out[i];
+/// 線形位相FIRで低域と高域に分割する
+/// - FIRの群遅延 (タップ数/2) を補正して時間軸を揃えるため、高域 = 原信号 - 低域 で位相のにじみなく完全に再構成できる
+/// - 畳み込みは固定長ブロックの overlap-add で行う (長いファイルでも FFT バッファはタップ数に比例した大きさで済む)
+pub fn split_bands(audio: &[f32], sample_rate: u32, cutoff_freq: f32) -> (Vec<f32>, Vec<f32>) {
+    let kernel = design_lowpass_fir(sample_rate, cutoff_freq);
+    let delay = kernel.len() / 2;
+
+    // 1ブロックの FFT 長はタップ数の OLA_FFT_FACTOR 倍 (ブロック長 = FFT長 - タップ数 + 1)
+    let fft_len = (kernel.len() * OLA_FFT_FACTOR).next_power_of_two();
+    let block_len = fft_len - kernel.len() + 1;
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(fft_len);
+    let ifft = planner.plan_fft_inverse(fft_len);
+    let mut kernel_spec: Vec<Complex<f32>> = kernel.iter().map(|&h| Complex::new(h, 0.0)).collect();
+    kernel_spec.resize(fft_len, Complex::default());
+    fft.process(&mut kernel_spec);
+
+    // 低域 = 原信号 * FIR (群遅延の分だけ前にずらした位置から audio.len() サンプル)
+    let mut convolved = vec![0.0f32; audio.len() + kernel.len() - 1];
+    let mut block_spec = vec![Complex::default(); fft_len];
+    for (block_index, block) in audio.chunks(block_len).enumerate() {
+        for (slot, &x) in block_spec.iter_mut().zip(block.iter()) {
+            *slot = Complex::new(x, 0.0);
+        }
+        block_spec[block.len()..].fill(Complex::default());
+        fft.process(&mut block_spec);
+        for (x, h) in block_spec.iter_mut().zip(kernel_spec.iter()) {
+            *x *= h;
+        }
+        ifft.process(&mut block_spec);
+
+        let offset = block_index * block_len;
+        let valid = block.len() + kernel.len() - 1;
+        for (out, c) in convolved[offset..offset + valid].iter_mut().zip(block_spec.iter()) {
+            *out += c.re / fft_len as f32;
         }
     }
-    out
+
+    let low: Vec<f32> = convolved[delay..delay + audio.len()].to_vec();
+    let high: Vec<f32> = audio.iter().zip(low.iter()).map(|(&x, &l)| x - l).collect();
+    (low, high)
 }
 
+/// 基音成分が sin (上昇ゼロクロス) から始まるようにテーブルを巡回シフトする
+/// - Freqモードのテーブルと同じ位相基準に揃えるために使う
+fn align_to_fundamental(table: &mut [f32]) {
+    let len = table.len();
+    if len == 0 {
+        return;
+    }
+    let fundamental: Complex<f32> = table.iter()
+        .enumerate()
+        .map(|(j, &x)| Complex::from_polar(x, -2.0 * PI * j as f32 / len as f32))
+        .sum();
+    if fundamental.norm() < 1e-9 {
+        return;
+    }
+    // x'(j) = x(j + d) で基音の位相 φ が φ + 2πd/L になるので、-π/2 に合わせる
+    let shift = (-PI / 2.0 - fundamental.arg()) * len as f32 / (2.0 * PI);
+    table.rotate_left((shift.round() as isize).rem_euclid(len as isize) as usize);
+}
+
+/// 信号のRMSを求める
+fn rms(signal: &[f32]) -> f32 {
+    if signal.is_empty() {
+        return 0.0;
+    }
+    (signal.iter().map(|x| x * x).sum::<f32>() / signal.len() as f32).sqrt()
+}
+
+/// 各モードで ±1 に正規化されたテーブルを、帯域ごとの元のRMSに合わせて拡大縮小する
+fn scale_to_rms(table: &mut [f32], target_rms: f32) {
+    let current = rms(table);
+    if current > 1e-9 {
+        for sample in table.iter_mut() {
+            *sample *= target_rms / current;
+        }
+    }
+}
+
+/// ハイブリッド解析 (低域: Timeモード, 高域: Freqモード) を行う
+/// - 高域の各テーブルに低域テーブルを足し合わせるため、Freqモードの時間変化テーブルはそのまま引き継がれる
 pub fn analyze_hybrid(
     audio: &[f32],
     sample_rate: u32,
     f0_curve: &[f32],
-) -> Result<Vec<Vec<f32>>, String> {
-    println!("[INFO] Hybrid analysis started.");
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<Vec<Vec<f32>>, AnalyzeError> {
 
     let average_f0 = f0_curve.iter().filter(|&&f| f > 0.0).sum::<f32>()
         / f0_curve.iter().filter(|&&f| f > 0.0).count() as f32;
     
     if average_f0.is_nan() {
-        return Err("Cannot perform hybrid analysis without a valid F0 curve.".to_string());
+        return Err(AnalyzeError::NoF0Found);
     }
     
-    let crossover_freq = (average_f0 * 5.0).max(800.0).clamp(800.0, 3000.0);
-    println!("[INFO] Crossover frequency set to: {:.2} Hz", crossover_freq);
-
-    let low_pass_audio = apply_filter(audio, sample_rate, crossover_freq, false);
-    let high_pass_audio = apply_filter(audio, sample_rate, crossover_freq, true);
+    let crossover_freq = (average_f0 * 5.0).clamp(800.0, 3000.0);
+    log_message_internal("Rust", &format!("Crossover frequency set to: {:.2} Hz", crossover_freq));
 
-    let low_table_result = mode_time::analyze_time_domain(&low_pass_audio, sample_rate, f0_curve)?;
-    let high_table_result = mode_freq::analyze_freq_domain(&high_pass_audio, sample_rate)?;
+    let (low_pass_audio, high_pass_audio) = split_bands(audio, sample_rate, crossover_freq);
 
-    let low_table = low_table_result.get(0).ok_or("Low-pass analysis failed.")?;
-    let high_table = high_table_result.get(0).ok_or("High-pass analysis failed.")?;
+    let low_table_result = mode_time::analyze_time_domain(
+        &low_pass_audio, sample_rate, f0_curve, params, &progress.stage(AnalysisStage::ModeAnalysis, 0.0, 0.2)?,
+    )?;
+    // 高域は基音を含まないため、基音の位相基準は帯域分割前の信号から取る
+    let high_table_result = mode_freq::analyze_freq_domain_with_reference(
+        &high_pass_audio, audio, sample_rate, f0_curve, params, &progress.stage(AnalysisStage::ModeAnalysis, 0.2, 1.0)?,
+    )?;
 
-    let target_len = low_table.len().max(high_table.len());
-    let mut final_table = vec![0.0; target_len];
-
-    for i in 0..target_len {
-        let low_sample = low_table.get(i).cloned().unwrap_or(0.0);
-        let high_sample = high_table.get(i).cloned().unwrap_or(0.0);
-        final_table[i] = low_sample + high_sample;
+    let mut low_table = low_table_result.into_iter().next().ok_or_else(|| AnalyzeError::AnalysisFailed("Low-pass analysis failed.".to_string()))?;
+    if high_table_result.is_empty() {
+        return Err(AnalyzeError::AnalysisFailed("High-pass analysis failed.".to_string()));
     }
+    align_to_fundamental(&mut low_table);
+    // 低域と高域のバランスを元の信号に合わせる
+    scale_to_rms(&mut low_table, rms(&low_pass_audio));
+    let high_rms = rms(&high_pass_audio);
+
+    let mut final_tables = Vec::with_capacity(high_table_result.len());
+    for high_table in high_table_result {
+        // 周期長を低域テーブルに揃えてから合成する
+        let mut high_table = if high_table.len() == low_table.len() {
+            high_table
+        } else {
+            resample_cycle(&high_table, low_table.len())
+        };
+        scale_to_rms(&mut high_table, high_rms);
+        let mut final_table: Vec<f32> = low_table.iter().zip(high_table.iter()).map(|(l, h)| l + h).collect();
 
-    let max_abs = final_table.iter().map(|&s| s.abs()).fold(0.0, f32::max);
-    if max_abs > 1e-6 {
-        for sample in final_table.iter_mut() {
-            *sample /= max_abs;
+        let max_abs = final_table.iter().map(|&s| s.abs()).fold(0.0, f32::max);
+        if max_abs > 1e-6 {
+            for sample in final_table.iter_mut() {
+                *sample /= max_abs;
+            }
         }
+        final_tables.push(final_table);
     }
 
-    println!("[INFO] Hybrid analysis finished. Generated 1 combined wavetable.");
-    Ok(vec![final_table])
+    log_message_internal("Rust", &format!("Hybrid analysis finished. Generated {} combined wavetable(s).", final_tables.len()));
+    Ok(final_tables)
 }
 
 
 #[cfg(test)]
 mod tests {
-    // ★ 修正点: 未使用の `use super::*;` を削除
+    use super::*;
 
     // --- モック（ダミー）の実装 ---
     mod mock_time {
@@ -130,4 +228,79 @@ mod tests {
             sum
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_linear_phase_split_reconstructs_input() {
+        // 1. Arrange: 200Hz + 5kHz の信号
+        const SAMPLE_RATE: u32 = 48000;
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize / 4)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                (2.0 * PI * 200.0 * t).sin() + (2.0 * PI * 5000.0 * t).sin()
+            })
+            .collect();
+
+        // 2. Act: 1kHzで分割
+        let (low, high) = split_bands(&signal, SAMPLE_RATE, 1000.0);
+
+        // 3. Assert: 低域+高域で完全に元に戻り、低域には5kHzがほぼ残らない
+        let max_error = signal.iter().zip(low.iter().zip(high.iter()))
+            .map(|(&x, (&l, &h))| (x - (l + h)).abs())
+            .fold(0.0, f32::max);
+        assert!(max_error < 1e-5);
+        let steady = &low[2000..low.len() - 2000];
+        let expected: Vec<f32> = (2000..low.len() - 2000)
+            .map(|i| (2.0 * PI * 200.0 * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect();
+        let residual = steady.iter().zip(expected.iter()).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+        assert!(residual < 0.01, "low band residual = {}", residual);
+    }
+
+    #[test]
+    fn test_overlap_add_matches_direct_convolution() {
+        // 1. Arrange: 複数ブロックにまたがる長さの不規則な信号
+        const SAMPLE_RATE: u32 = 48000;
+        let signal: Vec<f32> = (0..20000).map(|i| ((i * 7919 % 1013) as f32 / 1013.0 - 0.5) * 2.0).collect();
+        let kernel = design_lowpass_fir(SAMPLE_RATE, 1000.0);
+        let delay = kernel.len() / 2;
+
+        // 2. Act
+        let (low, _) = split_bands(&signal, SAMPLE_RATE, 1000.0);
+
+        // 3. Assert: 時間領域で直接畳み込んだ結果と一致する
+        let max_error = (0..signal.len())
+            .map(|n| {
+                let direct: f32 = kernel.iter().enumerate()
+                    .filter_map(|(k, &h)| (n + delay).checked_sub(k).and_then(|i| signal.get(i)).map(|&x| h * x))
+                    .sum();
+                (direct - low[n]).abs()
+            })
+            .fold(0.0, f32::max);
+        assert!(max_error < 1e-4, "max error = {}", max_error);
+    }
+
+    #[test]
+    fn test_hybrid_table_is_periodic_and_keeps_shape() {
+        // 1. Arrange: クロスオーバー (1100Hz) をまたぐ倍音を持つ220Hzの信号
+        const SAMPLE_RATE: u32 = 48000;
+        let params = AnalysisParams::default();
+        let shape = |p: f32| p.sin() + 0.5 * (2.0 * p).sin() + 0.4 * (7.0 * p + 0.3).sin() + 0.3 * (9.0 * p).sin();
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| shape(2.0 * PI * 220.0 * i as f32 / SAMPLE_RATE as f32))
+            .collect();
+        let f0_curve = vec![220.0; SAMPLE_RATE as usize / params.hop_size];
+
+        // 2. Act
+        let tables = analyze_hybrid(&signal, SAMPLE_RATE, &f0_curve, &params, &AnalysisProgress::default()).unwrap();
+
+        // 3. Assert: 1周期が target_cycle_len で、元の波形と同じ形になる
+        let table = &tables[0];
+        assert_eq!(table.len(), params.target_cycle_len);
+        let reference: Vec<f32> = (0..table.len())
+            .map(|j| shape(2.0 * PI * j as f32 / table.len() as f32))
+            .collect();
+        let dot: f32 = table.iter().zip(reference.iter()).map(|(a, b)| a * b).sum();
+        let energy = (table.iter().map(|x| x * x).sum::<f32>() * reference.iter().map(|x| x * x).sum::<f32>()).sqrt();
+        assert!(dot / energy > 0.98, "hybrid correlation = {}", dot / energy);
+    }
+}
@@ -1,91 +1,199 @@
 // src/analyzer/mode_time.rs
 
-/// 線形補間を使ってオーディオ波形をリサンプリングするヘルパー関数
-fn resample_linear(wave: &[f32], target_len: usize) -> Vec<f32> {
-    if wave.is_empty() || target_len == 0 {
-        return vec![0.0; target_len];
+use crate::log_message_internal;
+use super::error::AnalyzeError;
+use super::progress::AnalysisProgress;
+use super::resample::resample_segment;
+use super::types::{AnalysisParams, CycleAlignment};
+
+// --- 周期アライメントのパラメータ ---
+const SEARCH_RATIO     : f32 = 0.1; // 予測位置の前後に探索する範囲 (周期長に対する比率)
+const MAX_PERIOD_DRIFT : f32 = 0.5; // 実測周期が予測からこれ以上ずれたら予測値を使う
+
+/// 2つの周期波形の正規化相互相関を計算する
+fn normalized_correlation(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+    let energy_a: f32 = a.iter().map(|x| x * x).sum();
+    let energy_b: f32 = b.iter().map(|x| x * x).sum();
+    let denom = (energy_a * energy_b).sqrt();
+    if denom > 1e-12 { dot / denom } else { 0.0 }
+}
+
+/// 隣接周期との相関が最も高い (= 最も定常な) 周期を選ぶ
+fn select_most_stable_cycle(cycles: &[Vec<f32>]) -> Vec<f32> {
+    if cycles.len() < 2 {
+        return cycles.first().cloned().unwrap_or_default();
     }
-    let mut resampled = vec![0.0; target_len];
-    let scale = (wave.len() - 1) as f32 / (target_len - 1) as f32;
+    let best_idx = (0..cycles.len() - 1)
+        .max_by(|&a, &b| {
+            let corr_a = normalized_correlation(&cycles[a], &cycles[a + 1]);
+            let corr_b = normalized_correlation(&cycles[b], &cycles[b + 1]);
+            corr_a.partial_cmp(&corr_b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap_or(0);
+    cycles[best_idx].clone()
+}
 
-    for i in 0..target_len {
-        let original_idx_f = i as f32 * scale;
-        let idx0 = original_idx_f.floor() as usize;
-        let idx1 = (idx0 + 1).min(wave.len() - 1);
-        let frac = original_idx_f - idx0 as f32;
+/// 3点の放物線補間でピークの小数オフセット (-0.5〜0.5) を求める
+fn parabolic_offset(y0: f32, y1: f32, y2: f32) -> f32 {
+    let denom = y0 - 2.0 * y1 + y2;
+    if denom.abs() < 1e-12 { 0.0 } else { (0.5 * (y0 - y2) / denom).clamp(-0.5, 0.5) }
+}
 
-        resampled[i] = wave[idx0] * (1.0 - frac) + wave[idx1] * frac;
+/// 最初の周期の先頭位置 (アンカー) を [start, start + period) の範囲から探す
+/// - ZeroCrossing: 最も傾きが急な上昇ゼロクロス (線形補間で小数位置)
+/// - Peak: 最大値の位置 (放物線補間で小数位置)
+fn find_anchor(audio: &[f32], start: usize, period: f32, alignment: CycleAlignment) -> f32 {
+    let end = (start + period.ceil() as usize).min(audio.len().saturating_sub(1));
+    match alignment {
+        CycleAlignment::ZeroCrossing => (start..end)
+            .filter(|&i| audio[i] <= 0.0 && audio[i + 1] > 0.0)
+            .max_by(|&a, &b| {
+                let slope_a = audio[a + 1] - audio[a];
+                let slope_b = audio[b + 1] - audio[b];
+                slope_a.partial_cmp(&slope_b).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|i| i as f32 + audio[i] / (audio[i] - audio[i + 1]))
+            .unwrap_or(start as f32),
+        CycleAlignment::Peak => (start..end)
+            .max_by(|&a, &b| audio[a].partial_cmp(&audio[b]).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|i| {
+                if i == 0 || i + 1 >= audio.len() {
+                    i as f32
+                } else {
+                    i as f32 + parabolic_offset(audio[i - 1], audio[i], audio[i + 1])
+                }
+            })
+            .unwrap_or(start as f32),
     }
-    resampled
 }
 
-/// 時間領域での音声解析を行う
-pub fn analyze_time_domain(
+/// 直前の周期との相互相関が最大となる位置を、予測位置の近傍から小数精度で探す
+/// - 範囲外にはみ出す場合は None
+fn align_next_cycle(audio: &[f32], prev_start: f32, period: f32, predicted: f32) -> Option<f32> {
+    let template_len = period.round() as usize;
+    let search = (period * SEARCH_RATIO).ceil() as isize;
+    let template_start = prev_start.round() as usize;
+    let predicted_int = predicted.round() as isize;
+    if predicted_int - search - 1 < 0 || predicted_int as usize + search as usize + 1 + template_len > audio.len() {
+        return None;
+    }
+    let template = &audio[template_start..template_start + template_len];
+
+    let scores: Vec<f32> = (-search - 1..=search + 1)
+        .map(|d| {
+            let s = (predicted_int + d) as usize;
+            normalized_correlation(template, &audio[s..s + template_len])
+        })
+        .collect();
+    // 端の2点は放物線補間用なので探索対象から外す
+    let (best_idx, _) = scores[1..scores.len() - 1]
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))?;
+    let i = best_idx + 1;
+    let offset = parabolic_offset(scores[i - 1], scores[i], scores[i + 1]);
+    let best_int = predicted_int - search - 1 + i as isize;
+
+    // テンプレートを整数位置で切り出した分の端数を戻す
+    Some(best_int as f32 + offset + (prev_start - template_start as f32))
+}
+
+/// 周期境界 (先頭位置と長さ, いずれも小数サンプル) を検出する
+/// - 最初の周期の先頭をゼロクロス/ピークに揃え、以降は隣接周期との相互相関で境界を決める
+pub fn detect_cycle_boundaries(
     audio: &[f32],
     sample_rate: u32,
     f0_curve: &[f32],
-) -> Result<Vec<Vec<f32>>, String> {
-    println!("[INFO] Time domain analysis started.");
-
+    params: &AnalysisParams,
+) -> Result<Vec<(f32, f32)>, AnalyzeError> {
     // 1. F0カーブから平均的な周期（サンプル数）を計算する
     let average_f0: f32 = f0_curve.iter().filter(|&&f| f > 0.0).sum::<f32>() 
         / f0_curve.iter().filter(|&&f| f > 0.0).count() as f32;
     
     if average_f0.is_nan() || average_f0 < 1.0 {
-        return Err("Could not determine a valid average F0 from the curve.".to_string());
+        return Err(AnalyzeError::NoF0Found);
     }
-    let target_period_len = (sample_rate as f32 / average_f0).round() as usize;
+    let average_period = sample_rate as f32 / average_f0;
 
-    if target_period_len < 2 {
-        return Err("Average period is too short to process.".to_string());
+    if average_period < 2.0 {
+        return Err(AnalyzeError::NoCyclesExtracted);
     }
 
-    let mut cycles = Vec::new();
-    let mut current_pos = 0.0;
-    
     // F0カーブの1フレームがオーディオの何サンプル分に対応するか
-    let hop_size = 512; // f0_estimator.rs で定義したHOP_SIZEと合わせる
-    
-    // 2. F0カーブに従ってオーディオから1周期ずつ切り出す
-    while (current_pos as usize) < audio.len() {
-        let frame_idx = (current_pos / hop_size as f32).floor() as usize;
-        let f0 = f0_curve.get(frame_idx).cloned().unwrap_or(average_f0);
-
-        let period_len = if f0 > 0.0 {
-            sample_rate as f32 / f0
-        } else {
-            target_period_len as f32
-        };
+    let hop_size = params.hop_size;
+    let period_at = |pos: f32| -> f32 {
+        let frame_idx = (pos / hop_size as f32).floor() as usize;
+        match f0_curve.get(frame_idx) {
+            Some(&f0) if f0 > 0.0 => sample_rate as f32 / f0,
+            _ => average_period,
+        }
+    };
 
-        let start = current_pos.round() as usize;
-        let end = (current_pos + period_len).round() as usize;
+    // 2. 予測位置の近傍で相互相関が最大となる位置を次の周期の先頭とする
+    let mut boundaries = Vec::new();
+    let mut start = find_anchor(audio, 0, period_at(0.0), params.cycle_alignment);
+    loop {
+        let period = period_at(start);
+        let Some(next) = align_next_cycle(audio, start, period, start + period) else {
+            break;
+        };
+        let measured = next - start;
+        let length = if (measured - period).abs() <= period * MAX_PERIOD_DRIFT { measured } else { period };
+        boundaries.push((start, length));
+        start += length;
+    }
 
-        if end > audio.len() { break; }
-        
-        let cycle_slice = &audio[start..end];
-        cycles.push(cycle_slice.to_vec());
-        
-        current_pos += period_len;
+    if boundaries.is_empty() {
+        return Err(AnalyzeError::NoCyclesExtracted);
     }
+    Ok(boundaries)
+}
+
+/// 時間領域での音声解析を行う
+pub fn analyze_time_domain(
+    audio: &[f32],
+    sample_rate: u32,
+    f0_curve: &[f32],
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<Vec<Vec<f32>>, AnalyzeError> {
+
+    // 1-2. 周期境界を検出する
+    let boundaries = detect_cycle_boundaries(audio, sample_rate, f0_curve, params)?;
 
-    if cycles.is_empty() {
-        return Err("No cycles could be extracted from the audio.".to_string());
+    // 3. 各周期を帯域制限sinc補間で target_cycle_len サンプルへリサンプリングする
+    let mut resampled_cycles: Vec<Vec<f32>> = Vec::with_capacity(boundaries.len());
+    for (i, &(start, length)) in boundaries.iter().enumerate() {
+        progress.frame(i, boundaries.len())?;
+        resampled_cycles.push(resample_segment(audio, start, length, params.target_cycle_len));
     }
-    
-    // 3. 全ての周期を平均周期長にリサンプリングし、4. 平均化する
-    let mut averaged_table = vec![0.0; target_period_len];
-    for cycle in cycles.iter() {
-        let resampled_cycle = resample_linear(cycle, target_period_len);
-        for i in 0..target_period_len {
-            averaged_table[i] += resampled_cycle[i];
+    let target_period_len = params.target_cycle_len;
+
+    // 4. 平均化する (電子音プロファイルでは平均化せず、最も安定した1周期を使う)
+    let mut averaged_table = if params.cycle_averaging {
+        let mut averaged = vec![0.0; target_period_len];
+        for cycle in resampled_cycles.iter() {
+            for (acc, &sample) in averaged.iter_mut().zip(cycle.iter()) {
+                *acc += sample;
+            }
         }
-    }
+        for sample in averaged.iter_mut() {
+            *sample /= resampled_cycles.len() as f32;
+        }
+        averaged
+    } else {
+        select_most_stable_cycle(&resampled_cycles)
+    };
 
-    for sample in averaged_table.iter_mut() {
-        *sample /= cycles.len() as f32;
-    }
+    // 5. 平均化後の波形の先頭も同じ基準 (ゼロクロス/ピーク) に揃え直す
+    //    (最初の周期が前処理の過渡で歪んでいても、出力の位相が一意に決まるようにする)
+    let mut wrapped = averaged_table.clone();
+    wrapped.push(averaged_table[0]);
+    let anchor = find_anchor(&wrapped, 0, target_period_len as f32, params.cycle_alignment);
+    averaged_table.rotate_left(anchor.round() as usize % target_period_len);
 
-    // 5. 波形を -1.0 ~ 1.0 の範囲に正規化する
+    // 6. 波形を -1.0 ~ 1.0 の範囲に正規化する
     let max_abs = averaged_table.iter().map(|&s| s.abs()).fold(0.0, f32::max);
     if max_abs > 1e-6 {
         for sample in averaged_table.iter_mut() {
@@ -93,7 +201,7 @@ pub fn analyze_time_domain(
         }
     }
     
-    println!("[INFO] Time domain analysis finished. Generated 1 wavetable.");
+    log_message_internal("Rust", "Time domain analysis finished. Generated 1 wavetable.");
     Ok(vec![averaged_table])
 }
 
@@ -107,6 +215,7 @@ mod tests {
         const SAMPLE_RATE: u32 = 48000;
         const SIGNAL_FREQ: f32 = 440.0;
         const SIGNAL_LEN: usize = SAMPLE_RATE as usize; // 1秒
+        let params = AnalysisParams::default();
 
         let mut signal = Vec::new();
         for i in 0..SIGNAL_LEN {
@@ -119,7 +228,7 @@ mod tests {
         let f0_curve = vec![SIGNAL_FREQ; 100];
 
         // 2. Act: 時間領域解析を実行
-        let result = analyze_time_domain(&signal, SAMPLE_RATE, &f0_curve);
+        let result = analyze_time_domain(&signal, SAMPLE_RATE, &f0_curve, &params, &AnalysisProgress::default());
         assert!(result.is_ok());
         let tables = result.unwrap();
 
@@ -127,8 +236,8 @@ mod tests {
         assert_eq!(tables.len(), 1, "ウェーブテーブルは1つだけ生成されるべきです");
         let table = &tables[0];
 
-        // 期待されるテーブル長を計算 (48000 / 440 = 109.09... -> 109)
-        let expected_len = (SAMPLE_RATE as f32 / SIGNAL_FREQ).round() as usize;
+        // テーブル長は周期長によらず target_cycle_len に揃えられる
+        let expected_len = params.target_cycle_len;
         assert_eq!(table.len(), expected_len, "ウェーブテーブルの長さが正しくありません");
 
         // 波形の主要なポイントを検証
@@ -149,4 +258,38 @@ mod tests {
             table[trough_idx]
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cycles_stay_aligned_under_pitch_drift() {
+        // 1. Arrange: 440Hz→452Hzへ緩やかにずれる倍音付きの信号と、ずれを含まない平均的なF0カーブ
+        const SAMPLE_RATE: u32 = 48000;
+        let params = AnalysisParams::default();
+        let mut phase = 0.0f32;
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                let freq = 440.0 + 12.0 * i as f32 / SAMPLE_RATE as f32;
+                phase += 2.0 * std::f32::consts::PI * freq / SAMPLE_RATE as f32;
+                phase.sin() + 0.5 * (2.0 * phase).sin() + 0.3 * (3.0 * phase).sin()
+            })
+            .collect();
+        let f0_curve = vec![446.0; SAMPLE_RATE as usize / params.hop_size];
+
+        // 2. Act
+        let tables = analyze_time_domain(&signal, SAMPLE_RATE, &f0_curve, &params, &AnalysisProgress::default()).unwrap();
+
+        // 3. Assert: 平均化しても位相がにじまず、元の1周期波形とほぼ一致する
+        let reference: Vec<f32> = (0..params.target_cycle_len)
+            .map(|j| {
+                let p = 2.0 * std::f32::consts::PI * j as f32 / params.target_cycle_len as f32;
+                p.sin() + 0.5 * (2.0 * p).sin() + 0.3 * (3.0 * p).sin()
+            })
+            .collect();
+        let best = (0..params.target_cycle_len)
+            .map(|shift| {
+                let rotated: Vec<f32> = (0..reference.len()).map(|j| reference[(j + shift) % reference.len()]).collect();
+                normalized_correlation(&tables[0], &rotated)
+            })
+            .fold(f32::MIN, f32::max);
+        assert!(best > 0.999, "averaged cycle correlation = {}", best);
+    }
+}
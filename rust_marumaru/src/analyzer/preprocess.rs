@@ -1,10 +1,7 @@
 // src/analyzer/preprocess.rs
 use rustfft::{FftPlanner, num_complex::Complex};
-use std::f32::consts::PI;
-
-// --- STFTパラメータ ---
-const FFT_SIZE: usize = 1024;
-const HOP_SIZE: usize = 256;
+use super::error::AnalyzeError;
+use super::types::AnalysisParams;
 
 /// RMS (二乗平均平方根) を基準に音量を正規化する
 fn normalize(audio: &[f32], target_dbfs: f32) -> Vec<f32> {
@@ -36,40 +33,41 @@ fn dc_remove(audio: &[f32], alpha: f32) -> Vec<f32> {
 }
 
 /// スペクトルゲートによるノイズ除去
-fn spectral_gate(audio: &[f32]) -> Vec<f32> {
-    println!("[INFO] Applying spectral gate for noise reduction.");
-    if audio.len() < FFT_SIZE {
+fn spectral_gate(audio: &[f32], params: &AnalysisParams) -> Vec<f32> {
+    let fft_size = params.fft_size;
+    let hop_size = params.hop_size;
+    if audio.len() < fft_size {
         return audio.to_vec();
     }
 
     let mut planner = FftPlanner::new();
-    let fft = planner.plan_fft_forward(FFT_SIZE);
-    let ifft = planner.plan_fft_inverse(FFT_SIZE);
-    let window: Vec<f32> = (0..FFT_SIZE).map(|i| 0.5 * (1.0 - (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos())).collect();
+    let fft = planner.plan_fft_forward(fft_size);
+    let ifft = planner.plan_fft_inverse(fft_size);
+    let window = params.window.generate(fft_size);
 
-    let frames = audio.windows(FFT_SIZE).step_by(HOP_SIZE);
+    let frames = audio.windows(fft_size).step_by(hop_size);
     let mut spectrogram: Vec<Vec<Complex<f32>>> = frames.map(|frame| {
         let mut buffer: Vec<Complex<f32>> = frame.iter().zip(&window).map(|(&s, &w)| Complex::new(s * w, 0.0)).collect();
         fft.process(&mut buffer);
         buffer
     }).collect();
 
-    let mut noise_floor = vec![0.0; FFT_SIZE / 2 + 1];
+    let mut noise_floor = vec![0.0; fft_size / 2 + 1];
     if let Some(quietest_frame) = spectrogram.iter().min_by(|a, b| {
         let power_a: f32 = a.iter().map(|c| c.norm_sqr()).sum();
         let power_b: f32 = b.iter().map(|c| c.norm_sqr()).sum();
         power_a.partial_cmp(&power_b).unwrap()
     }) {
-        for i in 0..=FFT_SIZE / 2 {
-            noise_floor[i] = quietest_frame[i].norm();
+        for (floor, bin) in noise_floor.iter_mut().zip(quietest_frame.iter()) {
+            *floor = bin.norm();
         }
     }
 
-    let noise_threshold = 1.5;
+    let noise_threshold = params.noise_gate_threshold;
     for frame in &mut spectrogram {
-        for i in 0..=FFT_SIZE / 2 {
-            if frame[i].norm() < noise_floor[i] * noise_threshold {
-                frame[i] = Complex::new(0.0, 0.0);
+        for (bin, &floor) in frame.iter_mut().zip(noise_floor.iter()) {
+            if bin.norm() < floor * noise_threshold {
+                *bin = Complex::new(0.0, 0.0);
             }
         }
     }
@@ -78,8 +76,8 @@ fn spectral_gate(audio: &[f32]) -> Vec<f32> {
     let mut window_sum = vec![0.0; audio.len()];
     for (i, mut frame) in spectrogram.into_iter().enumerate() {
         ifft.process(&mut frame);
-        let start = i * HOP_SIZE;
-        for j in 0..FFT_SIZE {
+        let start = i * hop_size;
+        for j in 0..fft_size {
             if start + j < output_audio.len() {
                 output_audio[start + j] += frame[j].re * window[j];
                 window_sum[start + j] += window[j].powi(2);
@@ -87,9 +85,9 @@ fn spectral_gate(audio: &[f32]) -> Vec<f32> {
         }
     }
 
-    for i in 0..output_audio.len() {
-        if window_sum[i] > 1e-6 {
-            output_audio[i] /= window_sum[i];
+    for (sample, &sum) in output_audio.iter_mut().zip(window_sum.iter()) {
+        if sum > 1e-6 {
+            *sample /= sum;
         }
     }
     output_audio
@@ -97,16 +95,20 @@ fn spectral_gate(audio: &[f32]) -> Vec<f32> {
 
 
 /// 全ての前処理を順番に適用する
-pub fn apply_all_preprocessing(audio: &[f32]) -> Result<Vec<f32>, String> {
+pub fn apply_all_preprocessing(audio: &[f32], params: &AnalysisParams) -> Result<Vec<f32>, AnalyzeError> {
     if audio.is_empty() {
-        return Err("Input audio is empty.".to_string());
+        return Err(AnalyzeError::EmptyInput);
     }
     
-    let normalized = normalize(audio, -10.0);
-    let dc_removed = dc_remove(&normalized, 0.995);
-    let noise_reduced = spectral_gate(&dc_removed);
+    let mut processed = normalize(audio, params.normalize_target_dbfs);
+    if params.dc_highpass {
+        processed = dc_remove(&processed, 0.995);
+    }
+    if params.spectral_gate {
+        processed = spectral_gate(&processed, params);
+    }
     
-    Ok(noise_reduced)
+    Ok(processed)
 }
 
 // --- テストモジュール ---
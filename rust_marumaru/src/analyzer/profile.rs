@@ -0,0 +1,183 @@
+// src/analyzer/profile.rs
+
+use crate::log_message_internal;
+use super::types::{AnalysisParams, AnalysisProfile};
+
+// --- 判定パラメータ ---
+const MAX_PROBE_FRAMES       : usize = 16;    // 判定に使うフレーム数の上限
+const PERIODICITY_THRESHOLD  : f32   = 0.97;  // これを超える自己相関なら「完全周期的」とみなす
+const JITTER_THRESHOLD       : f32   = 0.002; // 周期の相対ばらつきがこれ未満なら揺らぎなし
+const EDGE_THRESHOLD         : f32   = 0.5;   // 1サンプル差分/フレームのピーク がこれを超えると急峻なエッジ
+const EDGE_FRAME_RATIO       : f32   = 0.5;   // 急峻なエッジを含むフレームがこの割合以上なら波形自体がエッジを持つ
+
+/// フレームの正規化自己相関が最大となるラグ(小数)とその値を求める
+fn best_period(frame: &[f32], min_lag: usize, max_lag: usize) -> Option<(f32, f32)> {
+    let max_lag = max_lag.min(frame.len() / 2);
+    if min_lag < 1 || min_lag + 1 >= max_lag {
+        return None;
+    }
+
+    let nacf: Vec<f32> = (min_lag - 1..=max_lag + 1)
+        .map(|lag| {
+            let head = &frame[..frame.len() - lag];
+            let tail = &frame[lag..];
+            let dot: f32 = head.iter().zip(tail.iter()).map(|(a, b)| a * b).sum();
+            let energy_head: f32 = head.iter().map(|x| x * x).sum();
+            let energy_tail: f32 = tail.iter().map(|x| x * x).sum();
+            let denom = (energy_head * energy_tail).sqrt();
+            if denom > 1e-12 { dot / denom } else { 0.0 }
+        })
+        .collect();
+
+    // 端 (min_lag-1, max_lag+1) は放物線補間用なので探索対象から外す
+    let (peak_idx, &peak_val) = nacf[1..nacf.len() - 1]
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))?;
+    let i = peak_idx + 1;
+
+    // 放物線補間でサブサンプル精度のラグを求める
+    let (y0, y1, y2) = (nacf[i - 1], nacf[i], nacf[i + 1]);
+    let denom = y0 - 2.0 * y1 + y2;
+    let offset = if denom.abs() > 1e-12 { 0.5 * (y0 - y2) / denom } else { 0.0 };
+    let lag = (i + min_lag - 1) as f32 + offset.clamp(-0.5, 0.5);
+
+    Some((lag, peak_val))
+}
+
+/// 入力音が電子音的 (完全周期・揺らぎなし・急峻なエッジ) か自然音的かを判定する
+pub fn detect_profile(audio: &[f32], sample_rate: u32, params: &AnalysisParams) -> AnalysisProfile {
+    let frame_size = params.fft_size;
+    if audio.len() < frame_size {
+        return AnalysisProfile::Natural;
+    }
+
+    let min_lag = (sample_rate as f32 / params.max_f0).floor() as usize;
+    let max_lag = (sample_rate as f32 / params.min_f0).ceil() as usize;
+
+    // 1. 音量が十分なフレームを均等に抽出して周期と周期性を測る
+    let peak_abs = audio.iter().map(|s| s.abs()).fold(0.0, f32::max);
+    if peak_abs < 1e-6 {
+        return AnalysisProfile::Natural;
+    }
+    let num_frames = (audio.len() - frame_size) / params.hop_size + 1;
+    let stride = (num_frames / MAX_PROBE_FRAMES).max(1);
+
+    let mut lags = Vec::new();
+    let mut periodicities = Vec::new();
+    let mut edge_frames = 0;
+    for frame_idx in (0..num_frames).step_by(stride) {
+        let start = frame_idx * params.hop_size;
+        let frame = &audio[start..start + frame_size];
+        let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame_size as f32).sqrt();
+        if rms < peak_abs * 0.01 {
+            continue; // 無音に近いフレームは判定に使わない
+        }
+        if let Some((lag, value)) = best_period(frame, min_lag, max_lag) {
+            lags.push(lag);
+            periodicities.push(value);
+
+            // 1サンプル間の最大変化量でハードエッジ (矩形波・鋸歯状波など) を検出する
+            let frame_peak = frame.iter().map(|s| s.abs()).fold(0.0, f32::max);
+            let max_step = frame.windows(2).map(|w| (w[1] - w[0]).abs()).fold(0.0, f32::max);
+            if max_step > frame_peak * EDGE_THRESHOLD {
+                edge_frames += 1;
+            }
+        }
+    }
+    if lags.is_empty() {
+        return AnalysisProfile::Natural;
+    }
+
+    periodicities.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median_periodicity = periodicities[periodicities.len() / 2];
+
+    let mean_lag = lags.iter().sum::<f32>() / lags.len() as f32;
+    let lag_std = (lags.iter().map(|l| (l - mean_lag).powi(2)).sum::<f32>() / lags.len() as f32).sqrt();
+    let jitter = lag_std / mean_lag;
+
+    // 2. ハードエッジを含むフレームの割合 (クリックや編集のつなぎ目が1か所あるだけでは電子音としない)
+    let edge_ratio = edge_frames as f32 / lags.len() as f32;
+
+    log_message_internal("Rust", &format!(
+        "Profile detection: periodicity={:.3}, jitter={:.5}, edge_ratio={:.3}",
+        median_periodicity, jitter, edge_ratio
+    ));
+
+    if median_periodicity > PERIODICITY_THRESHOLD
+        && (jitter < JITTER_THRESHOLD || edge_ratio >= EDGE_FRAME_RATIO)
+    {
+        AnalysisProfile::Electronic
+    } else {
+        AnalysisProfile::Natural
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    #[test]
+    fn test_sawtooth_is_detected_as_electronic() {
+        // 1. Arrange: 周期が完全に一定な鋸歯状波
+        let freq = 220.0;
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                let phase = (i as f32 * freq / SAMPLE_RATE as f32).fract();
+                2.0 * phase - 1.0
+            })
+            .collect();
+
+        // 2. Act
+        let profile = detect_profile(&signal, SAMPLE_RATE, &AnalysisParams::default());
+
+        // 3. Assert
+        assert_eq!(profile, AnalysisProfile::Electronic);
+    }
+
+    #[test]
+    fn test_vibrato_tone_is_detected_as_natural() {
+        // 1. Arrange: ビブラート(5Hz, ±3%)と倍音の減衰を持つ自然音的な信号
+        let base_freq = 220.0;
+        let mut phase = 0.0f32;
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let freq = base_freq * (1.0 + 0.03 * (2.0 * PI * 5.0 * t).sin());
+                phase += 2.0 * PI * freq / SAMPLE_RATE as f32;
+                (phase.sin() + 0.5 * (2.0 * phase).sin() + 0.25 * (3.0 * phase).sin()) * (-t * 2.0).exp()
+            })
+            .collect();
+
+        // 2. Act
+        let profile = detect_profile(&signal, SAMPLE_RATE, &AnalysisParams::default());
+
+        // 3. Assert
+        assert_eq!(profile, AnalysisProfile::Natural);
+    }
+
+    #[test]
+    fn test_single_click_does_not_make_natural_tone_electronic() {
+        // 1. Arrange: 軽いビブラート(5Hz, ±0.5%)の正弦波の途中に1か所だけクリックを入れる
+        let base_freq = 220.0;
+        let mut phase = 0.0f32;
+        let mut signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                phase += 2.0 * PI * base_freq * (1.0 + 0.005 * (2.0 * PI * 5.0 * t).sin()) / SAMPLE_RATE as f32;
+                0.5 * phase.sin()
+            })
+            .collect();
+        signal[SAMPLE_RATE as usize / 2] = 1.0;
+        signal[SAMPLE_RATE as usize / 2 + 1] = -1.0;
+
+        // 2. Act
+        let profile = detect_profile(&signal, SAMPLE_RATE, &AnalysisParams::default());
+
+        // 3. Assert
+        assert_eq!(profile, AnalysisProfile::Natural);
+    }
+}
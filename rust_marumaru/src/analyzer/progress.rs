@@ -0,0 +1,164 @@
+// src/analyzer/progress.rs
+
+use super::error::AnalyzeError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// 解析パイプラインの段階
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnalysisStage {
+    #[default]
+    Preprocess,
+    F0,
+    PitchSync,
+    ModeAnalysis,
+    Quality,
+}
+
+/// 別スレッドから解析を中断するためのトークン (複製したものはすべて同じ状態を共有する)
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// 同じトークンを複製したものかどうか
+    pub fn ptr_eq(&self, other: &CancelToken) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// 進捗コールバック (段階, 解析全体の進捗 0.0〜1.0)
+pub type ProgressCallback = Arc<dyn Fn(AnalysisStage, f32) + Send + Sync>;
+
+/// 解析の進捗通知とキャンセル確認
+/// - 各段階には解析全体 (0.0〜1.0) のうちの区間を割り当て、段階内の進捗をその区間に換算して通知する
+/// - フレームのループでは frame() を呼び、キャンセルされていれば AnalyzeError::Cancelled を返す
+#[derive(Clone)]
+pub struct AnalysisProgress {
+    callback: Option<ProgressCallback>,
+    cancel: CancelToken,
+    stage: AnalysisStage,
+    start: f32,
+    end: f32,
+}
+
+impl Default for AnalysisProgress {
+    /// 通知なし・キャンセルなし
+    fn default() -> Self {
+        AnalysisProgress { callback: None, cancel: CancelToken::default(), stage: AnalysisStage::default(), start: 0.0, end: 1.0 }
+    }
+}
+
+impl AnalysisProgress {
+    pub fn new(callback: Option<ProgressCallback>, cancel: CancelToken) -> Self {
+        AnalysisProgress { callback, cancel, ..AnalysisProgress::default() }
+    }
+
+    pub fn cancel_token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// 現在の区間のうち [from, to] を stage として切り出し、開始を通知する
+    pub fn stage(&self, stage: AnalysisStage, from: f32, to: f32) -> Result<AnalysisProgress, AnalyzeError> {
+        let span = self.end - self.start;
+        let child = AnalysisProgress {
+            callback: self.callback.clone(),
+            cancel: self.cancel.clone(),
+            stage,
+            start: self.start + span * from.clamp(0.0, 1.0),
+            end: self.start + span * to.clamp(0.0, 1.0),
+        };
+        child.update(0.0)?;
+        Ok(child)
+    }
+
+    /// 現在の区間を count 等分した index 番目を、同じ段階のまま切り出す (チャンネルやモードごとの繰り返し用)
+    pub fn part(&self, index: usize, count: usize) -> Result<AnalysisProgress, AnalyzeError> {
+        let count = count.max(1) as f32;
+        self.stage(self.stage, index as f32 / count, (index + 1) as f32 / count)
+    }
+
+    /// キャンセルされていればエラーを返す
+    pub fn check_cancelled(&self) -> Result<(), AnalyzeError> {
+        if self.cancel.is_cancelled() {
+            Err(AnalyzeError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 段階内の進捗 (0.0〜1.0) を通知する
+    pub fn update(&self, fraction: f32) -> Result<(), AnalyzeError> {
+        self.check_cancelled()?;
+        if let Some(callback) = &self.callback {
+            callback(self.stage, self.start + (self.end - self.start) * fraction.clamp(0.0, 1.0));
+        }
+        Ok(())
+    }
+
+    /// フレームのループから呼ぶ (キャンセルは毎回確認し、通知は 1% 進むごとに行う)
+    pub fn frame(&self, index: usize, total: usize) -> Result<(), AnalyzeError> {
+        self.check_cancelled()?;
+        if total > 0 && (index == 0 || index * 100 / total != (index - 1) * 100 / total) {
+            self.update(index as f32 / total as f32)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_progress_is_mapped_into_stage_ranges() {
+        // 1. Arrange
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        let progress = AnalysisProgress::new(
+            Some(Arc::new(move |stage, value| sink.lock().unwrap().push((stage, value)))),
+            CancelToken::new(),
+        );
+
+        // 2. Act: 全体の後半を ModeAnalysis とし、その2つ目 (0.75〜1.0) の中間を通知する
+        let mode = progress.stage(AnalysisStage::ModeAnalysis, 0.5, 1.0).unwrap();
+        mode.part(1, 2).unwrap().update(0.5).unwrap();
+
+        // 3. Assert
+        assert_eq!(*reports.lock().unwrap(), vec![
+            (AnalysisStage::ModeAnalysis, 0.5),
+            (AnalysisStage::ModeAnalysis, 0.75),
+            (AnalysisStage::ModeAnalysis, 0.875),
+        ]);
+    }
+
+    #[test]
+    fn test_cancelled_token_stops_frame_loop() {
+        // 1. Arrange
+        let token = CancelToken::new();
+        let progress = AnalysisProgress::new(None, token.clone());
+
+        // 2. Act
+        let before = progress.frame(10, 100);
+        token.cancel();
+        let after = progress.frame(11, 100);
+
+        // 3. Assert
+        assert!(before.is_ok());
+        assert_eq!(after.unwrap_err(), AnalyzeError::Cancelled);
+    }
+}
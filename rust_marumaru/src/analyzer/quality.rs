@@ -1,33 +1,44 @@
 // src/analyzer/quality.rs
-use super::types::QualityMetrics;
+use crate::log_message_internal;
+use super::error::AnalyzeError;
+use super::mode_time;
+use super::progress::AnalysisProgress;
+use super::resample::resample_segment;
+use super::types::{AnalysisParams, QualityFlags, QualityMetrics, SectionQuality};
 use rustfft::{FftPlanner, num_complex::Complex};
+use std::ops::Range;
+
+// --- 品質検査のパラメータ ---
+const CYCLE_COMPARE_LEN: usize = 256; // 周期間相関を比べるときに各周期をそろえる長さ
+const SNR_CEILING_DB: f32 = 120.0;    // 誤差がほぼ0のときのSNR上限
 
 /// 線形補間を使ってウェーブテーブルからサンプルを読み出すヘルパー関数
 fn sample_table_linear(table: &[f32], phase: f32) -> f32 {
     let table_len = table.len() as f32;
     if table_len < 2.0 { return 0.0; }
     let index_f = phase * table_len;
-    let idx0 = index_f.floor() as usize;
+    let idx0 = (index_f.floor() as usize).min(table.len() - 1);
     let idx1 = (idx0 + 1) % table.len();
     let frac = index_f - idx0 as f32;
     table[idx0] * (1.0 - frac) + table[idx1] * frac
 }
 
-/// ウェーブテーブルとF0カーブから音声を再合成する
+/// ウェーブテーブル列とF0カーブから音声を再合成する
+/// - テーブルが複数ある場合は、音声全体を等分して時間順に割り当てる
 fn resynthesize_audio(
-    table: &[f32],
+    tables: &[Vec<f32>],
     f0_curve: &[f32],
     sample_rate: u32,
     output_len: usize,
+    hop_size: usize,
 ) -> Vec<f32> {
-    if table.is_empty() || f0_curve.is_empty() {
+    if tables.is_empty() || f0_curve.is_empty() {
         return vec![0.0; output_len];
     }
     let mut output = vec![0.0; output_len];
     let mut phase = 0.0; // 0.0 ~ 1.0
-    let hop_size = 512; // f0_estimator.rs と合わせる
 
-    for i in 0..output_len {
+    for (i, out) in output.iter_mut().enumerate() {
         let frame_idx = (i / hop_size).min(f0_curve.len() - 1);
         let f0 = f0_curve[frame_idx];
 
@@ -37,54 +48,250 @@ fn resynthesize_audio(
                 phase -= 1.0;
             }
         }
-        output[i] = sample_table_linear(table, phase);
+        let table_idx = (i * tables.len() / output_len).min(tables.len() - 1);
+        *out = sample_table_linear(&tables[table_idx], phase);
     }
     output
 }
 
+/// 再合成音 (正規化済みテーブル) を原音の音量変化に合わせる
+/// - hop_size ごとに最小二乗ゲインを求め、ブロック中心の間を線形補間して掛ける
+fn match_envelope(original: &[f32], resynth: &[f32], hop_size: usize) -> Vec<f32> {
+    let gains: Vec<f32> = original.chunks(hop_size)
+        .zip(resynth.chunks(hop_size))
+        .map(|(o, r)| {
+            let energy: f32 = r.iter().map(|&s| s * s).sum();
+            if energy > 1e-12 {
+                o.iter().zip(r).map(|(&a, &b)| a * b).sum::<f32>() / energy
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    if gains.is_empty() {
+        return resynth.to_vec();
+    }
+
+    resynth.iter().enumerate()
+        .map(|(i, &s)| {
+            let block_pos = (i as f32 - hop_size as f32 * 0.5) / hop_size as f32;
+            let idx0 = block_pos.floor().max(0.0) as usize;
+            let idx0 = idx0.min(gains.len() - 1);
+            let idx1 = (idx0 + 1).min(gains.len() - 1);
+            let frac = (block_pos - idx0 as f32).clamp(0.0, 1.0);
+            s * (gains[idx0] * (1.0 - frac) + gains[idx1] * frac)
+        })
+        .collect()
+}
+
+/// 2つの信号のピアソン相関
+fn pearson_correlation(a: &[f32], b: &[f32]) -> f32 {
+    let len = a.len().min(b.len());
+    if len == 0 {
+        return 0.0;
+    }
+    let mean_a: f32 = a[..len].iter().sum::<f32>() / len as f32;
+    let mean_b: f32 = b[..len].iter().sum::<f32>() / len as f32;
+
+    let mut cov = 0.0;
+    let mut var_a = 0.0;
+    let mut var_b = 0.0;
+    for i in 0..len {
+        let va = a[i] - mean_a;
+        let vb = b[i] - mean_b;
+        cov += va * vb;
+        var_a += va * va;
+        var_b += vb * vb;
+    }
+    let denom = (var_a * var_b).sqrt();
+    if denom > 1e-12 { cov / denom } else { 0.0 }
+}
+
+/// 原音に対する誤差のSNR [dB]
+fn snr_db(original: &[f32], matched: &[f32]) -> f32 {
+    let signal: f32 = original.iter().map(|&s| s * s).sum();
+    let noise: f32 = original.iter().zip(matched).map(|(&o, &m)| (o - m).powi(2)).sum();
+    if signal <= 1e-12 {
+        return 0.0;
+    }
+    if noise <= 1e-12 {
+        return SNR_CEILING_DB;
+    }
+    (10.0 * (signal / noise).log10()).min(SNR_CEILING_DB)
+}
+
+/// Hann窓で fft_size ごとに区切った平均パワースペクトル (区間が短い場合はゼロ詰めの1フレーム)
+fn average_power_spectrum(signal: &[f32], fft_size: usize, planner: &mut FftPlanner<f32>) -> Vec<f32> {
+    let fft = planner.plan_fft_forward(fft_size);
+    let hop = (fft_size / 2).max(1);
+    let window: Vec<f32> = (0..fft_size)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / fft_size as f32).cos())
+        .collect();
+
+    let mut power = vec![0.0; fft_size / 2 + 1];
+    let mut frames = 0;
+    let mut start = 0;
+    loop {
+        let mut buffer: Vec<Complex<f32>> = (0..fft_size)
+            .map(|i| Complex::new(signal.get(start + i).copied().unwrap_or(0.0) * window[i], 0.0))
+            .collect();
+        fft.process(&mut buffer);
+        for (p, c) in power.iter_mut().zip(&buffer) {
+            *p += c.norm_sqr();
+        }
+        frames += 1;
+        start += hop;
+        if start + fft_size > signal.len() {
+            break;
+        }
+    }
+    power.iter_mut().for_each(|p| *p /= frames as f32);
+    power
+}
+
+/// 平均パワースペクトルのエネルギー差 (Σ|P_orig - P_resynth| / ΣP_orig)
+fn spectral_energy_diff(original: &[f32], matched: &[f32], fft_size: usize, planner: &mut FftPlanner<f32>) -> f32 {
+    let orig_power = average_power_spectrum(original, fft_size, planner);
+    let resynth_power = average_power_spectrum(matched, fft_size, planner);
+    let total: f32 = orig_power.iter().sum();
+    if total <= 1e-12 {
+        return 0.0;
+    }
+    orig_power.iter().zip(&resynth_power).map(|(a, b)| (a - b).abs()).sum::<f32>() / total
+}
+
+/// 隣り合う周期どうしの平均相関 (各周期を同じ長さにそろえて比較する)
+fn cycle_correlation(audio: &[f32], cycles: &[(f32, f32)]) -> f32 {
+    let resampled: Vec<Vec<f32>> = cycles.iter()
+        .map(|&(start, length)| resample_segment(audio, start, length, CYCLE_COMPARE_LEN))
+        .collect();
+    if resampled.len() < 2 {
+        return 0.0;
+    }
+    resampled.windows(2)
+        .map(|pair| pearson_correlation(&pair[0], &pair[1]))
+        .sum::<f32>() / (resampled.len() - 1) as f32
+}
+
+/// 検出した周期長と、その位置のF0から期待される周期長との整合度
+/// - 1.0 - 平均相対誤差 (0.0 〜 1.0)
+fn period_consistency(cycles: &[(f32, f32)], f0_curve: &[f32], sample_rate: u32, hop_size: usize) -> f32 {
+    let errors: Vec<f32> = cycles.iter()
+        .filter_map(|&(start, length)| {
+            let frame_idx = ((start + length * 0.5) / hop_size as f32) as usize;
+            match f0_curve.get(frame_idx) {
+                Some(&f0) if f0 > 0.0 => {
+                    let expected = sample_rate as f32 / f0;
+                    Some((length - expected).abs() / expected)
+                }
+                _ => None,
+            }
+        })
+        .collect();
+    if errors.is_empty() {
+        return 0.0;
+    }
+    (1.0 - errors.iter().sum::<f32>() / errors.len() as f32).clamp(0.0, 1.0)
+}
+
+/// F0が得られなかった (無声) フレームの割合
+/// - F0カーブは無声区間をスプライン補間で埋めているため、補間前の voicing マスクから数える
+fn nan_ratio(voicing: &[bool]) -> f32 {
+    if voicing.is_empty() {
+        return 1.0;
+    }
+    voicing.iter().filter(|&&voiced| !voiced).count() as f32 / voicing.len() as f32
+}
+
+/// 区間評価で共通に使う入力
+struct SectionInput<'a> {
+    original: &'a [f32], // 原音
+    resynth: &'a [f32],  // 再合成音
+    matched: &'a [f32],  // 原音の音量変化に合わせた再合成音
+    cycles: &'a [(f32, f32)], // 原音の周期境界 (先頭位置, 長さ)
+    f0_curve: &'a [f32],
+    voicing: &'a [bool],      // f0_curve と同じフレームの有声/無声判定
+    sample_rate: u32,
+}
+
+/// 区間 [range) について各指標を求める
+fn inspect_section(
+    input: &SectionInput,
+    range: Range<usize>,
+    params: &AnalysisParams,
+    planner: &mut FftPlanner<f32>,
+) -> SectionQuality {
+    let SectionInput { original, resynth, matched, cycles, f0_curve, voicing, sample_rate } = *input;
+    if range.is_empty() {
+        return SectionQuality::default();
+    }
+    let original_section = &original[range.clone()];
+    let matched_section = &matched[range.clone()];
+
+    // 周期の中心が区間内にあるものをその区間の周期とする
+    let section_cycles: Vec<(f32, f32)> = cycles.iter()
+        .cloned()
+        .filter(|&(start, length)| range.contains(&((start + length * 0.5) as usize)))
+        .collect();
+    let voicing_range = (range.start / params.hop_size).min(voicing.len())
+        ..range.end.div_ceil(params.hop_size).min(voicing.len());
+
+    let cycle_correlation = cycle_correlation(original, &section_cycles);
+    let spectral_energy_diff = spectral_energy_diff(original_section, matched_section, params.fft_size, planner);
+    let nan_ratio = nan_ratio(&voicing[voicing_range]);
+    let snr_db = snr_db(original_section, matched_section);
+    let period_consistency = period_consistency(&section_cycles, f0_curve, sample_rate, params.hop_size);
+
+    SectionQuality {
+        correlation: pearson_correlation(original_section, &resynth[range]).max(0.0),
+        cycle_correlation,
+        spectral_energy_diff,
+        snr_db,
+        period_consistency,
+        nan_ratio,
+        flags: QualityFlags::evaluate(cycle_correlation, spectral_energy_diff, nan_ratio, snr_db, period_consistency),
+    }
+}
 
 /// 解析結果の品質を検査する
 pub fn inspect_quality(
     original_audio: &[f32],
     final_tables: &[Vec<f32>],
     f0_curve: &[f32], // F0カーブを引数として受け取るように変更
+    voicing: &[bool],  // F0カーブの有声/無声判定 (NaN率の計算に使う)
     sample_rate: u32,  // サンプルレートを引数として受け取るように変更
-) -> Result<QualityMetrics, String> {
-    println!("[INFO] Quality inspection started.");
+    params: &AnalysisParams,
+    progress: &AnalysisProgress,
+) -> Result<QualityMetrics, AnalyzeError> {
 
-    // 代表として最初のテーブルを使用
-    let main_table = match final_tables.get(0) {
-        Some(table) => table,
-        None => return Ok(QualityMetrics { correlation: 0.0, spectral_residual: 1.0, nan_ratio: 0.0 }),
-    };
+    if final_tables.is_empty() || original_audio.is_empty() {
+        return Ok(QualityMetrics {
+            correlation: 0.0,
+            spectral_residual: 1.0,
+            nan_ratio: nan_ratio(voicing),
+            overall: SectionQuality::default(),
+            core: SectionQuality::default(),
+            loop_section: SectionQuality::default(),
+            release: SectionQuality::default(),
+        });
+    }
 
-    // 1. 音声を再合成
-    let resynthesized_audio = resynthesize_audio(main_table, f0_curve, sample_rate, original_audio.len());
+    // 1. 音声を再合成し、原音の音量変化に合わせたものも用意する
+    let resynthesized_audio = resynthesize_audio(final_tables, f0_curve, sample_rate, original_audio.len(), params.hop_size);
+    let matched_audio = match_envelope(original_audio, &resynthesized_audio, params.hop_size);
 
-    // 2. 原音と再構成音の相関を計算 (簡易版: 正規化されたドット積)
-    let mean_orig: f32 = original_audio.iter().sum::<f32>() / original_audio.len() as f32;
-    let mean_resynth: f32 = resynthesized_audio.iter().sum::<f32>() / resynthesized_audio.len() as f32;
-    
-    let mut cov = 0.0;
-    let mut var_orig = 0.0;
-    let mut var_resynth = 0.0;
+    progress.update(0.3)?;
 
-    for i in 0..original_audio.len() {
-        let v_orig = original_audio[i] - mean_orig;
-        let v_resynth = resynthesized_audio[i] - mean_resynth;
-        cov += v_orig * v_resynth;
-        var_orig += v_orig.powi(2);
-        var_resynth += v_resynth.powi(2);
-    }
-    let correlation = cov / (var_orig.sqrt() * var_resynth.sqrt());
+    // 2. 原音と再構成音の相関を計算
+    let correlation = pearson_correlation(original_audio, &resynthesized_audio);
 
     // 3. スペクトル残差を計算
     let mut planner = FftPlanner::new();
     let fft = planner.plan_fft_forward(original_audio.len().next_power_of_two());
-    
+
     let mut orig_spec = original_audio.to_vec();
     orig_spec.resize(fft.len(), 0.0);
-    let mut resynth_spec = resynthesized_audio;
+    let mut resynth_spec = resynthesized_audio.clone();
     resynth_spec.resize(fft.len(), 0.0);
 
     let mut orig_complex: Vec<_> = orig_spec.into_iter().map(|s| Complex::new(s, 0.0)).collect();
@@ -102,10 +309,105 @@ pub fn inspect_quality(
     }
     let spectral_residual = (residual_sum / orig_power_sum).sqrt();
 
-    println!("[INFO] Quality inspection finished.");
+    progress.update(0.5)?;
+
+    // 4. 原音の周期境界を検出し、全体と Core / Loop / Release の各区間を評価する
+    //    (周期が取れない音声では周期間相関・周期整合度を 0 とする)
+    let cycles = mode_time::detect_cycle_boundaries(original_audio, sample_rate, f0_curve, params)
+        .unwrap_or_default();
+    let total_len = original_audio.len();
+    let core_end = (total_len as f32 * params.core_end_ratio.clamp(0.0, 1.0)).round() as usize;
+    let release_start = ((total_len as f32 * params.release_start_ratio.clamp(0.0, 1.0)).round() as usize).max(core_end);
+
+    let input = SectionInput {
+        original: original_audio,
+        resynth: &resynthesized_audio,
+        matched: &matched_audio,
+        cycles: &cycles,
+        f0_curve,
+        voicing,
+        sample_rate,
+    };
+    let mut section = |range: Range<usize>| inspect_section(&input, range, params, &mut planner);
+    progress.update(0.6)?;
+    let overall = section(0..total_len);
+    progress.update(0.85)?;
+    let core = section(0..core_end);
+    let loop_section = section(core_end..release_start);
+    let release = section(release_start..total_len);
+
+    log_message_internal("Rust", &format!(
+        "Quality: cycle R = {:.3}, energy diff = {:.2}%, SNR = {:.1} dB, period consistency = {:.3}, NaN ratio = {:.1}%",
+        overall.cycle_correlation,
+        overall.spectral_energy_diff * 100.0,
+        overall.snr_db,
+        overall.period_consistency,
+        overall.nan_ratio * 100.0
+    ));
+    progress.update(1.0)?;
     Ok(QualityMetrics {
         correlation: correlation.max(0.0),
         spectral_residual: spectral_residual.max(0.0),
-        nan_ratio: 0.0, // TODO: f0_curveのNaN率を計算
+        nan_ratio: overall.nan_ratio,
+        overall,
+        core,
+        loop_section,
+        release,
     })
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_quality_metrics_on_matching_table() {
+        // 1. Arrange: 220Hz のサイン波と、それに一致するテーブル
+        const SAMPLE_RATE: u32 = 48000;
+        let params = AnalysisParams::default();
+        let signal: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| (2.0 * PI * 220.0 * i as f32 / SAMPLE_RATE as f32).sin() * 0.5)
+            .collect();
+        let table: Vec<f32> = (0..2048).map(|j| (2.0 * PI * j as f32 / 2048.0).sin()).collect();
+        let f0_curve = vec![220.0; SAMPLE_RATE as usize / params.hop_size + 1];
+        let voicing = vec![true; f0_curve.len()];
+
+        // 2. Act
+        let metrics = inspect_quality(&signal, &[table], &f0_curve, &voicing, SAMPLE_RATE, &params, &AnalysisProgress::default()).unwrap();
+
+        // 3. Assert: すべての区間で目標を満たす
+        assert!(metrics.passed(), "metrics = {:?}", metrics);
+        assert!(metrics.overall.snr_db > 30.0, "SNR = {}", metrics.overall.snr_db);
+        assert!(metrics.overall.period_consistency > 0.99);
+        assert_eq!(metrics.nan_ratio, 0.0);
+    }
+
+    #[test]
+    fn test_quality_flags_detect_noise_and_unvoiced_frames() {
+        // 1. Arrange: 周期性のないノイズと、半分が無声のF0カーブ (無声フレームは補間で埋まっている)
+        const SAMPLE_RATE: u32 = 48000;
+        let params = AnalysisParams::default();
+        let mut seed = 1u32;
+        let noise: Vec<f32> = (0..SAMPLE_RATE as usize / 2)
+            .map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (seed >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+            })
+            .collect();
+        let table: Vec<f32> = (0..2048).map(|j| (2.0 * PI * j as f32 / 2048.0).sin()).collect();
+        let frames = noise.len() / params.hop_size + 1;
+        let f0_curve = vec![220.0; frames];
+        let voicing: Vec<bool> = (0..frames).map(|i| i % 2 == 0).collect();
+
+        // 2. Act
+        let metrics = inspect_quality(&noise, &[table], &f0_curve, &voicing, SAMPLE_RATE, &params, &AnalysisProgress::default()).unwrap();
+
+        // 3. Assert
+        assert!(!metrics.overall.flags.cycle_correlation_ok);
+        assert!(!metrics.overall.flags.spectral_energy_ok);
+        assert!(!metrics.overall.flags.nan_ratio_ok);
+        assert!(!metrics.overall.flags.snr_ok);
+        assert!(!metrics.passed());
+    }
+}
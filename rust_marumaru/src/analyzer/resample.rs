@@ -0,0 +1,172 @@
+// src/analyzer/resample.rs
+
+use std::f32::consts::PI;
+
+// --- 補間カーネルのパラメータ ---
+const SINC_HALF_ZEROS: usize = 16; // 窓付きsincの片側ゼロ交差数 (タップ数 = 2 * 16 / cutoff)
+
+/// Blackman窓付きsincカーネル
+/// - x: カーネル中心からの距離 (出力側のサンプル単位)
+fn windowed_sinc(x: f32) -> f32 {
+    let half = SINC_HALF_ZEROS as f32;
+    if x.abs() >= half {
+        return 0.0;
+    }
+    let sinc = if x.abs() < 1e-6 { 1.0 } else { (PI * x).sin() / (PI * x) };
+    let n = 0.5 + x / (2.0 * half); // 窓の位置 (0〜1)
+    let window = 0.42 - 0.5 * (2.0 * PI * n).cos() + 0.08 * (4.0 * PI * n).cos();
+    sinc * window
+}
+
+/// 帯域制限付きで任意の小数位置の値を補間する
+/// - cutoff: 元信号のナイキストに対するカットオフ比 (1.0 = 帯域制限なし, 0.5 = 半分に制限)
+/// - 範囲外のサンプルは0として扱う
+pub fn sinc_interpolate(signal: &[f32], position: f32, cutoff: f32) -> f32 {
+    let cutoff = cutoff.clamp(1e-3, 1.0);
+    let reach = (SINC_HALF_ZEROS as f32 / cutoff).ceil() as isize;
+    let center = position.floor() as isize;
+
+    let mut acc = 0.0;
+    for n in (center - reach + 1)..=(center + reach) {
+        if n < 0 || n as usize >= signal.len() {
+            continue;
+        }
+        acc += signal[n as usize] * cutoff * windowed_sinc((position - n as f32) * cutoff);
+    }
+    acc
+}
+
+/// 信号の [start, start + length) 区間を target_len サンプルへ帯域制限リサンプリングする
+/// - 縮小時 (length > target_len) はエイリアスを防ぐためカットオフを下げる
+pub fn resample_segment(signal: &[f32], start: f32, length: f32, target_len: usize) -> Vec<f32> {
+    if target_len == 0 {
+        return Vec::new();
+    }
+    let step = length / target_len as f32;
+    let cutoff = (1.0 / step).min(1.0);
+    (0..target_len)
+        .map(|j| sinc_interpolate(signal, start + j as f32 * step, cutoff))
+        .collect()
+}
+
+/// 1周期分の波形を周期的に扱い、target_len サンプルへ帯域制限リサンプリングする
+pub fn resample_cycle(cycle: &[f32], target_len: usize) -> Vec<f32> {
+    if cycle.is_empty() || target_len == 0 {
+        return vec![0.0; target_len];
+    }
+    // 周期境界で途切れないよう、前後にカーネル幅分だけ周期を連結してから補間する
+    let len = cycle.len();
+    let step = len as f32 / target_len as f32;
+    let cutoff = (1.0 / step).min(1.0);
+    let pad = ((SINC_HALF_ZEROS as f32 / cutoff).ceil() as usize).max(1);
+    let extended: Vec<f32> = (0..len + 2 * pad)
+        .map(|i| cycle[(i + len * pad - pad) % len])
+        .collect();
+    (0..target_len)
+        .map(|j| sinc_interpolate(&extended, pad as f32 + j as f32 * step, cutoff))
+        .collect()
+}
+
+/// 最大公約数
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// --- ポリフェーズ変換のパラメータ ---
+const MAX_POLYPHASE_PHASES: u32 = 1024; // これを超える変換比は係数を都度計算する
+
+/// サンプルレート from_rate の信号を to_rate へ変換する (窓付きsincのポリフェーズ実装)
+/// - 変換比 L/M (既約) の L 個の位相について係数を事前計算し、各出力サンプルで使い回す
+/// - ダウンサンプリング時はカットオフを L/M に下げてエイリアスを防ぐ
+/// - L が大きすぎる変換比は sinc_interpolate で係数を都度計算する
+pub fn resample_rate(signal: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || from_rate == 0 || to_rate == 0 || signal.is_empty() {
+        return signal.to_vec();
+    }
+    let g = gcd(from_rate, to_rate);
+    let (up, down) = ((to_rate / g) as usize, (from_rate / g) as usize);
+    let output_len = ((signal.len() as u64 * up as u64) / down as u64) as usize;
+    let cutoff = (up as f32 / down as f32).min(1.0);
+
+    if up as u32 > MAX_POLYPHASE_PHASES {
+        let step = down as f32 / up as f32;
+        return (0..output_len)
+            .map(|n| sinc_interpolate(signal, n as f32 * step, cutoff))
+            .collect();
+    }
+
+    // 1. 位相 p (出力位置の小数部 = p / up) ごとの係数表
+    //    taps[k] は入力 center - reach + 1 + k に掛ける係数
+    let reach = (SINC_HALF_ZEROS as f32 / cutoff).ceil() as isize;
+    let taps_len = (2 * reach) as usize;
+    let bank: Vec<Vec<f32>> = (0..up)
+        .map(|p| {
+            let frac = p as f32 / up as f32;
+            (0..taps_len)
+                .map(|k| {
+                    let distance = frac + reach as f32 - 1.0 - k as f32;
+                    cutoff * windowed_sinc(distance * cutoff)
+                })
+                .collect()
+        })
+        .collect();
+
+    // 2. 出力 n の位置は n * down / up (整数部 center, 位相 n * down % up)
+    (0..output_len)
+        .map(|n| {
+            let center = ((n * down) / up) as isize;
+            let taps = &bank[(n * down) % up];
+            let first = center - reach + 1;
+            let mut acc = 0.0;
+            for (k, &tap) in taps.iter().enumerate() {
+                let index = first + k as isize;
+                if index >= 0 && (index as usize) < signal.len() {
+                    acc += signal[index as usize] * tap;
+                }
+            }
+            acc
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resample_cycle_preserves_sine() {
+        // 1. Arrange: 109サンプルの1周期サイン波
+        let cycle: Vec<f32> = (0..109).map(|i| (2.0 * PI * i as f32 / 109.0).sin()).collect();
+
+        // 2. Act: 2048サンプルへリサンプリング
+        let resampled = resample_cycle(&cycle, 2048);
+
+        // 3. Assert: 理想的なサイン波との誤差が十分小さい
+        let max_error = resampled.iter()
+            .enumerate()
+            .map(|(j, &s)| (s - (2.0 * PI * j as f32 / 2048.0).sin()).abs())
+            .fold(0.0, f32::max);
+        assert!(max_error < 1e-3, "max error = {}", max_error);
+    }
+
+    #[test]
+    fn test_resample_rate_matches_sinc_interpolation() {
+        // 1. Arrange: 22.05kHz の 1kHz サイン波
+        let signal: Vec<f32> = (0..2205).map(|i| (2.0 * PI * 1000.0 * i as f32 / 22050.0).sin()).collect();
+
+        // 2. Act: 44.1kHz / 48kHz へ変換
+        let doubled = resample_rate(&signal, 22050, 44100);
+        let converted = resample_rate(&signal, 22050, 48000);
+
+        // 3. Assert: 長さが比率どおりで、中央部は理想的なサイン波と一致する
+        assert_eq!(doubled.len(), 4410);
+        assert_eq!(converted.len(), 4800);
+        let max_error = converted.iter()
+            .enumerate()
+            .skip(100)
+            .take(4600)
+            .map(|(n, &s)| (s - (2.0 * PI * 1000.0 * n as f32 / 48000.0).sin()).abs())
+            .fold(0.0, f32::max);
+        assert!(max_error < 1e-3, "max error = {}", max_error);
+    }
+}
@@ -1,18 +1,91 @@
 // analyzer/types.rs
 
+use super::error::AnalyzeError;
+
+// --- 品質検査の目標値 (設計書 7. 品質検査) ---
+pub const MIN_CYCLE_CORRELATION: f32 = 0.85;   // 周期間相関 R > 0.85
+pub const MAX_SPECTRAL_ENERGY_DIFF: f32 = 0.05; // 原音と再構成音のスペクトルエネルギー差 < 5%
+pub const MAX_NAN_RATIO: f32 = 0.1;            // F0が得られないフレームの割合 < 10%
+pub const MIN_SNR_DB: f32 = 10.0;              // 再合成誤差のSNR > 10dB (誤差のパワーが原音の 1/10 未満)
+pub const MIN_PERIOD_CONSISTENCY: f32 = 0.97;  // 周期長とF0の平均誤差 < 3% (設計書 8. の F0誤差 < 3% に合わせる)
+
+/// 目標値に対する合否
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QualityFlags {
+    pub cycle_correlation_ok: bool, // 周期間相関が MIN_CYCLE_CORRELATION を超えている
+    pub spectral_energy_ok: bool,   // スペクトルエネルギー差が MAX_SPECTRAL_ENERGY_DIFF 未満
+    pub nan_ratio_ok: bool,         // NaN率が MAX_NAN_RATIO 未満
+    pub snr_ok: bool,               // SNRが MIN_SNR_DB を超えている
+    pub period_consistency_ok: bool, // 周期整合度が MIN_PERIOD_CONSISTENCY を超えている
+}
+
+impl QualityFlags {
+    /// 各指標を目標値と比較する
+    pub fn evaluate(cycle_correlation: f32, spectral_energy_diff: f32, nan_ratio: f32, snr_db: f32, period_consistency: f32) -> Self {
+        Self {
+            cycle_correlation_ok: cycle_correlation > MIN_CYCLE_CORRELATION,
+            spectral_energy_ok: spectral_energy_diff < MAX_SPECTRAL_ENERGY_DIFF,
+            nan_ratio_ok: nan_ratio < MAX_NAN_RATIO,
+            snr_ok: snr_db > MIN_SNR_DB,
+            period_consistency_ok: period_consistency > MIN_PERIOD_CONSISTENCY,
+        }
+    }
+
+    /// すべての目標を満たしているか
+    pub fn passed(&self) -> bool {
+        self.cycle_correlation_ok && self.spectral_energy_ok && self.nan_ratio_ok && self.snr_ok && self.period_consistency_ok
+    }
+}
+
+/// 区間 (全体 / Core / Loop / Release) ごとの品質指標
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SectionQuality {
+    pub correlation: f32,          // 原音と再合成音の相関
+    pub cycle_correlation: f32,    // 隣り合う周期どうしの平均相関
+    pub spectral_energy_diff: f32, // 平均パワースペクトルのエネルギー差 (原音のエネルギーに対する比)
+    pub snr_db: f32,               // 原音に対する再合成誤差のSNR [dB]
+    pub period_consistency: f32,   // 検出した周期長とF0カーブの整合度 (1.0 = 完全一致)
+    pub nan_ratio: f32,            // 無声と判定されたフレームの割合 (voicing マスクから求める)
+    pub flags: QualityFlags,       // 目標値に対する合否
+}
+
 /// 品質指標を格納する構造体
 #[derive(Debug, Clone, PartialEq)]
 pub struct QualityMetrics {
     pub correlation: f32,       // 平均相関
     pub spectral_residual: f32, // スペクトル残差
     pub nan_ratio: f32,         // NaN率
+    pub overall: SectionQuality, // 全体の詳細指標
+    pub core: SectionQuality,    // Core区間 (先頭 〜 core_end_ratio)
+    pub loop_section: SectionQuality, // Loop区間 (core_end_ratio 〜 release_start_ratio)
+    pub release: SectionQuality, // Release区間 (release_start_ratio 〜 末尾)
+}
+
+impl QualityMetrics {
+    /// モード間の比較に使う総合スコア (高いほど良い)
+    pub fn score(&self) -> f32 {
+        self.correlation * (1.0 - self.spectral_residual.clamp(0.0, 1.0)) * (1.0 - self.nan_ratio)
+    }
+
+    /// 全体・各区間のすべてが目標を満たしているか
+    pub fn passed(&self) -> bool {
+        [&self.overall, &self.core, &self.loop_section, &self.release]
+            .iter()
+            .all(|section| section.flags.passed())
+    }
 }
 
 /// 解析結果を格納する構造体
 #[derive(Debug, Clone)]
 pub struct AnalysisResult {
+    pub sample_rate: u32,       // 解析した音声のサンプルレート (Core/Release はこのレートの時間軸)
     pub f0_curve: Vec<f32>,
     pub confidence: Vec<f32>,
+    pub voicing: Vec<bool>,     // フレームごとの有声/無声判定 (f0_curveと同じ長さ)
+    pub target_f0: f32,         // DynamicPitchSync の基準ピッチ (有声フレームの平均F0)
+    pub pitch_contour: Vec<f32>, // target_f0 に対するピッチ変化 [cent] (再生時の変調ソース)
+    pub pitch_contour_rate: f32, // pitch_contour のフレームレート [frames/s] (= sample_rate / hop_size)
+    pub cycle_boundaries: Vec<(f32, f32)>, // 原音の周期境界 (開始位置, 周期長) [サンプル]
     pub tables: Vec<Vec<f32>>,
     pub core_wave: Vec<f32>,
     pub loop_wave: Vec<f32>,
@@ -22,6 +95,84 @@ pub struct AnalysisResult {
     pub loop_gain: Vec<f32>,    // Loopセクションの振幅プロファイル
     pub release_gain: Vec<f32>, // Releaseセクションの振幅プロファイル
     pub quality: QualityMetrics,
+    pub profile: AnalysisProfile, // 実際に適用されたプロファイル (Autoの場合は判定結果)
+    pub mode_decision: ModeDecision,       // 採用したモードとその理由
+    pub mode_candidates: Vec<ModeCandidate>, // 評価した各モードの結果 (スコア降順)
+}
+
+/// 複数チャンネル音声の解析結果
+/// - channels の並び: MidOnly = [Mid], PerChannel = 入力チャンネル順, MidSide = [Mid, Side]
+/// - 全チャンネルで Mid から求めた F0 と DynamicPitchSync の伸縮を共有するため、テーブルの位相がそろう
+#[derive(Debug, Clone)]
+pub struct MultichannelAnalysisResult {
+    pub channel_mode: ChannelMode,
+    pub channels: Vec<AnalysisResult>,
+}
+
+/// 複数チャンネル音声の扱い方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelMode {
+    MidOnly,    // 全チャンネルの平均 (Mid) のみを解析する
+    PerChannel, // チャンネルごとにテーブルを生成する
+    MidSide,    // Mid と Side ((L - R) / 2) のテーブルを生成する (2ch のみ)
+}
+
+/// 解析ドメイン (Time / Hybrid / Freq の各処理系)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalysisDomain {
+    Time,   // 時間領域 (周期切り出し)
+    Hybrid, // 低域Time + 高域Freq
+    Freq,   // 周波数領域
+}
+
+/// モードの選び方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeSelection {
+    Auto,    // 周期性の閾値で自動選択
+    Time,    // Timeモードを強制
+    Hybrid,  // Hybridモードを強制
+    Freq,    // Freqモードを強制
+    RankAll, // 3モードすべてを実行し、品質スコアが最も高いものを採用
+}
+
+/// モードが選ばれた理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeReason {
+    HighPeriodicity,   // 周期性が time_mode_threshold を超えた
+    MediumPeriodicity, // 周期性が hybrid_mode_threshold を超えた
+    LowPeriodicity,    // 周期性が hybrid_mode_threshold 以下
+    Forced,            // ユーザーが指定した
+    BestQuality,       // RankAll で品質スコアが最も高かった
+}
+
+/// モード判定の結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeDecision {
+    pub mode: AnalysisDomain,
+    pub reason: ModeReason,
+    pub periodicity: f32, // 判定に使った周期性 (F0の平均信頼度)
+}
+
+impl ModeDecision {
+    /// UIやログ向けの説明文を返す
+    pub fn describe(&self) -> String {
+        let why = match self.reason {
+            ModeReason::HighPeriodicity => "high periodicity",
+            ModeReason::MediumPeriodicity => "medium periodicity",
+            ModeReason::LowPeriodicity => "low periodicity",
+            ModeReason::Forced => "forced by user",
+            ModeReason::BestQuality => "best quality score among all modes",
+        };
+        format!("{:?} mode ({}, periodicity = {:.3})", self.mode, why, self.periodicity)
+    }
+}
+
+/// 1モード分の解析結果
+#[derive(Debug, Clone)]
+pub struct ModeCandidate {
+    pub mode: AnalysisDomain,
+    pub tables: Vec<Vec<f32>>,
+    pub quality: QualityMetrics,
 }
 
 /// 解析モードの選択
@@ -36,11 +187,220 @@ pub enum AnalysisMode {
 pub enum AnalysisProfile {
     Natural, // 自然音向け
     Electronic, // 電子音向け
+    Auto, // 入力音から自動判定
 }
 
-// 他にもFFTサイズや窓関数など、共通で使うパラメータがあればここに追加します
+/// F0推定に使う推定器の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PitchEstimatorKind {
+    Yin,      // 自己差分法
+    Cepstrum, // ケプストラム法
+    Hps,      // ハーモニック積スペクトル
+}
+
+/// 周期の先頭をどこに揃えるか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CycleAlignment {
+    ZeroCrossing, // 上昇ゼロクロス
+    Peak,         // 正のピーク
+}
+
+/// STFT等で使用する窓関数の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowType {
+    Hann,
+    Hamming,
+    Blackman,
+    Rectangular,
+}
+
+impl WindowType {
+    /// 指定した長さの窓係数を生成する
+    pub fn generate(&self, size: usize) -> Vec<f32> {
+        match self {
+            WindowType::Hann => apodize::hanning_iter(size).map(|w| w as f32).collect(),
+            WindowType::Hamming => apodize::hamming_iter(size).map(|w| w as f32).collect(),
+            WindowType::Blackman => apodize::blackman_iter(size).map(|w| w as f32).collect(),
+            WindowType::Rectangular => vec![1.0; size],
+        }
+    }
+}
+
+/// F0推定フレームに最低限含める min_f0 の周期数 (YINの差分関数は半フレーム分のラグしか見ないため)
+const F0_MIN_PERIODS: usize = 3;
+
+/// 解析パイプライン全体で共通に使うパラメータ
+#[derive(Debug, Clone, PartialEq)]
 pub struct AnalysisParams {
-    pub fft_size: usize,
-    pub hop_size: usize,
-    // ...
-}
\ No newline at end of file
+    // --- STFT / フレーム分割 ---
+    pub fft_size: usize,              // フレーム長 (F0推定・STFT共通)
+    pub hop_size: usize,              // ホップ長 (F0カーブ1フレームあたりのサンプル数)
+    pub window: WindowType,           // 窓関数
+
+    // --- F0探索範囲 ---
+    pub min_f0: f32,                  // 探索する最低周波数 [Hz]
+    pub max_f0: f32,                  // 探索する最高周波数 [Hz]
+    pub pitch_estimators: Vec<PitchEstimatorKind>, // 融合する推定器の組 (信頼度で加重平均)
+    pub voicing_threshold: f32,       // これ未満の信頼度は欠損 (無声) として扱う
+    pub yin_power_threshold: f32,     // YIN: これ未満のパワーのフレームは推定しない
+    pub yin_clarity_threshold: f32,   // YIN: これ未満の明瞭度のピークは採用しない
+
+    // --- プロファイル ---
+    pub profile: AnalysisProfile,     // 下記の前処理/抽出設定・モード閾値のプリセット (from_profile で適用, Autoの場合は解析時に入力音から判定して適用)
+
+    // --- 前処理 ---
+    pub normalize_target_dbfs: f32,   // RMS正規化の目標値 [dBFS]
+    pub dc_highpass: bool,            // DC除去ハイパスを適用するか
+    pub spectral_gate: bool,          // スペクトルゲートによるノイズ除去を適用するか
+    pub noise_gate_threshold: f32,    // スペクトルゲートの閾値 (ノイズ床に対する倍率)
+
+    // --- 周期抽出 ---
+    pub cycle_averaging: bool,        // true: 全周期を平均化, false: 最も安定した1周期をそのまま使う
+    pub cycle_alignment: CycleAlignment, // 最初の周期の先頭を揃える基準 (以降は相互相関で追従)
+    pub target_cycle_len: usize,      // 1周期をリサンプリングする長さ (2の冪)
+    pub freq_table_count: usize,      // Freqモードで生成する時間変化テーブルの数 (1 = 全体平均)
+
+    // --- DynamicPitchSync ---
+    pub pitch_sync: bool,             // ビブラート/グライドを音声レベルで平坦化してからテーブルを切り出すか
+
+    // --- モード判定 ---
+    pub mode_selection: ModeSelection, // Auto以外を指定すると閾値判定を行わない
+    pub time_mode_threshold: f32,     // この周期性を超えるとTimeモード
+    pub hybrid_mode_threshold: f32,   // この周期性を超えるとHybridモード (それ以下はFreq)
+
+    // --- セクション分割 ---
+    pub core_end_ratio: f32,          // Core終了位置 (テーブル長に対する比率)
+    pub release_start_ratio: f32,     // Release開始位置 (テーブル長に対する比率)
+
+    // --- チャンネル ---
+    pub channel_mode: ChannelMode,    // 複数チャンネル入力の解析方法 (analyze_multichannel で使用)
+    pub analysis_sample_rate: Option<u32>, // ファイル解析時のリサンプリング先 (None = ファイルのまま)
+}
+
+impl Default for AnalysisParams {
+    fn default() -> Self {
+        AnalysisParams {
+            fft_size: 2048,
+            hop_size: 512,
+            window: WindowType::Hann,
+            min_f0: 80.0,
+            max_f0: 1000.0,
+            pitch_estimators: vec![PitchEstimatorKind::Yin, PitchEstimatorKind::Hps],
+            voicing_threshold: 0.2,
+            yin_power_threshold: 0.1,
+            yin_clarity_threshold: 0.0,
+            profile: AnalysisProfile::Natural,
+            normalize_target_dbfs: -10.0,
+            dc_highpass: true,
+            spectral_gate: true,
+            noise_gate_threshold: 1.5,
+            cycle_averaging: true,
+            cycle_alignment: CycleAlignment::ZeroCrossing,
+            target_cycle_len: 2048,
+            freq_table_count: 1,
+            pitch_sync: true,
+            mode_selection: ModeSelection::Auto,
+            time_mode_threshold: 0.6,
+            hybrid_mode_threshold: 0.35,
+            core_end_ratio: 0.2,
+            release_start_ratio: 0.8,
+            channel_mode: ChannelMode::MidOnly,
+            analysis_sample_rate: None,
+        }
+    }
+}
+
+impl AnalysisParams {
+    /// プロファイルのプリセットからパラメータを生成する
+    pub fn from_profile(profile: AnalysisProfile) -> Self {
+        let mut params = AnalysisParams::default();
+        params.apply_profile(profile);
+        params
+    }
+
+    /// プロファイルに依存する設定 (前処理・周期抽出・モード閾値) を一括で書き換える
+    /// - Natural: ノイズ除去とDC除去を行い、周期を平均化して揺らぎを吸収する
+    /// - Electronic: 原波形を崩さないよう前処理を省き、1周期を厳密に切り出す
+    /// - Auto: 設定は変更せず、解析時に判定したプロファイルを適用する
+    /// - 解析は明示したプロファイルのプリセットを適用し直さないため、個別の設定はこの後に上書きすること
+    pub fn apply_profile(&mut self, profile: AnalysisProfile) {
+        self.profile = profile;
+        match profile {
+            AnalysisProfile::Natural => {
+                self.dc_highpass = true;
+                self.spectral_gate = true;
+                self.cycle_averaging = true;
+                self.time_mode_threshold = 0.6;
+                self.hybrid_mode_threshold = 0.35;
+            }
+            AnalysisProfile::Electronic => {
+                self.dc_highpass = false;
+                self.spectral_gate = false;
+                self.cycle_averaging = false;
+                self.time_mode_threshold = 0.8;
+                self.hybrid_mode_threshold = 0.5;
+            }
+            AnalysisProfile::Auto => {}
+        }
+    }
+
+    /// F0推定に使うフレーム長を返す
+    /// - min_f0 の周期が F0_MIN_PERIODS 個以上入るよう、fft_size から2の冪で伸ばす
+    pub fn f0_frame_size(&self, sample_rate: u32) -> usize {
+        let longest_period = (sample_rate as f32 / self.min_f0).ceil() as usize;
+        (longest_period * F0_MIN_PERIODS).max(self.fft_size).next_power_of_two()
+    }
+
+    /// パラメータの整合性を検査する
+    pub fn validate(&self) -> Result<(), AnalyzeError> {
+        let invalid = |message: String| Err(AnalyzeError::InvalidParams(message));
+        if self.fft_size < 64 {
+            return invalid(format!("fft_size must be at least 64 (got {}).", self.fft_size));
+        }
+        if self.hop_size == 0 || self.hop_size > self.fft_size {
+            return invalid(format!(
+                "hop_size must be in 1..={} (got {}).",
+                self.fft_size, self.hop_size
+            ));
+        }
+        if !(self.min_f0 > 0.0 && self.min_f0 < self.max_f0) {
+            return invalid(format!(
+                "Invalid F0 range: min_f0={} max_f0={}.",
+                self.min_f0, self.max_f0
+            ));
+        }
+        if !self.target_cycle_len.is_power_of_two() || self.target_cycle_len < 16 {
+            return invalid(format!(
+                "target_cycle_len must be a power of two >= 16 (got {}).",
+                self.target_cycle_len
+            ));
+        }
+        if self.freq_table_count == 0 {
+            return invalid("freq_table_count must be at least 1.".to_string());
+        }
+        if self.pitch_estimators.is_empty() {
+            return invalid("At least one pitch estimator must be enabled.".to_string());
+        }
+        if self.yin_power_threshold < 0.0 || !(0.0..=1.0).contains(&self.yin_clarity_threshold) {
+            return invalid(format!(
+                "Invalid YIN thresholds: power={} clarity={}.",
+                self.yin_power_threshold, self.yin_clarity_threshold
+            ));
+        }
+        if !(0.0..1.0).contains(&self.voicing_threshold) {
+            return invalid(format!("voicing_threshold must be in 0.0..1.0 (got {}).", self.voicing_threshold));
+        }
+        if self.hybrid_mode_threshold > self.time_mode_threshold {
+            return invalid("hybrid_mode_threshold must not exceed time_mode_threshold.".to_string());
+        }
+        if let Some(rate) = self.analysis_sample_rate {
+            if (rate as f32) < self.max_f0 * 2.0 {
+                return invalid(format!(
+                    "analysis_sample_rate must be at least twice max_f0 (got {} Hz).",
+                    rate
+                ));
+            }
+        }
+        Ok(())
+    }
+}
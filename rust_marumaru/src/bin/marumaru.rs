@@ -0,0 +1,418 @@
+// src/bin/marumaru.rs
+
+//! MaruMaruSynth のエンジンをプラグインなしで使うためのコマンドラインツール
+//! - 解析の進捗 (段階) は stderr、結果 (テキスト / --json) は stdout に出す
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rust_marumaru::analyzer::{
+    self, AnalysisParams, AnalysisProfile, AnalysisProgress, AnalysisStage, AnalyzeError, CancelToken, ChannelMode,
+    ModeSelection, MultichannelAnalysisResult,
+};
+use rust_marumaru::diagnostics::{self, ReportFormat, ReportOptions};
+use rust_marumaru::io::{self, Preset};
+use rust_marumaru::render::{self, RenderSettings};
+use rust_marumaru::ParamBundle;
+use serde::Serialize;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+
+/// 解析で扱う音声ファイルの拡張子
+const AUDIO_EXTENSIONS: [&str; 4] = ["wav", "aif", "aiff", "flac"];
+
+#[derive(Parser)]
+#[command(name = "marumaru", version, about = "MaruMaruSynth analysis / rendering tool")]
+struct Cli {
+    /// 結果を JSON で出力する
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 音声ファイルを解析し、モード・F0統計・品質・周期境界を表示する
+    Analyze {
+        input: PathBuf,
+        /// 解析結果をプリセット (ZIP) として保存する
+        #[arg(long)]
+        preset: Option<PathBuf>,
+        #[command(flatten)]
+        options: AnalysisOptions,
+    },
+    /// プリセットで MIDI ファイルを再生し、WAV に書き出す
+    Render {
+        preset: PathBuf,
+        midi: PathBuf,
+        output: PathBuf,
+        #[arg(long, default_value_t = 44100)]
+        sample_rate: u32,
+        /// 最後のイベントの後に書き出す秒数
+        #[arg(long, default_value_t = 2.0)]
+        tail: f32,
+    },
+    /// 音声ファイルを解析し、テーブルを 2048 サンプル/フレームのウェーブテーブル WAV として書き出す
+    ExportWavetable {
+        input: PathBuf,
+        output: PathBuf,
+        #[command(flatten)]
+        options: AnalysisOptions,
+    },
+    /// フォルダ内の音声ファイルをすべて解析する
+    Batch {
+        dir: PathBuf,
+        /// 各ファイルのプリセットを書き出すフォルダ
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+        #[command(flatten)]
+        options: AnalysisOptions,
+    },
+    /// 区間ごとのスペクトル・波形 (log/spectral_analysis_log.txt と同じ書式) を出力する
+    Report {
+        input: PathBuf,
+        /// 出力形式 (--json は --format json と同じ)
+        #[arg(long, value_enum, default_value_t = FormatArg::Text)]
+        format: FormatArg,
+        /// 出力先のファイル (省略時は stdout)
+        #[arg(long)]
+        output: Option<PathBuf>,
+        /// 解析する区間 "名前=開始:終了" [サンプル] (複数指定可, 省略時は3等分した Attack / Core / Sustain)
+        #[arg(long = "region", value_parser = parse_region)]
+        regions: Vec<(String, Range<usize>)>,
+        /// 解析も行い、倍音ピーク・F0カーブ・モードごとのテーブルを加える
+        #[arg(long)]
+        full: bool,
+        /// 区間ごとに出すスペクトルのビン数
+        #[arg(long, default_value_t = 20)]
+        bins: usize,
+        /// 区間ごと・テーブルごとに出すサンプル数
+        #[arg(long, default_value_t = 20)]
+        samples: usize,
+        #[command(flatten)]
+        options: AnalysisOptions,
+    },
+}
+
+#[derive(Args, Clone)]
+struct AnalysisOptions {
+    #[arg(long, value_enum, default_value_t = ProfileArg::Natural)]
+    profile: ProfileArg,
+    #[arg(long, value_enum, default_value_t = ModeArg::Auto)]
+    mode: ModeArg,
+    #[arg(long, value_enum, default_value_t = ChannelArg::Mid)]
+    channels: ChannelArg,
+    /// 解析前にこのサンプルレートへ変換する
+    #[arg(long)]
+    analysis_sample_rate: Option<u32>,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ProfileArg { Natural, Electronic, Auto }
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ModeArg { Auto, Time, Hybrid, Freq, RankAll }
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ChannelArg { Mid, PerChannel, MidSide }
+
+#[derive(ValueEnum, Clone, Copy)]
+enum FormatArg { Text, Csv, Json }
+
+/// "名前=開始:終了" を区間に変換する
+fn parse_region(value: &str) -> Result<(String, Range<usize>), String> {
+    let (name, range) = value.split_once('=').ok_or("expected NAME=START:END")?;
+    let (start, end) = range.split_once(':').ok_or("expected NAME=START:END")?;
+    let start: usize = start.trim().parse().map_err(|e| format!("invalid start: {}", e))?;
+    let end: usize = end.trim().parse().map_err(|e| format!("invalid end: {}", e))?;
+    if end <= start {
+        return Err("END must be greater than START".to_string());
+    }
+    Ok((name.to_string(), start..end))
+}
+
+impl AnalysisOptions {
+    fn to_params(&self) -> AnalysisParams {
+        let mut params = AnalysisParams::from_profile(match self.profile {
+            ProfileArg::Natural => AnalysisProfile::Natural,
+            ProfileArg::Electronic => AnalysisProfile::Electronic,
+            ProfileArg::Auto => AnalysisProfile::Auto,
+        });
+        params.mode_selection = match self.mode {
+            ModeArg::Auto => ModeSelection::Auto,
+            ModeArg::Time => ModeSelection::Time,
+            ModeArg::Hybrid => ModeSelection::Hybrid,
+            ModeArg::Freq => ModeSelection::Freq,
+            ModeArg::RankAll => ModeSelection::RankAll,
+        };
+        params.channel_mode = match self.channels {
+            ChannelArg::Mid => ChannelMode::MidOnly,
+            ChannelArg::PerChannel => ChannelMode::PerChannel,
+            ChannelArg::MidSide => ChannelMode::MidSide,
+        };
+        params.analysis_sample_rate = self.analysis_sample_rate;
+        params
+    }
+}
+
+/// 有声フレームの F0 統計
+#[derive(Serialize)]
+struct F0Stats {
+    target_f0: f32,
+    min: f32,
+    max: f32,
+    mean: f32,
+    voiced_ratio: f32,
+}
+
+/// 品質指標の要約
+#[derive(Serialize)]
+struct QualitySummary {
+    score: f32,
+    passed: bool,
+    correlation: f32,
+    cycle_correlation: f32,
+    spectral_energy_diff: f32,
+    snr_db: f32,
+    nan_ratio: f32,
+}
+
+/// analyze / batch の1ファイル分の結果
+#[derive(Serialize)]
+struct AnalyzeReport {
+    file: String,
+    sample_rate: u32,
+    channel_mode: String,
+    channels: usize,
+    profile: String,
+    mode: String,
+    mode_reason: String,
+    periodicity: f32,
+    f0: F0Stats,
+    quality: QualitySummary,
+    cycles_count: usize,
+    cycle_boundaries: Vec<(f32, f32)>,
+    section_lengths: [usize; 3],
+    preset: Option<String>,
+}
+
+impl AnalyzeReport {
+    fn new(path: &Path, result: &MultichannelAnalysisResult) -> Self {
+        let main = &result.channels[0];
+        let voiced: Vec<f32> = main.f0_curve.iter().copied().filter(|&f| f > 0.0).collect();
+        let f0 = F0Stats {
+            target_f0: main.target_f0,
+            min: voiced.iter().copied().reduce(f32::min).unwrap_or(0.0),
+            max: voiced.iter().copied().reduce(f32::max).unwrap_or(0.0),
+            mean: if voiced.is_empty() { 0.0 } else { voiced.iter().sum::<f32>() / voiced.len() as f32 },
+            voiced_ratio: voiced.len() as f32 / main.f0_curve.len().max(1) as f32,
+        };
+        let quality = QualitySummary {
+            score: main.quality.score(),
+            passed: main.quality.passed(),
+            correlation: main.quality.correlation,
+            cycle_correlation: main.quality.overall.cycle_correlation,
+            spectral_energy_diff: main.quality.overall.spectral_energy_diff,
+            snr_db: main.quality.overall.snr_db,
+            nan_ratio: main.quality.nan_ratio,
+        };
+        AnalyzeReport {
+            file: path.display().to_string(),
+            sample_rate: main.sample_rate,
+            channel_mode: format!("{:?}", result.channel_mode),
+            channels: result.channels.len(),
+            profile: format!("{:?}", main.profile),
+            mode: format!("{:?}", main.mode_decision.mode),
+            mode_reason: format!("{:?}", main.mode_decision.reason),
+            periodicity: main.mode_decision.periodicity,
+            f0,
+            quality,
+            cycles_count: main.cycle_boundaries.len(),
+            cycle_boundaries: main.cycle_boundaries.clone(),
+            section_lengths: [main.core_wave.len(), main.loop_wave.len(), main.release_wave.len()],
+            preset: None,
+        }
+    }
+
+    fn print_text(&self) {
+        println!("{}", self.file);
+        println!("  sample rate : {} Hz ({} x{})", self.sample_rate, self.channel_mode, self.channels);
+        println!("  mode        : {} ({}, periodicity = {:.3}, profile = {})",
+            self.mode, self.mode_reason, self.periodicity, self.profile);
+        println!("  F0          : target {:.2} Hz, min {:.2} / mean {:.2} / max {:.2} Hz, voiced {:.1}%",
+            self.f0.target_f0, self.f0.min, self.f0.mean, self.f0.max, self.f0.voiced_ratio * 100.0);
+        println!("  quality     : score {:.3} ({}), corr {:.3}, cycle corr {:.3}, spectral diff {:.4}, SNR {:.1} dB, NaN {:.3}",
+            self.quality.score, if self.quality.passed { "passed" } else { "FAILED" },
+            self.quality.correlation, self.quality.cycle_correlation, self.quality.spectral_energy_diff,
+            self.quality.snr_db, self.quality.nan_ratio);
+        println!("  sections    : core {} / loop {} / release {} samples",
+            self.section_lengths[0], self.section_lengths[1], self.section_lengths[2]);
+        let preview: Vec<String> = self.cycle_boundaries.iter().take(8)
+            .map(|(start, length)| format!("{:.1}+{:.1}", start, length))
+            .collect();
+        println!("  cycles      : {} [{}{}]", self.cycles_count, preview.join(", "),
+            if self.cycles_count > preview.len() { ", ..." } else { "" });
+        if let Some(preset) = &self.preset {
+            println!("  preset      : {}", preset);
+        }
+    }
+}
+
+/// 解析の段階が変わるたびに stderr へ出す進捗
+fn stage_progress(input: &Path) -> AnalysisProgress {
+    let name = input.display().to_string();
+    let last_stage = Mutex::new(None::<AnalysisStage>);
+    AnalysisProgress::new(
+        Some(Arc::new(move |stage, value| {
+            let Ok(mut last_stage) = last_stage.lock() else { return };
+            if *last_stage != Some(stage) {
+                *last_stage = Some(stage);
+                eprintln!("{}: {:?} ({:.0}%)", name, stage, value * 100.0);
+            }
+        })),
+        CancelToken::new(),
+    )
+}
+
+/// 1ファイルを解析し、必要ならプリセットを書き出す
+fn analyze_one(input: &Path, preset_path: Option<&Path>, params: &AnalysisParams) -> Result<AnalyzeReport, AnalyzeError> {
+    let result = analyzer::analyze_file_with_progress(input, params, &stage_progress(input))?;
+    let mut report = AnalyzeReport::new(input, &result);
+    if let Some(preset_path) = preset_path {
+        let mut preset = Preset::from_multichannel(&result, ParamBundle::default())
+            .ok_or_else(|| AnalyzeError::AnalysisFailed("Analysis produced no channels.".to_string()))?;
+        preset.original_file_name = input.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        io::save_preset(preset_path, &preset)?;
+        report.preset = Some(preset_path.display().to_string());
+    }
+    Ok(report)
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| format!("Failed to encode JSON: {}", e))?;
+    println!("{}", json);
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<bool, Box<dyn std::error::Error>> {
+    match cli.command {
+        Command::Analyze { input, preset, options } => {
+            let report = analyze_one(&input, preset.as_deref(), &options.to_params())?;
+            if cli.json { print_json(&report)?; } else { report.print_text(); }
+            Ok(true)
+        }
+        Command::Render { preset, midi, output, sample_rate, tail } => {
+            let preset = io::load_preset(&preset)?;
+            let settings = RenderSettings { sample_rate, tail_seconds: tail };
+            let rendered = render::render_midi_file(&midi, &preset, &output, &settings)?;
+            let frames = rendered.channels.first().map_or(0, |channel| channel.len());
+            let peak = rendered.channels.iter().flatten().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            if cli.json {
+                print_json(&serde_json::json!({
+                    "output": output.display().to_string(),
+                    "sample_rate": rendered.sample_rate,
+                    "channels": rendered.channels.len(),
+                    "seconds": frames as f64 / rendered.sample_rate as f64,
+                    "peak": peak,
+                }))?;
+            } else {
+                println!("{}: {:.2} s, {} ch, {} Hz, peak {:.3}",
+                    output.display(), frames as f64 / rendered.sample_rate as f64,
+                    rendered.channels.len(), rendered.sample_rate, peak);
+            }
+            Ok(true)
+        }
+        Command::ExportWavetable { input, output, options } => {
+            let result = analyzer::analyze_file_with_progress(&input, &options.to_params(), &stage_progress(&input))?;
+            let main = &result.channels[0];
+            io::export_wavetable(&output, &main.tables, main.sample_rate)?;
+            if cli.json {
+                print_json(&serde_json::json!({
+                    "output": output.display().to_string(),
+                    "frames": main.tables.len().min(io::MAX_WAVETABLE_FRAMES),
+                    "frame_size": io::WAVETABLE_FRAME_SIZE,
+                }))?;
+            } else {
+                println!("{}: {} frame(s) x {} samples", output.display(),
+                    main.tables.len().min(io::MAX_WAVETABLE_FRAMES), io::WAVETABLE_FRAME_SIZE);
+            }
+            Ok(true)
+        }
+        Command::Report { input, format, output, regions, full, bins, samples, options } => {
+            let format = match format {
+                _ if cli.json => ReportFormat::Json,
+                FormatArg::Text => ReportFormat::Text,
+                FormatArg::Csv => ReportFormat::Csv,
+                FormatArg::Json => ReportFormat::Json,
+            };
+            let report_options = ReportOptions {
+                max_bins: bins,
+                max_samples: samples,
+                ..if full { ReportOptions::full() } else { ReportOptions::default() }
+            };
+            let params = options.to_params();
+            let regions = if regions.is_empty() { None } else { Some(&regions[..]) };
+            let report = diagnostics::report_file(&input, full.then_some(&params), regions, &report_options)?;
+            let rendered = report.render(format);
+            match output {
+                Some(output) => std::fs::write(&output, rendered)
+                    .map_err(|e| format!("Failed to write {:?}: {}", output, e))?,
+                None => print!("{}", rendered),
+            }
+            Ok(true)
+        }
+        Command::Batch { dir, out_dir, options } => {
+            let mut inputs: Vec<PathBuf> = std::fs::read_dir(&dir)
+                .map_err(|e| format!("Failed to read directory {:?}: {}", dir, e))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str())))
+                .collect();
+            inputs.sort();
+            if let Some(out_dir) = &out_dir {
+                std::fs::create_dir_all(out_dir).map_err(|e| format!("Failed to create {:?}: {}", out_dir, e))?;
+            }
+
+            let params = options.to_params();
+            let mut all_ok = true;
+            let mut entries = Vec::new();
+            for input in &inputs {
+                let preset_path = out_dir.as_ref().map(|out_dir| {
+                    out_dir.join(input.file_stem().unwrap_or_default()).with_extension("zip")
+                });
+                match analyze_one(input, preset_path.as_deref(), &params) {
+                    Ok(report) => {
+                        if !cli.json { report.print_text(); }
+                        entries.push(serde_json::json!({ "file": report.file.clone(), "ok": true, "report": report }));
+                    }
+                    Err(e) => {
+                        all_ok = false;
+                        if !cli.json { println!("{}\n  error       : {}", input.display(), e); }
+                        entries.push(serde_json::json!({ "file": input.display().to_string(), "ok": false, "error": e.to_string() }));
+                    }
+                }
+            }
+            if cli.json {
+                print_json(&entries)?;
+            } else {
+                let failed = entries.iter().filter(|entry| entry["ok"] == false).count();
+                println!("{} file(s) analyzed, {} failed.", entries.len(), failed);
+            }
+            Ok(all_ok)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,430 @@
+// src/diagnostics/mod.rs
+
+//! 解析の中間結果をテキスト / CSV / JSON のレポートとして書き出す
+//! - テキストは log/spectral_analysis_log.txt と同じ書式 (追加の項目は末尾に続ける)
+//! - 数値の桁を固定しているため、解析結果の差分 (回帰) を diff で確認できる
+
+use crate::analyzer::{self, audio_file, AnalysisParams, AnalysisResult, AnalyzeError, ModeCandidate};
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::ops::Range;
+use std::path::Path;
+
+const SEPARATOR: &str = "----------------------------------------";
+
+/// レポートの出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+/// レポートに含める項目と件数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReportOptions {
+    pub max_bins: usize,      // 区間ごとに出すスペクトルのビン数
+    pub max_samples: usize,   // 区間の波形・モードごとのテーブルで出すサンプル数
+    pub max_harmonics: usize, // 区間ごとに出す倍音ピークの数 (0 = 出さない)
+    pub include_f0: bool,     // F0カーブを出すか
+    pub include_mode_tables: bool, // モードごとのテーブルを出すか
+}
+
+impl Default for ReportOptions {
+    /// log/spectral_analysis_log.txt と同じ内容 (各区間の先頭20ビン・20サンプルのみ)
+    fn default() -> Self {
+        ReportOptions {
+            max_bins: 20,
+            max_samples: 20,
+            max_harmonics: 0,
+            include_f0: false,
+            include_mode_tables: false,
+        }
+    }
+}
+
+impl ReportOptions {
+    /// 全項目を含める (倍音16本, F0カーブ, モードごとのテーブル)
+    pub fn full() -> Self {
+        ReportOptions { max_harmonics: 16, include_f0: true, include_mode_tables: true, ..ReportOptions::default() }
+    }
+}
+
+/// スペクトルの1ビン
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SpectrumBin {
+    pub frequency: f32, // [Hz]
+    pub amplitude: f32, // 片側振幅 (正弦波の振幅 A なら A)
+    pub phase: f32,     // [rad]
+}
+
+/// 倍音ピーク
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HarmonicPeak {
+    pub number: usize,
+    pub frequency: f32,
+    pub amplitude: f32,
+    pub phase: f32,
+    pub level_db: f32, // 基音に対するレベル [dB]
+}
+
+/// 解析区間1つ分のレポート
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegionReport {
+    pub name: String,
+    pub start: usize,
+    pub len: usize,
+    pub spectrum: Vec<SpectrumBin>,
+    pub wavetable: Vec<f32>, // 区間の先頭の波形
+    pub harmonics: Vec<HarmonicPeak>,
+}
+
+/// F0カーブの1フレーム
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct F0Frame {
+    pub time: f32, // [s]
+    pub f0: f32,   // [Hz] (0 = 無声)
+    pub confidence: f32,
+    pub voiced: bool,
+}
+
+/// 1モード分のテーブル
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModeTableReport {
+    pub mode: String,
+    pub score: f32,
+    pub table_len: usize,
+    pub tables: Vec<Vec<f32>>, // 各テーブルの先頭 max_samples サンプル
+}
+
+/// 診断レポート全体
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DiagnosticsReport {
+    pub source: String,
+    pub sample_rate: u32,
+    pub total_samples: usize,
+    pub regions: Vec<RegionReport>,
+    pub f0_curve: Vec<F0Frame>,
+    pub mode_tables: Vec<ModeTableReport>,
+}
+
+/// 音声を3等分した Attack / Core / Sustain 区間 (log/spectral_analysis_log.txt と同じ区切り)
+pub fn default_regions(total_samples: usize) -> Vec<(String, Range<usize>)> {
+    let third = total_samples / 3;
+    vec![
+        ("Attack".to_string(), 0..third),
+        ("Core".to_string(), third..third * 2),
+        ("Sustain".to_string(), third * 2..third * 3),
+    ]
+}
+
+/// 区間全体を1フレームとして FFT し、片側スペクトルを返す (窓なし, 分解能 = sample_rate / 区間長)
+pub fn region_spectrum(segment: &[f32], sample_rate: u32, planner: &mut FftPlanner<f32>) -> Vec<SpectrumBin> {
+    let len = segment.len();
+    if len == 0 {
+        return vec![];
+    }
+    let mut buffer: Vec<Complex<f32>> = segment.iter().map(|&s| Complex::new(s, 0.0)).collect();
+    planner.plan_fft_forward(len).process(&mut buffer);
+    let bin_hz = sample_rate as f32 / len as f32;
+    buffer.iter().take(len / 2 + 1).enumerate()
+        .map(|(k, value)| {
+            // DC とナイキストは片側にしても2倍しない
+            let scale = if k == 0 || 2 * k == len { 1.0 } else { 2.0 };
+            SpectrumBin { frequency: k as f32 * bin_hz, amplitude: value.norm() * scale / len as f32, phase: value.arg() }
+        })
+        .collect()
+}
+
+/// スペクトルから f0 の倍音ピークを探す (各倍音の ±f0/2 の範囲で最大のビン)
+pub fn harmonic_peaks(spectrum: &[SpectrumBin], f0: f32, max_harmonics: usize) -> Vec<HarmonicPeak> {
+    if spectrum.len() < 2 || f0 <= 0.0 {
+        return vec![];
+    }
+    let bin_hz = spectrum[1].frequency;
+    let mut peaks: Vec<HarmonicPeak> = Vec::new();
+    for number in 1..=max_harmonics {
+        let center = number as f32 * f0 / bin_hz;
+        let half_width = (0.5 * f0 / bin_hz).max(1.0);
+        let lo = (center - half_width).floor().max(1.0) as usize;
+        let hi = ((center + half_width).ceil() as usize).min(spectrum.len() - 1);
+        if lo > hi {
+            break;
+        }
+        let Some(peak) = spectrum[lo..=hi].iter().max_by(|a, b| a.amplitude.total_cmp(&b.amplitude)) else { break };
+        let fundamental = peaks.first().map_or(peak.amplitude, |first| first.amplitude);
+        peaks.push(HarmonicPeak {
+            number,
+            frequency: peak.frequency,
+            amplitude: peak.amplitude,
+            phase: peak.phase,
+            level_db: 20.0 * (peak.amplitude.max(1e-12) / fundamental.max(1e-12)).log10(),
+        });
+    }
+    peaks
+}
+
+/// 区間内の有声フレームの平均F0 (フレームは pitch_contour_rate [frames/s] で並ぶ)
+fn region_f0(analysis: &AnalysisResult, range: &Range<usize>) -> f32 {
+    let frames_per_sample = analysis.pitch_contour_rate / analysis.sample_rate.max(1) as f32;
+    let first = (range.start as f32 * frames_per_sample) as usize;
+    let last = ((range.end as f32 * frames_per_sample).ceil() as usize).min(analysis.f0_curve.len());
+    let voiced: Vec<f32> = analysis.f0_curve.get(first..last).unwrap_or(&[])
+        .iter().copied().filter(|&f| f > 0.0).collect();
+    if voiced.is_empty() { analysis.target_f0 } else { voiced.iter().sum::<f32>() / voiced.len() as f32 }
+}
+
+fn mode_table_report(candidate: &ModeCandidate, max_samples: usize) -> ModeTableReport {
+    ModeTableReport {
+        mode: format!("{:?}", candidate.mode),
+        score: candidate.quality.score(),
+        table_len: candidate.tables.first().map_or(0, |table| table.len()),
+        tables: candidate.tables.iter().map(|table| table.iter().take(max_samples).copied().collect()).collect(),
+    }
+}
+
+/// 音声の各区間のスペクトル・波形と、解析結果 (あれば) の倍音・F0カーブ・モードごとのテーブルをまとめる
+/// - 倍音ピークは区間内の平均F0から探すため、analysis がない場合は出さない
+pub fn build_report(
+    source: &str,
+    audio: &[f32],
+    sample_rate: u32,
+    regions: &[(String, Range<usize>)],
+    analysis: Option<&AnalysisResult>,
+    options: &ReportOptions,
+) -> DiagnosticsReport {
+    let mut planner = FftPlanner::new();
+    let region_reports = regions.iter()
+        .map(|(name, range)| {
+            let range = range.start.min(audio.len())..range.end.min(audio.len());
+            let segment = &audio[range.clone()];
+            let spectrum = region_spectrum(segment, sample_rate, &mut planner);
+            let harmonics = match analysis {
+                Some(analysis) if options.max_harmonics > 0 => {
+                    harmonic_peaks(&spectrum, region_f0(analysis, &range), options.max_harmonics)
+                }
+                _ => vec![],
+            };
+            RegionReport {
+                name: name.clone(),
+                start: range.start,
+                len: segment.len(),
+                spectrum: spectrum.into_iter().take(options.max_bins).collect(),
+                wavetable: segment.iter().take(options.max_samples).copied().collect(),
+                harmonics,
+            }
+        })
+        .collect();
+
+    let f0_curve = match analysis {
+        Some(analysis) if options.include_f0 => analysis.f0_curve.iter().enumerate()
+            .map(|(i, &f0)| F0Frame {
+                time: i as f32 / analysis.pitch_contour_rate.max(f32::EPSILON),
+                f0,
+                confidence: analysis.confidence.get(i).copied().unwrap_or(0.0),
+                voiced: analysis.voicing.get(i).copied().unwrap_or(f0 > 0.0),
+            })
+            .collect(),
+        _ => vec![],
+    };
+    let mode_tables = match analysis {
+        Some(analysis) if options.include_mode_tables => analysis.mode_candidates.iter()
+            .map(|candidate| mode_table_report(candidate, options.max_samples))
+            .collect(),
+        _ => vec![],
+    };
+
+    DiagnosticsReport {
+        source: source.to_string(),
+        sample_rate,
+        total_samples: audio.len(),
+        regions: region_reports,
+        f0_curve,
+        mode_tables,
+    }
+}
+
+/// 音声ファイルのレポートを作る
+/// - 複数チャンネルはダウンミックス (Mid) して扱う (解析の MidOnly と同じ信号)
+/// - params があれば同じ信号を解析し、倍音・F0カーブ・モードごとのテーブルに使う
+/// - regions が None なら default_regions (3等分)
+pub fn report_file<P: AsRef<Path>>(
+    path: P,
+    params: Option<&AnalysisParams>,
+    regions: Option<&[(String, Range<usize>)]>,
+    options: &ReportOptions,
+) -> Result<DiagnosticsReport, AnalyzeError> {
+    let mut decoded = audio_file::decode_file(path.as_ref())?;
+    if let Some(rate) = params.and_then(|params| params.analysis_sample_rate) {
+        if rate != decoded.sample_rate {
+            decoded = decoded.resample(rate);
+        }
+    }
+    let audio = decoded.downmix();
+    let analysis = match params {
+        Some(params) => Some(analyzer::analyze_audio(&audio, decoded.sample_rate, params)?),
+        None => None,
+    };
+    let regions = regions.map_or_else(|| default_regions(audio.len()), |regions| regions.to_vec());
+    let source = path.as_ref().display().to_string();
+    Ok(build_report(&source, &audio, decoded.sample_rate, &regions, analysis.as_ref(), options))
+}
+
+impl DiagnosticsReport {
+    /// 指定の形式で書き出す
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.to_text(),
+            ReportFormat::Csv => self.to_csv(),
+            ReportFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default() + "\n",
+        }
+    }
+
+    /// log/spectral_analysis_log.txt と同じ書式のテキスト
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "--- Spectral Analysis Log for {} ---", self.source);
+        let _ = writeln!(out, "Sample Rate: {}", self.sample_rate);
+        let _ = writeln!(out, "Total Samples: {}", self.total_samples);
+        let _ = writeln!(out, "{}", SEPARATOR);
+        for region in &self.regions {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "### {} Region ###", region.name);
+            let _ = writeln!(out);
+            let _ = writeln!(out, "--- Spectrum Data (First {} bins) ---", region.spectrum.len());
+            let _ = writeln!(out, "  (Freq[Hz], Amplitude, Phase)");
+            for bin in &region.spectrum {
+                let _ = writeln!(out, "  ({:8.2}, {:8.4}, {:8.4})", bin.frequency, bin.amplitude, bin.phase);
+            }
+            let _ = writeln!(out);
+            let _ = writeln!(out, "--- Wavetable (First {} samples) ---", region.wavetable.len());
+            for sample in &region.wavetable {
+                let _ = writeln!(out, "{:12.6}", sample);
+            }
+            if !region.harmonics.is_empty() {
+                let _ = writeln!(out);
+                let _ = writeln!(out, "--- Harmonic Peaks ---");
+                let _ = writeln!(out, "  (No., Freq[Hz], Amplitude, Phase, Level[dB])");
+                for peak in &region.harmonics {
+                    let _ = writeln!(out, "  ({:3}, {:8.2}, {:8.4}, {:8.4}, {:7.2})",
+                        peak.number, peak.frequency, peak.amplitude, peak.phase, peak.level_db);
+                }
+            }
+            let _ = writeln!(out);
+            let _ = writeln!(out, "{}", SEPARATOR);
+        }
+        if !self.f0_curve.is_empty() {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "### F0 Curve ###");
+            let _ = writeln!(out, "  (Time[s], F0[Hz], Confidence, Voiced)");
+            for frame in &self.f0_curve {
+                let _ = writeln!(out, "  ({:8.4}, {:8.2}, {:6.3}, {})", frame.time, frame.f0, frame.confidence, frame.voiced as u8);
+            }
+            let _ = writeln!(out);
+            let _ = writeln!(out, "{}", SEPARATOR);
+        }
+        for table in &self.mode_tables {
+            let _ = writeln!(out);
+            let _ = writeln!(out, "### {} Mode Tables (score = {:.4}, {} table(s) x {} samples) ###",
+                table.mode, table.score, table.tables.len(), table.table_len);
+            for (i, samples) in table.tables.iter().enumerate() {
+                let _ = writeln!(out);
+                let _ = writeln!(out, "--- Table {} (First {} samples) ---", i, samples.len());
+                for sample in samples {
+                    let _ = writeln!(out, "{:12.6}", sample);
+                }
+            }
+            let _ = writeln!(out);
+            let _ = writeln!(out, "{}", SEPARATOR);
+        }
+        out
+    }
+
+    /// 1行1値の CSV (kind 列で種類を区別し、使わない列は空にする)
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("kind,region,index,time_s,frequency_hz,amplitude,phase,value\n");
+        for region in &self.regions {
+            for (i, bin) in region.spectrum.iter().enumerate() {
+                let _ = writeln!(out, "spectrum,{},{},,{:.4},{:.6},{:.6},", region.name, i, bin.frequency, bin.amplitude, bin.phase);
+            }
+            for (i, sample) in region.wavetable.iter().enumerate() {
+                let _ = writeln!(out, "wavetable,{},{},,,,,{:.6}", region.name, i, sample);
+            }
+            for peak in &region.harmonics {
+                let _ = writeln!(out, "harmonic,{},{},,{:.4},{:.6},{:.6},{:.3}",
+                    region.name, peak.number, peak.frequency, peak.amplitude, peak.phase, peak.level_db);
+            }
+        }
+        for (i, frame) in self.f0_curve.iter().enumerate() {
+            let _ = writeln!(out, "f0,,{},{:.6},{:.4},{:.6},,{}", i, frame.time, frame.f0, frame.confidence, frame.voiced as u8);
+        }
+        for table in &self.mode_tables {
+            for (t, samples) in table.tables.iter().enumerate() {
+                for (i, sample) in samples.iter().enumerate() {
+                    let _ = writeln!(out, "mode_table,{}:{},{},,,,,{:.6}", table.mode, t, i, sample);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_spectrum_and_harmonics_of_sawtooth_like_signal() {
+        // 1. Arrange: 100Hz (振幅 0.5) + 200Hz (振幅 0.25), 1秒 (分解能 1Hz)
+        const SAMPLE_RATE: u32 = 8000;
+        let signal: Vec<f32> = (0..SAMPLE_RATE)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                0.5 * (std::f32::consts::TAU * 100.0 * t).sin() + 0.25 * (std::f32::consts::TAU * 200.0 * t).sin()
+            })
+            .collect();
+        let mut planner = FftPlanner::new();
+
+        // 2. Act
+        let spectrum = region_spectrum(&signal, SAMPLE_RATE, &mut planner);
+        let peaks = harmonic_peaks(&spectrum, 100.0, 3);
+
+        // 3. Assert
+        assert!((spectrum[100].amplitude - 0.5).abs() < 1e-3);
+        assert!((spectrum[200].amplitude - 0.25).abs() < 1e-3);
+        assert_eq!(peaks.len(), 3);
+        assert_eq!(peaks[1].frequency, 200.0);
+        assert!((peaks[1].level_db + 6.02).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_text_report_matches_log_layout() {
+        // 1. Arrange
+        let audio: Vec<f32> = (0..300).map(|i| (i as f32 * 0.1).sin() * 0.5).collect();
+        let regions = default_regions(audio.len());
+
+        // 2. Act
+        let report = build_report("test.wav", &audio, 22050, &regions, None, &ReportOptions::default());
+        let text = report.to_text();
+        let lines: Vec<&str> = text.lines().collect();
+
+        // 3. Assert: 見出しと数値の桁が log/spectral_analysis_log.txt と同じ
+        assert_eq!(&lines[0..6], &[
+            "--- Spectral Analysis Log for test.wav ---",
+            "Sample Rate: 22050",
+            "Total Samples: 300",
+            SEPARATOR,
+            "",
+            "### Attack Region ###",
+        ]);
+        assert_eq!(lines[7], "--- Spectrum Data (First 20 bins) ---");
+        assert_eq!(lines[8], "  (Freq[Hz], Amplitude, Phase)");
+        assert!(lines[9].starts_with("  (    0.00, "));
+        assert_eq!(lines[30], "--- Wavetable (First 20 samples) ---");
+        assert_eq!(lines[31], "    0.000000");
+        assert_eq!(report.regions[2].name, "Sustain");
+        assert_eq!(report.to_csv().lines().filter(|line| line.starts_with("spectrum,")).count(), 60);
+    }
+}
@@ -0,0 +1,13 @@
+// src/io/mod.rs
+
+// 各モジュールを公開
+pub mod preset;
+mod riff;
+pub mod sampler;
+pub mod state;
+pub mod wavetable;
+
+pub use self::preset::{load_preset, save_preset, Preset, PresetSections, PRESET_FORMAT_VERSION};
+pub use self::state::{decode_state, encode_state, SessionState, STATE_FORMAT_VERSION};
+pub use self::wavetable::{export_wavetable, import_wavetable, Wavetable, MAX_WAVETABLE_FRAMES, WAVETABLE_FRAME_SIZE};
+pub use self::sampler::export_sampler;
@@ -0,0 +1,422 @@
+// src/io/preset.rs
+
+use crate::analyzer::{AnalysisResult, AnalyzeError, ChannelMode, MultichannelAnalysisResult};
+use crate::oscillator::StereoLayout;
+use crate::ParamBundle;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// プリセットのフォーマットバージョン
+/// - フィールドを追加しただけなら据え置き (古いファイルの欠けたフィールドはデフォルト値で読む)
+/// - 既存フィールドの意味やバイナリの並びを変えたら上げ、migrate_metadata に変換を追加する
+pub const PRESET_FORMAT_VERSION: u32 = 1;
+
+const METADATA_FILE: &str = "metadata.json";
+const CYCLES_FILE: &str = "cycles.bin";           // 周期境界 (開始位置, 周期長) の組
+const FREQ_FILE: &str = "freq.bin";               // F0カーブ, ピッチ変化
+const SYNTH_MODES_FILE: &str = "synth_modes.bin"; // チャンネルごとの Core/Loop/Release 波形とゲインカーブ
+
+/// 1チャンネル分の Core/Loop/Release 波形とゲインカーブ
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PresetSections {
+    pub core_wave: Vec<f32>,
+    pub loop_wave: Vec<f32>,
+    pub release_wave: Vec<f32>,
+    pub core_gain: Vec<f32>,
+    pub loop_gain: Vec<f32>,
+    pub release_gain: Vec<f32>,
+}
+
+/// 保存/読み込みの単位 (解析結果 + 再生パラメータ)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Preset {
+    pub sample_rate: u32,                   // Core/Release のサンプルレート
+    pub f0_curve: Vec<f32>,
+    pub pitch_contour: Vec<f32>,            // 平均からのずれ [cent]
+    pub pitch_contour_rate: f32,            // pitch_contour のフレームレート [frames/s]
+    pub cycle_boundaries: Vec<(f32, f32)>,  // 原音の周期境界 (開始位置, 周期長)
+    pub sections: PresetSections,
+    pub second_channel: Option<(StereoLayout, PresetSections)>, // ステレオの2チャンネル目
+    pub params: ParamBundle,
+    pub original_file_name: String,
+    pub created_at: u64,                    // 作成日時 (UNIX秒)
+}
+
+impl PresetSections {
+    fn from_analysis(result: &AnalysisResult) -> Self {
+        PresetSections {
+            core_wave: result.core_wave.clone(),
+            loop_wave: result.loop_wave.clone(),
+            release_wave: result.release_wave.clone(),
+            core_gain: result.core_gain.clone(),
+            loop_gain: result.loop_gain.clone(),
+            release_gain: result.release_gain.clone(),
+        }
+    }
+}
+
+impl Preset {
+    /// 解析結果とパラメータからプリセットを作る
+    pub fn from_analysis(result: &AnalysisResult, params: ParamBundle) -> Self {
+        Preset {
+            sample_rate: result.sample_rate,
+            f0_curve: result.f0_curve.clone(),
+            pitch_contour: result.pitch_contour.clone(),
+            pitch_contour_rate: result.pitch_contour_rate,
+            cycle_boundaries: result.cycle_boundaries.clone(),
+            sections: PresetSections::from_analysis(result),
+            second_channel: None,
+            params,
+            original_file_name: String::new(),
+            created_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        }
+    }
+
+    /// 複数チャンネルの解析結果からプリセットを作る
+    /// - PerChannel は先頭2チャンネルを L/R、MidSide は Mid/Side として保持する (FFIと同じ扱い)
+    /// - 解析結果が空の場合は None
+    pub fn from_multichannel(result: &MultichannelAnalysisResult, params: ParamBundle) -> Option<Self> {
+        let mut preset = Preset::from_analysis(result.channels.first()?, params);
+        let layout = match (result.channel_mode, result.channels.len()) {
+            (ChannelMode::PerChannel, n) if n >= 2 => Some(StereoLayout::LeftRight),
+            (ChannelMode::MidSide, 2) => Some(StereoLayout::MidSide),
+            _ => None,
+        };
+        preset.second_channel = layout.map(|layout| (layout, PresetSections::from_analysis(&result.channels[1])));
+        Some(preset)
+    }
+}
+
+/// バイナリ内の各配列の長さ (synth_modes.bin 1チャンネル分)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SectionLengths {
+    core_wave: usize,
+    loop_wave: usize,
+    release_wave: usize,
+    core_gain: usize,
+    loop_gain: usize,
+    release_gain: usize,
+}
+
+/// metadata.json の内容
+/// - 全フィールドにデフォルト値があるため、後から追加したフィールドがない古いファイルも読める
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct PresetMetadata {
+    format_version: u32,
+    sample_rate: u32,
+    cycles_count: usize,
+    samples_per_cycle: f32,
+    synth_modes_count: usize,
+    created_at: u64,
+    original_file_name: String,
+    f0_len: usize,
+    pitch_contour_len: usize,
+    pitch_contour_rate: f32,
+    channel_layout: String, // "mono" / "left_right" / "mid_side"
+    synth_modes: Vec<SectionLengths>,
+    params: ParamBundle,
+}
+
+impl SectionLengths {
+    fn of(sections: &PresetSections) -> Self {
+        SectionLengths {
+            core_wave: sections.core_wave.len(),
+            loop_wave: sections.loop_wave.len(),
+            release_wave: sections.release_wave.len(),
+            core_gain: sections.core_gain.len(),
+            loop_gain: sections.loop_gain.len(),
+            release_gain: sections.release_gain.len(),
+        }
+    }
+}
+
+/// 古いバージョンのメタデータを現在の形式に変換する
+fn migrate_metadata(mut metadata: PresetMetadata) -> Result<PresetMetadata, AnalyzeError> {
+    match metadata.format_version {
+        // 0 = バージョン番号のないファイル (初版と同じ並びとして扱う)
+        0 | 1 => metadata.format_version = PRESET_FORMAT_VERSION,
+        version => return Err(AnalyzeError::Preset(format!(
+            "Preset format version {} is newer than supported version {}.", version, PRESET_FORMAT_VERSION
+        ))),
+    }
+    Ok(metadata)
+}
+
+/// f32 をリトルエンディアンで並べる
+fn write_f32s(out: &mut Vec<u8>, values: &[f32]) {
+    for value in values {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// リトルエンディアンの f32 列を先頭から順に読み出す
+struct BlobReader<'a> {
+    name: &'static str,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BlobReader<'a> {
+    fn new(name: &'static str, data: &'a [u8]) -> Self {
+        BlobReader { name, data, pos: 0 }
+    }
+
+    /// len 個の f32 を読む (len は metadata.json の値なので、桁あふれも短すぎる場合と同じエラーにする)
+    fn take(&mut self, len: usize) -> Result<Vec<f32>, AnalyzeError> {
+        let bytes = len.checked_mul(4)
+            .and_then(|byte_len| self.pos.checked_add(byte_len))
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| AnalyzeError::Preset(format!("{} is shorter than described in {}.", self.name, METADATA_FILE)))?;
+        let end = self.pos + bytes.len();
+        self.pos = end;
+        Ok(bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+    }
+}
+
+fn layout_name(layout: Option<StereoLayout>) -> &'static str {
+    match layout {
+        None => "mono",
+        Some(StereoLayout::LeftRight) => "left_right",
+        Some(StereoLayout::MidSide) => "mid_side",
+    }
+}
+
+/// プリセットを ZIP (metadata.json + cycles.bin + freq.bin + synth_modes.bin) に保存する
+pub fn save_preset<P: AsRef<Path>>(path: P, preset: &Preset) -> Result<(), AnalyzeError> {
+    let channels: Vec<&PresetSections> = std::iter::once(&preset.sections)
+        .chain(preset.second_channel.as_ref().map(|(_, sections)| sections))
+        .collect();
+
+    let samples_per_cycle = if preset.cycle_boundaries.is_empty() {
+        0.0
+    } else {
+        preset.cycle_boundaries.iter().map(|(_, length)| length).sum::<f32>()
+            / preset.cycle_boundaries.len() as f32
+    };
+    let metadata = PresetMetadata {
+        format_version: PRESET_FORMAT_VERSION,
+        sample_rate: preset.sample_rate,
+        cycles_count: preset.cycle_boundaries.len(),
+        samples_per_cycle,
+        synth_modes_count: channels.len(),
+        created_at: preset.created_at,
+        original_file_name: preset.original_file_name.clone(),
+        f0_len: preset.f0_curve.len(),
+        pitch_contour_len: preset.pitch_contour.len(),
+        pitch_contour_rate: preset.pitch_contour_rate,
+        channel_layout: layout_name(preset.second_channel.as_ref().map(|(layout, _)| *layout)).to_string(),
+        synth_modes: channels.iter().map(|sections| SectionLengths::of(sections)).collect(),
+        params: preset.params,
+    };
+    let metadata_json = serde_json::to_vec_pretty(&metadata)
+        .map_err(|e| AnalyzeError::Preset(format!("Failed to encode {}: {}", METADATA_FILE, e)))?;
+
+    let mut cycles = Vec::new();
+    for (start, length) in &preset.cycle_boundaries {
+        write_f32s(&mut cycles, &[*start, *length]);
+    }
+    let mut freq = Vec::new();
+    write_f32s(&mut freq, &preset.f0_curve);
+    write_f32s(&mut freq, &preset.pitch_contour);
+    let mut synth_modes = Vec::new();
+    for sections in &channels {
+        write_f32s(&mut synth_modes, &sections.core_wave);
+        write_f32s(&mut synth_modes, &sections.loop_wave);
+        write_f32s(&mut synth_modes, &sections.release_wave);
+        write_f32s(&mut synth_modes, &sections.core_gain);
+        write_f32s(&mut synth_modes, &sections.loop_gain);
+        write_f32s(&mut synth_modes, &sections.release_gain);
+    }
+
+    let file = File::create(path.as_ref())
+        .map_err(|e| AnalyzeError::Io(format!("Failed to create preset {:?}: {}", path.as_ref(), e)))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, data) in [
+        (METADATA_FILE, &metadata_json),
+        (CYCLES_FILE, &cycles),
+        (FREQ_FILE, &freq),
+        (SYNTH_MODES_FILE, &synth_modes),
+    ] {
+        zip.start_file(name, options).map_err(|e| AnalyzeError::Io(format!("Failed to write {}: {}", name, e)))?;
+        zip.write_all(data).map_err(|e| AnalyzeError::Io(format!("Failed to write {}: {}", name, e)))?;
+    }
+    zip.finish().map_err(|e| AnalyzeError::Io(format!("Failed to finish preset: {}", e)))?;
+    Ok(())
+}
+
+/// ZIP 内のファイルを読み出す (存在しない場合は None)
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Option<Vec<u8>>, AnalyzeError> {
+    let mut entry = match archive.by_name(name) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(AnalyzeError::Preset(format!("Failed to open {}: {}", name, e))),
+    };
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data).map_err(|e| AnalyzeError::Preset(format!("Failed to read {}: {}", name, e)))?;
+    Ok(Some(data))
+}
+
+/// save_preset で保存したプリセットを読み込む
+/// - 古いバージョンのファイルは現在の形式に変換して読む
+/// - バイナリが存在しない場合は空として扱う (メタデータの長さが 0 であること)
+pub fn load_preset<P: AsRef<Path>>(path: P) -> Result<Preset, AnalyzeError> {
+    let file = File::open(path.as_ref())
+        .map_err(|e| AnalyzeError::Io(format!("Failed to open preset {:?}: {}", path.as_ref(), e)))?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| AnalyzeError::Preset(format!("Preset is not a valid ZIP archive: {}", e)))?;
+
+    let metadata_json = read_entry(&mut archive, METADATA_FILE)?
+        .ok_or_else(|| AnalyzeError::Preset(format!("Preset has no {}.", METADATA_FILE)))?;
+    let metadata: PresetMetadata = serde_json::from_slice(&metadata_json)
+        .map_err(|e| AnalyzeError::Preset(format!("Failed to parse {}: {}", METADATA_FILE, e)))?;
+    let metadata = migrate_metadata(metadata)?;
+
+    let cycles = read_entry(&mut archive, CYCLES_FILE)?.unwrap_or_default();
+    let freq = read_entry(&mut archive, FREQ_FILE)?.unwrap_or_default();
+    let synth_modes = read_entry(&mut archive, SYNTH_MODES_FILE)?.unwrap_or_default();
+
+    let boundary_len = metadata.cycles_count.checked_mul(2)
+        .ok_or_else(|| AnalyzeError::Preset(format!("Invalid cycles_count in {}: {}.", METADATA_FILE, metadata.cycles_count)))?;
+    let boundary_values = BlobReader::new(CYCLES_FILE, &cycles).take(boundary_len)?;
+    let cycle_boundaries = boundary_values.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect();
+
+    let mut freq_reader = BlobReader::new(FREQ_FILE, &freq);
+    let f0_curve = freq_reader.take(metadata.f0_len)?;
+    let pitch_contour = freq_reader.take(metadata.pitch_contour_len)?;
+
+    let mut synth_reader = BlobReader::new(SYNTH_MODES_FILE, &synth_modes);
+    let mut channels = Vec::with_capacity(metadata.synth_modes.len());
+    for lengths in &metadata.synth_modes {
+        channels.push(PresetSections {
+            core_wave: synth_reader.take(lengths.core_wave)?,
+            loop_wave: synth_reader.take(lengths.loop_wave)?,
+            release_wave: synth_reader.take(lengths.release_wave)?,
+            core_gain: synth_reader.take(lengths.core_gain)?,
+            loop_gain: synth_reader.take(lengths.loop_gain)?,
+            release_gain: synth_reader.take(lengths.release_gain)?,
+        });
+    }
+    let mut channels = channels.into_iter();
+    let sections = channels.next().unwrap_or_default();
+    let layout = match metadata.channel_layout.as_str() {
+        "left_right" => Some(StereoLayout::LeftRight),
+        "mid_side" => Some(StereoLayout::MidSide),
+        _ => None,
+    };
+    let second_channel = layout.zip(channels.next());
+
+    Ok(Preset {
+        sample_rate: metadata.sample_rate,
+        f0_curve,
+        pitch_contour,
+        pitch_contour_rate: metadata.pitch_contour_rate,
+        cycle_boundaries,
+        sections,
+        second_channel,
+        params: metadata.params,
+        original_file_name: metadata.original_file_name,
+        created_at: metadata.created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_preset() -> Preset {
+        let sections = |offset: f32| PresetSections {
+            core_wave: (0..32).map(|i| (i as f32 * 0.1 + offset).sin()).collect(),
+            loop_wave: (0..16).map(|i| (i as f32 * 0.2 + offset).sin()).collect(),
+            release_wave: (0..8).map(|i| (i as f32 * 0.3 + offset).sin()).collect(),
+            core_gain: vec![0.0, 0.5, 1.0],
+            loop_gain: vec![1.0; 4],
+            release_gain: vec![1.0, 0.5, 0.0],
+        };
+        Preset {
+            sample_rate: 44100,
+            f0_curve: vec![220.0, 221.0, 219.5],
+            pitch_contour: vec![0.0, 7.8, -3.9],
+            pitch_contour_rate: 86.13,
+            cycle_boundaries: vec![(0.0, 200.4), (200.4, 200.5)],
+            sections: sections(0.0),
+            second_channel: Some((StereoLayout::MidSide, sections(1.0))),
+            params: ParamBundle { vibrato_depth: 0.7, cutoff: 8000.0, ..ParamBundle::default() },
+            original_file_name: "piano.wav".to_string(),
+            created_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_preset_round_trip() {
+        // 1. Arrange
+        let path = std::env::temp_dir().join(format!("marumaru_preset_{}.zip", std::process::id()));
+        let preset = sample_preset();
+
+        // 2. Act
+        save_preset(&path, &preset).unwrap();
+        let loaded = load_preset(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // 3. Assert
+        assert_eq!(loaded, preset);
+    }
+
+    #[test]
+    fn test_old_metadata_without_new_fields_loads_with_defaults() {
+        // 1. Arrange: バージョン番号も params もない、セクションだけのメタデータ
+        let path = std::env::temp_dir().join(format!("marumaru_preset_old_{}.zip", std::process::id()));
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file(METADATA_FILE, SimpleFileOptions::default()).unwrap();
+        zip.write_all(br#"{"sample_rate": 22050, "synth_modes": [{"loop_wave": 2}]}"#).unwrap();
+        zip.start_file(SYNTH_MODES_FILE, SimpleFileOptions::default()).unwrap();
+        let mut synth_modes = Vec::new();
+        write_f32s(&mut synth_modes, &[0.25, -0.25]);
+        zip.write_all(&synth_modes).unwrap();
+        zip.finish().unwrap();
+
+        // 2. Act
+        let loaded = load_preset(&path);
+        let _ = std::fs::remove_file(&path);
+
+        // 3. Assert
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.sample_rate, 22050);
+        assert_eq!(loaded.sections.loop_wave, vec![0.25, -0.25]);
+        assert!(loaded.f0_curve.is_empty());
+        assert!(loaded.second_channel.is_none());
+        assert_eq!(loaded.params, ParamBundle::default());
+    }
+
+    #[test]
+    fn test_overflowing_lengths_in_metadata_are_rejected() {
+        // 1. Arrange: 4倍・2倍すると usize をあふれる長さを書いたメタデータ
+        let metadata = [
+            format!(r#"{{"cycles_count": {}}}"#, usize::MAX / 2 + 1),
+            format!(r#"{{"synth_modes": [{{"loop_wave": {}}}]}}"#, usize::MAX / 4 + 1),
+        ];
+
+        for (index, json) in metadata.iter().enumerate() {
+            let path = std::env::temp_dir().join(format!("marumaru_preset_overflow_{}_{}.zip", std::process::id(), index));
+            let mut zip = ZipWriter::new(File::create(&path).unwrap());
+            zip.start_file(METADATA_FILE, SimpleFileOptions::default()).unwrap();
+            zip.write_all(json.as_bytes()).unwrap();
+            zip.finish().unwrap();
+
+            // 2. Act
+            let loaded = load_preset(&path);
+            let _ = std::fs::remove_file(&path);
+
+            // 3. Assert: パニックせずにエラーを返す
+            assert!(matches!(loaded, Err(AnalyzeError::Preset(_))), "{}", json);
+        }
+    }
+}
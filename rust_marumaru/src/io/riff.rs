@@ -0,0 +1,67 @@
+// src/io/riff.rs
+
+use crate::analyzer::AnalyzeError;
+use std::fs;
+use std::path::Path;
+
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// モノラル 32bit float の WAV を書き出す
+/// - extra_chunks は fmt と data の間に順に入れる (`clm ` / `smpl` 等)
+pub(crate) fn write_float_wav(
+    path: &Path,
+    sample_rate: u32,
+    samples: &[f32],
+    extra_chunks: &[(&[u8; 4], Vec<u8>)],
+) -> Result<(), AnalyzeError> {
+    let mut fmt = Vec::with_capacity(16);
+    fmt.extend_from_slice(&WAVE_FORMAT_IEEE_FLOAT.to_le_bytes());
+    fmt.extend_from_slice(&1u16.to_le_bytes()); // モノラル
+    fmt.extend_from_slice(&sample_rate.to_le_bytes());
+    fmt.extend_from_slice(&(sample_rate * 4).to_le_bytes()); // バイトレート
+    fmt.extend_from_slice(&4u16.to_le_bytes()); // ブロックサイズ
+    fmt.extend_from_slice(&32u16.to_le_bytes());
+
+    let mut data = Vec::with_capacity(samples.len() * 4);
+    for sample in samples {
+        data.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    let mut wav = Vec::with_capacity(12 + 8 + fmt.len() + 8 + data.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&0u32.to_le_bytes()); // 後で書き換える
+    wav.extend_from_slice(b"WAVE");
+    let chunks = std::iter::once((b"fmt ", &fmt))
+        .chain(extra_chunks.iter().map(|(id, body)| (*id, body)))
+        .chain(std::iter::once((b"data", &data)));
+    for (id, body) in chunks {
+        wav.extend_from_slice(id);
+        wav.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        wav.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            wav.push(0); // チャンクは偶数バイト境界にそろえる
+        }
+    }
+    let riff_len = (wav.len() - 8) as u32;
+    wav[4..8].copy_from_slice(&riff_len.to_le_bytes());
+
+    fs::write(path, wav).map_err(|e| AnalyzeError::Io(format!("Failed to write {:?}: {}", path, e)))
+}
+
+/// RIFF/WAVE のチャンクを探し、本体を返す
+pub(crate) fn find_chunk<'a>(bytes: &'a [u8], chunk_id: &[u8; 4]) -> Option<&'a [u8]> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let size = u32::from_le_bytes([bytes[pos + 4], bytes[pos + 5], bytes[pos + 6], bytes[pos + 7]]) as usize;
+        let body = bytes.get(pos + 8..(pos + 8 + size).min(bytes.len()))?;
+        if id == chunk_id {
+            return Some(body);
+        }
+        pos += 8 + size + size % 2;
+    }
+    None
+}
@@ -0,0 +1,196 @@
+// src/io/sampler.rs
+
+use super::preset::Preset;
+use super::riff::write_float_wav;
+use crate::analyzer::resample::resample_cycle;
+use crate::analyzer::AnalyzeError;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const SMPL_CHUNK_ID: &[u8; 4] = b"smpl";
+
+/// 有声フレームの平均F0 (F0が取れていない場合は None)
+fn mean_f0(f0_curve: &[f32]) -> Option<f32> {
+    let voiced: Vec<f32> = f0_curve.iter().copied().filter(|&f| f > 0.0).collect();
+    if voiced.is_empty() {
+        None
+    } else {
+        Some(voiced.iter().sum::<f32>() / voiced.len() as f32)
+    }
+}
+
+/// 周波数を MIDI ノート番号とのずれ [cent] に分ける
+fn midi_note_and_cents(freq: f32) -> (u8, f32) {
+    let note = 69.0 + 12.0 * (freq / 440.0).log2();
+    let rounded = note.round().clamp(0.0, 127.0);
+    (rounded as u8, (note - rounded) * 100.0)
+}
+
+/// 波形にゲインカーブを掛ける (カーブの長さが違う場合は線形補間で読む)
+fn apply_gain(wave: &[f32], gain: &[f32]) -> Vec<f32> {
+    if gain.is_empty() {
+        return wave.to_vec();
+    }
+    let scale = if wave.len() > 1 { (gain.len() - 1) as f32 / (wave.len() - 1) as f32 } else { 0.0 };
+    wave.iter().enumerate().map(|(i, &sample)| {
+        let pos = i as f32 * scale;
+        let idx0 = (pos.floor() as usize).min(gain.len() - 1);
+        let idx1 = (idx0 + 1).min(gain.len() - 1);
+        let frac = pos - idx0 as f32;
+        sample * (gain[idx0] * (1.0 - frac) + gain[idx1] * frac)
+    }).collect()
+}
+
+/// RIFF `smpl` チャンクの本体 (ループ1つ, 前方向)
+/// - loop_end はループ最後のサンプル位置 (終端を含む)
+fn smpl_chunk(sample_rate: u32, unity_note: u8, cents: f32, loop_start: u32, loop_end: u32) -> Vec<u8> {
+    // ピッチのずれは半音の割合を 2^32 倍した値 (正の方向のみ表現できる)
+    let pitch_fraction = (cents.max(0.0) / 100.0 * 4_294_967_296.0) as u32;
+    let header = [
+        0,                                   // manufacturer
+        0,                                   // product
+        1_000_000_000 / sample_rate.max(1),  // sample period [ns]
+        unity_note as u32,
+        pitch_fraction,
+        0,                                   // SMPTE format
+        0,                                   // SMPTE offset
+        1,                                   // ループ数
+        0,                                   // sampler data
+    ];
+    let sample_loop = [
+        0,          // cue point ID
+        0,          // type (0 = forward)
+        loop_start,
+        loop_end,
+        0,          // fraction
+        0,          // play count (0 = 無限)
+    ];
+    header.iter().chain(sample_loop.iter()).flat_map(|value| value.to_le_bytes()).collect()
+}
+
+/// Core/Loop/Release をサンプラー用の SFZ + WAV として書き出す
+/// - `<名前>.wav`: Core の後に Loop を原音の周期長で1周期置き、`smpl` チャンクにループ位置を書く
+/// - `<名前>_release.wav`: Release (ノートオフで鳴らすリリーストリガー)
+/// - `<名前>.sfz`: loop_sustain のリージョンとリリースのリージョン (エンベロープは ParamBundle から)
+pub fn export_sampler<P: AsRef<Path>>(sfz_path: P, preset: &Preset) -> Result<(), AnalyzeError> {
+    let sfz_path = sfz_path.as_ref();
+    if preset.sample_rate == 0 {
+        return Err(AnalyzeError::Sampler("Preset has no sample rate.".to_string()));
+    }
+    let sections = &preset.sections;
+    if sections.core_wave.is_empty() && sections.loop_wave.is_empty() {
+        return Err(AnalyzeError::Sampler("No Core or Loop section to export.".to_string()));
+    }
+    let stem = sfz_path.file_stem().and_then(|stem| stem.to_str())
+        .ok_or_else(|| AnalyzeError::InvalidArgument(format!("Invalid SFZ path: {:?}", sfz_path)))?;
+    let directory = sfz_path.parent().unwrap_or_else(|| Path::new(""));
+    let sustain_name = format!("{}.wav", stem);
+    let release_name = format!("{}_release.wav", stem);
+
+    // 1. ピッチ (平均F0) からキーと微調整を決める
+    let f0 = mean_f0(&preset.f0_curve);
+    let (key_center, cents) = f0.map_or((60, 0.0), midi_note_and_cents);
+
+    // 2. Loop は位相で1周期として再生しているため、原音の周期長に直してから Core の後ろに置く
+    //    (ゲインは平均値を掛けて Core の終わりとの段差を抑える)
+    let loop_cycle = if sections.loop_wave.is_empty() {
+        vec![]
+    } else {
+        let period = f0.map_or(sections.loop_wave.len(), |f0| (preset.sample_rate as f32 / f0).round().max(2.0) as usize);
+        let loop_gain = if sections.loop_gain.is_empty() {
+            1.0
+        } else {
+            sections.loop_gain.iter().sum::<f32>() / sections.loop_gain.len() as f32
+        };
+        resample_cycle(&sections.loop_wave, period).iter().map(|s| s * loop_gain).collect()
+    };
+    let mut sustain = apply_gain(&sections.core_wave, &sections.core_gain);
+    let loop_start = sustain.len();
+    sustain.extend_from_slice(&loop_cycle);
+    let loop_end = sustain.len().saturating_sub(1);
+
+    let smpl = smpl_chunk(preset.sample_rate, key_center, cents, loop_start as u32, loop_end as u32);
+    write_float_wav(&directory.join(&sustain_name), preset.sample_rate, &sustain, &[(SMPL_CHUNK_ID, smpl)])?;
+
+    let release = apply_gain(&sections.release_wave, &sections.release_gain);
+    if !release.is_empty() {
+        write_float_wav(&directory.join(&release_name), preset.sample_rate, &release, &[])?;
+    }
+
+    // 3. SFZ を書く
+    let params = &preset.params;
+    let mut sfz = String::new();
+    let _ = writeln!(sfz, "// MaruMaruSynth sampler export{}",
+        if preset.original_file_name.is_empty() { String::new() } else { format!(" ({})", preset.original_file_name) });
+    let _ = writeln!(sfz, "<global>");
+    let _ = writeln!(sfz, "pitch_keycenter={}", key_center);
+    let _ = writeln!(sfz, "tune={}", cents.round() as i32);
+    let _ = writeln!(sfz, "ampeg_attack={:.4}", params.attack);
+    let _ = writeln!(sfz, "ampeg_decay={:.4}", params.decay);
+    let _ = writeln!(sfz, "ampeg_sustain={:.1}", params.sustain.clamp(0.0, 1.0) * 100.0);
+    let _ = writeln!(sfz, "ampeg_release={:.4}", params.release);
+    let _ = writeln!(sfz);
+    let _ = writeln!(sfz, "<region>");
+    let _ = writeln!(sfz, "sample={}", sustain_name);
+    if !loop_cycle.is_empty() {
+        let _ = writeln!(sfz, "loop_mode=loop_sustain");
+        let _ = writeln!(sfz, "loop_start={}", loop_start);
+        let _ = writeln!(sfz, "loop_end={}", loop_end);
+    }
+    if !release.is_empty() {
+        let _ = writeln!(sfz);
+        let _ = writeln!(sfz, "<region>");
+        let _ = writeln!(sfz, "sample={}", release_name);
+        let _ = writeln!(sfz, "trigger=release");
+        let _ = writeln!(sfz, "rt_decay=0"); // 押している時間によらず同じ音量で鳴らす
+        let _ = writeln!(sfz, "ampeg_attack=0");
+        let _ = writeln!(sfz, "ampeg_sustain=100");
+    }
+    fs::write(sfz_path, sfz).map_err(|e| AnalyzeError::Io(format!("Failed to write {:?}: {}", sfz_path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::riff::find_chunk;
+    use crate::io::PresetSections;
+
+    #[test]
+    fn test_sampler_export_writes_loop_points() {
+        // 1. Arrange: 220Hz (44100Hz で約200サンプル周期) の Core/Loop/Release
+        let directory = std::env::temp_dir().join(format!("marumaru_sampler_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let cycle: Vec<f32> = (0..64).map(|i| (i as f32 / 64.0 * std::f32::consts::TAU).sin()).collect();
+        let preset = Preset {
+            sample_rate: 44100,
+            f0_curve: vec![0.0, 220.0, 220.0],
+            sections: PresetSections {
+                core_wave: vec![0.1; 1000],
+                loop_wave: cycle,
+                release_wave: vec![0.05; 300],
+                ..PresetSections::default()
+            },
+            ..Preset::default()
+        };
+
+        // 2. Act
+        export_sampler(directory.join("piano.sfz"), &preset).unwrap();
+        let sfz = fs::read_to_string(directory.join("piano.sfz")).unwrap();
+        let wav = fs::read(directory.join("piano.wav")).unwrap();
+        let release_exists = directory.join("piano_release.wav").exists();
+        let _ = fs::remove_dir_all(&directory);
+
+        // 3. Assert: ループは Core の直後から 1周期 (44100 / 220 ≒ 200 サンプル)
+        let smpl = find_chunk(&wav, SMPL_CHUNK_ID).unwrap();
+        let read_u32 = |offset: usize| u32::from_le_bytes(smpl[offset..offset + 4].try_into().unwrap());
+        assert_eq!(read_u32(12), 57); // A3
+        assert_eq!(read_u32(28), 1);
+        assert_eq!((read_u32(44), read_u32(48)), (1000, 1199));
+        assert!(sfz.contains("loop_mode=loop_sustain"));
+        assert!(sfz.contains("loop_start=1000"));
+        assert!(sfz.contains("loop_end=1199"));
+        assert!(sfz.contains("trigger=release"));
+        assert!(release_exists);
+    }
+}
@@ -0,0 +1,290 @@
+// src/io/state.rs
+
+use super::preset::{Preset, PresetSections};
+use crate::analyzer::AnalyzeError;
+use crate::oscillator::StereoLayout;
+use crate::ParamBundle;
+
+/// セッション状態のフォーマットバージョン
+/// - 新しいフィールドはペイロードの末尾に追加する (古い状態は末尾が足りない分をデフォルト値で読む)
+/// - 既存フィールドの並びを変えたら上げ、decode_state に変換を追加する
+pub const STATE_FORMAT_VERSION: u32 = 1;
+
+const STATE_MAGIC: &[u8; 4] = b"MMST";
+const HEADER_LEN: usize = 16; // マジック, バージョン, ペイロード長, CRC32 (各4バイト)
+
+/// ホストのプロジェクトに埋め込む Rust 側の状態 (パラメータとロード中の解析結果)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionState {
+    pub params: ParamBundle,
+    pub preset: Option<Preset>, // preset.params は params と同じ値として扱う
+}
+
+/// ParamBundle のフィールドを保存順に並べる (並びを変えないこと, 追加は末尾へ)
+fn param_fields(params: &mut ParamBundle) -> [&mut f32; 17] {
+    [
+        &mut params.attack, &mut params.decay, &mut params.sustain, &mut params.release,
+        &mut params.blend, &mut params.cutoff, &mut params.resonance,
+        &mut params.osc1_level, &mut params.osc2_level, &mut params.osc3_level,
+        &mut params.osc1_ratio, &mut params.osc2_ratio, &mut params.osc3_ratio,
+        &mut params.fm_index, &mut params.mix_mode_f,
+        &mut params.vibrato_depth, &mut params.vibrato_rate,
+    ]
+}
+
+/// リトルエンディアンのバイト列を組み立てる
+struct StateWriter {
+    out: Vec<u8>,
+}
+
+impl StateWriter {
+    fn u8(&mut self, value: u8) { self.out.push(value); }
+    fn u32(&mut self, value: u32) { self.out.extend_from_slice(&value.to_le_bytes()); }
+    fn u64(&mut self, value: u64) { self.out.extend_from_slice(&value.to_le_bytes()); }
+    fn f32(&mut self, value: f32) { self.out.extend_from_slice(&value.to_le_bytes()); }
+
+    fn f32s(&mut self, values: &[f32]) {
+        self.u32(values.len() as u32);
+        for &value in values {
+            self.f32(value);
+        }
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.out.extend_from_slice(value.as_bytes());
+    }
+
+    fn sections(&mut self, sections: &PresetSections) {
+        self.f32s(&sections.core_wave);
+        self.f32s(&sections.loop_wave);
+        self.f32s(&sections.release_wave);
+        self.f32s(&sections.core_gain);
+        self.f32s(&sections.loop_gain);
+        self.f32s(&sections.release_gain);
+    }
+}
+
+/// StateWriter で書いたバイト列を先頭から読む
+struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    fn is_at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], AnalyzeError> {
+        let bytes = self.pos.checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| AnalyzeError::State("State blob ended unexpectedly.".to_string()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, AnalyzeError> { Ok(self.bytes(1)?[0]) }
+    fn u32(&mut self) -> Result<u32, AnalyzeError> { Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap())) }
+    fn u64(&mut self) -> Result<u64, AnalyzeError> { Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap())) }
+    fn f32(&mut self) -> Result<f32, AnalyzeError> { Ok(f32::from_le_bytes(self.bytes(4)?.try_into().unwrap())) }
+
+    fn f32s(&mut self) -> Result<Vec<f32>, AnalyzeError> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len * 4)?;
+        Ok(bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+    }
+
+    fn string(&mut self) -> Result<String, AnalyzeError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec())
+            .map_err(|_| AnalyzeError::State("State blob has an invalid string.".to_string()))
+    }
+
+    fn sections(&mut self) -> Result<PresetSections, AnalyzeError> {
+        Ok(PresetSections {
+            core_wave: self.f32s()?,
+            loop_wave: self.f32s()?,
+            release_wave: self.f32s()?,
+            core_gain: self.f32s()?,
+            loop_gain: self.f32s()?,
+            release_gain: self.f32s()?,
+        })
+    }
+}
+
+/// セッション状態をバイナリ (ヘッダ + ペイロード) にする
+/// - ヘッダ: "MMST", バージョン, ペイロード長, ペイロードの CRC32
+pub fn encode_state(state: &SessionState) -> Vec<u8> {
+    let mut writer = StateWriter { out: Vec::new() };
+
+    let mut params = state.params;
+    let fields = param_fields(&mut params);
+    writer.u32(fields.len() as u32);
+    for field in fields {
+        writer.f32(*field);
+    }
+
+    match &state.preset {
+        None => writer.u8(0),
+        Some(preset) => {
+            writer.u8(1);
+            writer.u32(preset.sample_rate);
+            writer.u64(preset.created_at);
+            writer.string(&preset.original_file_name);
+            writer.f32s(&preset.f0_curve);
+            writer.f32s(&preset.pitch_contour);
+            writer.f32(preset.pitch_contour_rate);
+            let boundaries: Vec<f32> = preset.cycle_boundaries.iter().flat_map(|&(start, length)| [start, length]).collect();
+            writer.f32s(&boundaries);
+            writer.sections(&preset.sections);
+            match &preset.second_channel {
+                None => writer.u8(0),
+                Some((layout, sections)) => {
+                    writer.u8(match layout {
+                        StereoLayout::LeftRight => 1,
+                        StereoLayout::MidSide => 2,
+                    });
+                    writer.sections(sections);
+                }
+            }
+        }
+    }
+
+    let payload = writer.out;
+    let mut blob = Vec::with_capacity(HEADER_LEN + payload.len());
+    blob.extend_from_slice(STATE_MAGIC);
+    blob.extend_from_slice(&STATE_FORMAT_VERSION.to_le_bytes());
+    blob.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    blob.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    blob.extend_from_slice(&payload);
+    blob
+}
+
+/// encode_state で作ったバイナリを読み込む
+/// - マジック・長さ・チェックサムが合わない場合や、新しすぎるバージョンの場合はエラー
+pub fn decode_state(blob: &[u8]) -> Result<SessionState, AnalyzeError> {
+    if blob.len() < HEADER_LEN || &blob[0..4] != STATE_MAGIC {
+        return Err(AnalyzeError::State("Not a MaruMaru state blob.".to_string()));
+    }
+    let header_u32 = |offset: usize| u32::from_le_bytes([blob[offset], blob[offset + 1], blob[offset + 2], blob[offset + 3]]);
+    let version = header_u32(4);
+    let payload_len = header_u32(8) as usize;
+    let checksum = header_u32(12);
+    if version > STATE_FORMAT_VERSION {
+        return Err(AnalyzeError::State(format!(
+            "State format version {} is newer than supported version {}.", version, STATE_FORMAT_VERSION
+        )));
+    }
+    let payload = blob.get(HEADER_LEN..HEADER_LEN + payload_len)
+        .ok_or_else(|| AnalyzeError::State("State blob is truncated.".to_string()))?;
+    if crc32fast::hash(payload) != checksum {
+        return Err(AnalyzeError::State("State blob checksum mismatch.".to_string()));
+    }
+
+    let mut reader = StateReader { data: payload, pos: 0 };
+
+    // 保存時より ParamBundle のフィールドが増えていれば、足りない分はデフォルト値のまま
+    let mut params = ParamBundle::default();
+    let stored_count = reader.u32()? as usize;
+    {
+        let mut fields = param_fields(&mut params).into_iter();
+        for _ in 0..stored_count {
+            let value = reader.f32()?;
+            if let Some(field) = fields.next() {
+                *field = value;
+            }
+        }
+    }
+
+    let preset = if reader.is_at_end() || reader.u8()? == 0 {
+        None
+    } else {
+        let sample_rate = reader.u32()?;
+        let created_at = reader.u64()?;
+        let original_file_name = reader.string()?;
+        let f0_curve = reader.f32s()?;
+        let pitch_contour = reader.f32s()?;
+        let pitch_contour_rate = reader.f32()?;
+        let boundaries = reader.f32s()?;
+        let sections = reader.sections()?;
+        let second_channel = match reader.u8()? {
+            1 => Some((StereoLayout::LeftRight, reader.sections()?)),
+            2 => Some((StereoLayout::MidSide, reader.sections()?)),
+            _ => None,
+        };
+        Some(Preset {
+            sample_rate,
+            f0_curve,
+            pitch_contour,
+            pitch_contour_rate,
+            cycle_boundaries: boundaries.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect(),
+            sections,
+            second_channel,
+            params,
+            original_file_name,
+            created_at,
+        })
+    };
+
+    Ok(SessionState { params, preset })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_state() -> SessionState {
+        let params = ParamBundle { osc3_level: 0.4, mix_mode_f: 1.0, ..ParamBundle::default() };
+        let sections = PresetSections {
+            core_wave: (0..64).map(|i| (i as f32 * 0.05).sin()).collect(),
+            loop_wave: (0..32).map(|i| (i as f32 * 0.2).sin()).collect(),
+            release_wave: vec![0.1, 0.05, 0.0],
+            core_gain: vec![0.0, 1.0],
+            loop_gain: vec![1.0],
+            release_gain: vec![1.0, 0.0],
+        };
+        SessionState {
+            params,
+            preset: Some(Preset {
+                sample_rate: 48000,
+                f0_curve: vec![110.0, 110.5],
+                pitch_contour: vec![0.0, 7.8],
+                pitch_contour_rate: 93.75,
+                cycle_boundaries: vec![(12.0, 436.4)],
+                sections: sections.clone(),
+                second_channel: Some((StereoLayout::LeftRight, sections)),
+                params,
+                original_file_name: "cello.flac".to_string(),
+                created_at: 1_700_000_000,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        // 1. Arrange
+        let state = sample_state();
+
+        // 2. Act
+        let decoded = decode_state(&encode_state(&state)).unwrap();
+
+        // 3. Assert
+        assert_eq!(decoded, state);
+    }
+
+    #[test]
+    fn test_corrupted_state_is_rejected() {
+        // 1. Arrange
+        let mut blob = encode_state(&sample_state());
+        let last = blob.len() - 1;
+        blob[last] ^= 0xFF;
+
+        // 2. Act
+        let result = decode_state(&blob);
+
+        // 3. Assert
+        assert!(result.unwrap_err().to_string().contains("checksum"));
+        assert!(matches!(decode_state(&blob[..HEADER_LEN + 3]), Err(AnalyzeError::State(_))));
+    }
+}
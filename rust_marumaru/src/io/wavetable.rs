@@ -0,0 +1,114 @@
+// src/io/wavetable.rs
+
+use super::riff::{find_chunk, write_float_wav};
+use crate::analyzer::resample::resample_cycle;
+use crate::analyzer::AnalyzeError;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+/// 他のウェーブテーブルシンセ (Serum / Vital 等) が標準とするフレーム長
+pub const WAVETABLE_FRAME_SIZE: usize = 2048;
+/// 書き出すフレーム数の上限 (Serum の上限に合わせる)
+pub const MAX_WAVETABLE_FRAMES: usize = 256;
+
+const CLM_CHUNK_ID: &[u8; 4] = b"clm ";
+
+/// 1周期ずつのフレームを並べたウェーブテーブル
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wavetable {
+    pub frame_size: usize,
+    pub frames: Vec<Vec<f32>>,
+}
+
+/// テーブル (1周期ずつ) を 2048 サンプルのフレームに揃え、32bit float WAV として書き出す
+/// - フレーム長を示す `clm ` チャンクを fmt と data の間に入れる
+/// - フレーム数が MAX_WAVETABLE_FRAMES を超える場合は等間隔に間引く
+pub fn export_wavetable<P: AsRef<Path>>(path: P, tables: &[Vec<f32>], sample_rate: u32) -> Result<(), AnalyzeError> {
+    let tables: Vec<&Vec<f32>> = tables.iter().filter(|table| !table.is_empty()).collect();
+    if tables.is_empty() {
+        return Err(AnalyzeError::Wavetable("No wavetable frames to export.".to_string()));
+    }
+    let frame_count = tables.len().min(MAX_WAVETABLE_FRAMES);
+    let frames: Vec<Vec<f32>> = (0..frame_count)
+        .map(|i| tables[i * tables.len() / frame_count])
+        .map(|table| {
+            if table.len() == WAVETABLE_FRAME_SIZE { table.clone() } else { resample_cycle(table, WAVETABLE_FRAME_SIZE) }
+        })
+        .collect();
+
+    // 全フレームで共通のゲインで -1.0〜1.0 に収める (フレーム間の音量差は保つ)
+    let peak = frames.iter().flatten().map(|s| s.abs()).fold(0.0, f32::max);
+    let scale = if peak > 1.0 { 1.0 / peak } else { 1.0 };
+
+    let samples: Vec<f32> = frames.iter().flatten().map(|sample| sample * scale).collect();
+    let clm = format!("<!>{} 00000000 wavetable (MaruMaruSynth)", WAVETABLE_FRAME_SIZE).into_bytes();
+    write_float_wav(path.as_ref(), sample_rate, &samples, &[(CLM_CHUNK_ID, clm)])
+}
+
+/// RIFF の `clm ` チャンクからフレーム長を読む ("<!>2048 ..." の数字部分)
+fn clm_frame_size(bytes: &[u8]) -> Option<usize> {
+    let text = String::from_utf8_lossy(find_chunk(bytes, CLM_CHUNK_ID)?);
+    let digits: String = text.strip_prefix("<!>")?.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok().filter(|&size| size > 0)
+}
+
+/// ウェーブテーブル WAV を読み込み、フレームに分割する
+/// - フレーム長は `clm ` チャンクから読み、なければ 2048 とする
+/// - 複数チャンネルのファイルは平均してから分割し、端数のサンプルは捨てる
+pub fn import_wavetable<P: AsRef<Path>>(path: P) -> Result<Wavetable, AnalyzeError> {
+    let bytes = fs::read(path.as_ref())
+        .map_err(|e| AnalyzeError::Io(format!("Failed to read wavetable {:?}: {}", path.as_ref(), e)))?;
+    let frame_size = clm_frame_size(&bytes).unwrap_or(WAVETABLE_FRAME_SIZE);
+
+    let mut reader = hound::WavReader::new(Cursor::new(&bytes))
+        .map_err(|e| AnalyzeError::Wavetable(format!("Failed to parse wavetable WAV: {}", e)))?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>(),
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader.samples::<i32>().map(|s| s.map(|s| s as f32 * scale)).collect::<Result<_, _>>()
+        }
+    }.map_err(|e| AnalyzeError::Wavetable(format!("Failed to read wavetable samples: {}", e)))?;
+
+    let channels = spec.channels.max(1) as usize;
+    let mono: Vec<f32> = interleaved.chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    if mono.len() < frame_size {
+        return Err(AnalyzeError::Wavetable(format!(
+            "Wavetable is shorter than one frame ({} < {} samples).", mono.len(), frame_size
+        )));
+    }
+    let frames = mono.chunks_exact(frame_size).map(|frame| frame.to_vec()).collect();
+    Ok(Wavetable { frame_size, frames })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wavetable_round_trip_resamples_frames_to_2048() {
+        // 1. Arrange: 長さの違う2つの周期 (サイン波と矩形波)
+        let path = std::env::temp_dir().join(format!("marumaru_wavetable_{}.wav", std::process::id()));
+        let sine: Vec<f32> = (0..512).map(|i| (i as f32 / 512.0 * std::f32::consts::TAU).sin() * 0.5).collect();
+        let square: Vec<f32> = (0..2048).map(|i| if i < 1024 { 0.5 } else { -0.5 }).collect();
+
+        // 2. Act
+        export_wavetable(&path, &[sine, square.clone()], 44100).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let imported = import_wavetable(&path);
+        let _ = fs::remove_file(&path);
+
+        // 3. Assert
+        assert_eq!(clm_frame_size(&bytes), Some(WAVETABLE_FRAME_SIZE));
+        let imported = imported.unwrap();
+        assert_eq!(imported.frame_size, WAVETABLE_FRAME_SIZE);
+        assert_eq!(imported.frames.len(), 2);
+        let expected_sine = (512.0 / 2048.0 * std::f32::consts::TAU).sin() * 0.5;
+        assert!((imported.frames[0][512] - expected_sine).abs() < 1e-3);
+        assert_eq!(imported.frames[1], square);
+    }
+}
@@ -0,0 +1,189 @@
+// src/jobs/mod.rs
+
+//! バックグラウンド解析ジョブ
+//! - 解析はジョブごとのワーカースレッドで行い、呼び出し側はジョブIDで状態を問い合わせる
+//! - 結果は take_result で一度だけ取り出す (取り出したジョブは一覧から消える)
+
+use crate::analyzer::{self, AnalysisParams, AnalysisProgress, AnalysisResult, AnalyzeError, CancelToken};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// ジョブの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// ワーカースレッドと共有する状態
+struct JobShared {
+    progress: AtomicU32, // 進捗 0.0〜1.0 (f32 のビット列)
+    outcome: Mutex<Option<Result<AnalysisResult, AnalyzeError>>>, // 終了したら Some
+}
+
+struct Job {
+    shared: Arc<JobShared>,
+    cancel: CancelToken,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Job {
+    fn status(&self) -> JobStatus {
+        match self.shared.outcome.lock().ok().as_deref() {
+            Some(None) => JobStatus::Running,
+            Some(Some(Ok(_))) => JobStatus::Completed,
+            Some(Some(Err(AnalyzeError::Cancelled))) => JobStatus::Cancelled,
+            _ => JobStatus::Failed,
+        }
+    }
+}
+
+/// 解析ジョブの一覧 (Context ごとに1つ持つ)
+#[derive(Default)]
+pub struct JobManager {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<u64, Job>>,
+}
+
+impl JobManager {
+    pub fn new() -> Self {
+        JobManager::default()
+    }
+
+    /// 音声をワーカースレッドで解析し、ジョブID (1 以上) を返す
+    pub fn spawn_analysis(&self, audio: Vec<f32>, sample_rate: u32, params: AnalysisParams) -> Result<u64, AnalyzeError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let shared = Arc::new(JobShared { progress: AtomicU32::new(0), outcome: Mutex::new(None) });
+        let cancel = CancelToken::new();
+
+        let worker_shared = shared.clone();
+        let reporter = shared.clone();
+        let progress = AnalysisProgress::new(
+            Some(Arc::new(move |_stage, value: f32| reporter.progress.store(value.to_bits(), Ordering::Relaxed))),
+            cancel.clone(),
+        );
+        let handle = std::thread::Builder::new()
+            .name(format!("marumaru-analysis-{}", id))
+            .spawn(move || {
+                let result = analyzer::analyze_audio_with_progress(&audio, sample_rate, &params, &progress);
+                if let Ok(mut outcome) = worker_shared.outcome.lock() {
+                    *outcome = Some(result);
+                }
+            })
+            .map_err(|e| AnalyzeError::AnalysisFailed(format!("Failed to start analysis thread: {}", e)))?;
+
+        let mut jobs = self.jobs.lock().map_err(|_| AnalyzeError::AnalysisFailed("Job list is poisoned.".to_string()))?;
+        jobs.insert(id, Job { shared, cancel, handle: Some(handle) });
+        Ok(id)
+    }
+
+    /// ジョブの状態と進捗を返す (存在しないジョブは None)
+    pub fn poll(&self, id: u64) -> Option<(JobStatus, f32)> {
+        let jobs = self.jobs.lock().ok()?;
+        let job = jobs.get(&id)?;
+        let progress = f32::from_bits(job.shared.progress.load(Ordering::Relaxed));
+        Some((job.status(), progress))
+    }
+
+    /// ジョブにキャンセルを要求する (終了の確認は poll で行う)
+    pub fn cancel(&self, id: u64) -> bool {
+        let Ok(jobs) = self.jobs.lock() else { return false };
+        jobs.get(&id).map(|job| job.cancel.cancel()).is_some()
+    }
+
+    /// 終了したジョブの結果を取り出し、ジョブを一覧から消す
+    /// - 実行中のジョブや存在しないジョブは None
+    pub fn take_result(&self, id: u64) -> Option<Result<AnalysisResult, AnalyzeError>> {
+        let mut job = {
+            let mut jobs = self.jobs.lock().ok()?;
+            if jobs.get(&id)?.status() == JobStatus::Running {
+                return None;
+            }
+            jobs.remove(&id)?
+        };
+        if let Some(handle) = job.handle.take() {
+            let _ = handle.join();
+        }
+        let outcome = job.shared.outcome.lock().ok()?.take();
+        outcome
+    }
+}
+
+impl Drop for JobManager {
+    /// 実行中のジョブをすべてキャンセルし、ワーカースレッドの終了を待つ
+    fn drop(&mut self) {
+        let jobs = match self.jobs.get_mut() {
+            Ok(jobs) => std::mem::take(jobs),
+            Err(poisoned) => std::mem::take(poisoned.into_inner()),
+        };
+        for job in jobs.values() {
+            job.cancel.cancel();
+        }
+        for (_, mut job) in jobs {
+            if let Some(handle) = job.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn sine(len: usize) -> Vec<f32> {
+        (0..len).map(|i| (i as f32 * 220.0 / SAMPLE_RATE as f32 * std::f32::consts::TAU).sin() * 0.7).collect()
+    }
+
+    fn wait_until_finished(manager: &JobManager, id: u64) -> (JobStatus, f32) {
+        let deadline = Instant::now() + Duration::from_secs(120);
+        loop {
+            let state = manager.poll(id).unwrap();
+            if state.0 != JobStatus::Running || Instant::now() > deadline {
+                return state;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_job_completes_and_result_is_taken_once() {
+        // 1. Arrange
+        let manager = JobManager::new();
+
+        // 2. Act
+        let id = manager.spawn_analysis(sine(SAMPLE_RATE as usize), SAMPLE_RATE, AnalysisParams::default()).unwrap();
+        let (status, progress) = wait_until_finished(&manager, id);
+        let result = manager.take_result(id);
+
+        // 3. Assert
+        assert_eq!(status, JobStatus::Completed);
+        assert_eq!(progress, 1.0);
+        assert!(!result.unwrap().unwrap().tables.is_empty());
+        assert!(manager.poll(id).is_none());
+        assert!(manager.take_result(id).is_none());
+    }
+
+    #[test]
+    fn test_cancelled_job_reports_cancelled() {
+        // 1. Arrange: 長めの音声 (キャンセル前に終わらないように)
+        let manager = JobManager::new();
+        let id = manager.spawn_analysis(sine(SAMPLE_RATE as usize * 20), SAMPLE_RATE, AnalysisParams::default()).unwrap();
+
+        // 2. Act
+        assert!(manager.cancel(id));
+        let (status, _) = wait_until_finished(&manager, id);
+
+        // 3. Assert
+        assert_eq!(status, JobStatus::Cancelled);
+        assert_eq!(manager.take_result(id).unwrap().unwrap_err(), AnalyzeError::Cancelled);
+        assert!(!manager.cancel(id));
+    }
+}
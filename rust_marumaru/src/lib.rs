@@ -11,16 +11,23 @@ use std::fs::{File, OpenOptions};
 use std::io::Write;      
 use std::time::{SystemTime, UNIX_EPOCH}; 
 use std::ffi::CStr;
-use std::os::raw::c_char;
+use std::os::raw::{c_char, c_void};
 use std::sync::atomic::{AtomicPtr, Ordering};
 use std::boxed::Box;
-use std::ops::Rem; 
 
 pub mod analyzer;
 pub mod oscillator; 
+pub mod io;
+pub mod render;
+pub mod diagnostics;
+pub mod jobs;
 
 // ★ 修正点: 必要な型をインポート
-use crate::analyzer::types::{AnalysisResult}; 
+use crate::analyzer::AnalyzeError;
+use crate::analyzer::types::{
+    AnalysisDomain, AnalysisParams, AnalysisProfile, AnalysisResult, ChannelMode, CycleAlignment, ModeReason,
+    ModeSelection, MultichannelAnalysisResult, PitchEstimatorKind, QualityFlags, WindowType,
+}; 
 
 
 //==============================================================================
@@ -79,8 +86,11 @@ fn log_to_file(msg: &str) {
 //==============================================================================
 
 /// Rust と C++ 間で共有するパラメータ構造体
+/// - フィールドを変えたら Source/RustBridge.h の ParamBundle も同じ順序に揃えること
+/// - プリセットに保存するため serde に対応 (欠けたフィールドはデフォルト値で読む)
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct ParamBundle {
     pub attack    : f32,
     pub decay     : f32,
@@ -98,25 +108,16 @@ pub struct ParamBundle {
     pub osc3_ratio: f32,
     pub fm_index: f32, // OSC2がOSC1を変調する強度
     pub mix_mode_f: f32, // MixModeをf32で受け取る (0.0=Add, 1.0=FMなど)
+    pub vibrato_depth: f32, // 原音のビブラート再適用の深さ (0.0=無効, 1.0=原音どおり)
+    pub vibrato_rate : f32, // 原音のビブラート再適用の速さ (1.0=原音どおり)
 }
 
-/// プラグイン内部コンテキスト（C++からは不透明ポインタで扱う）
-pub struct Context {
-    pub sample_rate : f32,
-    pub block_size  : i32,
-    pub channels    : i32,
-    pub params_ptr  : AtomicPtr<ParamBundle>,
-    pub phase       : f32,
-    pub phase_inc   : f32,
-    pub active      : bool,
-    pub amp         : f32,
-    pub osc_bank    : Mutex<oscillator::OscillatorBank>, 
-}
+// C++ 側 (RustBridge.h) の static_assert と同じサイズであることを確認する
+const _: () = assert!(std::mem::size_of::<ParamBundle>() == 17 * std::mem::size_of::<f32>());
 
-impl Context {
-    /// 初期パラメータをヒープに確保してポインタを返す
-    fn default_params_ptr() -> *mut ParamBundle {
-        let b = Box::new(ParamBundle {
+impl Default for ParamBundle {
+    fn default() -> Self {
+        ParamBundle {
             attack     : 0.01,
             decay      : 0.1,
             sustain    : 0.8,
@@ -133,7 +134,33 @@ impl Context {
             osc3_ratio : 2.0,
             fm_index   : 5.0, // 初期FM変調強度
             mix_mode_f : 0.0, // 初期値は加算合成(Add)
-        });
+            vibrato_depth : 0.0, // 初期値はビブラート再適用なし
+            vibrato_rate  : 1.0,
+        }
+    }
+}
+
+/// プラグイン内部コンテキスト（C++からは不透明ポインタで扱う）
+pub struct Context {
+    pub sample_rate : f32,
+    pub block_size  : i32,
+    pub channels    : i32,
+    pub params_ptr  : AtomicPtr<ParamBundle>,
+    pub phase       : f32,
+    pub phase_inc   : f32,
+    pub active      : bool,
+    pub amp         : f32,
+    pub osc_bank    : Mutex<oscillator::OscillatorBank>, 
+    pub preset      : Mutex<Option<io::Preset>>, // ロード中の解析結果 (プリセット保存用)
+    pub running     : Mutex<Vec<analyzer::CancelToken>>, // 実行中の解析ごとのキャンセルトークン (mm_cancel_analysis)
+    pub jobs        : jobs::JobManager,          // バックグラウンド解析ジョブ (mm_analyze_async)
+    pub last_error  : Mutex<Option<AnalyzeError>>, // 直近に失敗した呼び出しの理由 (mm_last_error_message)
+}
+
+impl Context {
+    /// 初期パラメータをヒープに確保してポインタを返す
+    fn default_params_ptr() -> *mut ParamBundle {
+        let b = Box::new(ParamBundle::default());
         Box::into_raw(b)
     }
 }
@@ -161,8 +188,54 @@ pub struct AnalysisResultFFI {
     // Other Analysis Data
     pub avg_periodicity     : f32,
     pub quality_score       : f32,
+
+    // Mode Decision (0=Time, 1=Hybrid, 2=Freq / 0=HighPeriodicity, 1=MediumPeriodicity,
+    //                2=LowPeriodicity, 3=Forced, 4=BestQuality)
+    pub selected_mode       : i32,
+    pub mode_reason         : i32,
+
+    // Pitch Contour (平均からのずれ [cent], フレームレート [frames/s])
+    pub pitch_contour_ptr   : *mut f32,
+    pub pitch_contour_len   : usize,
+    pub pitch_contour_rate  : f32,
+
+    // Quality Details (全体の指標)
+    // - quality_flags: bit0=周期間相関OK, bit1=スペクトルエネルギー差OK, bit2=NaN率OK, bit3=SNR OK, bit4=周期整合度OK
+    // - *_quality_flags: Core / Loop / Release 区間ごとの同じビット
+    pub cycle_correlation   : f32,
+    pub spectral_energy_diff: f32,
+    pub snr_db              : f32,
+    pub period_consistency  : f32,
+    pub nan_ratio           : f32,
+    pub quality_flags       : u32,
+    pub core_quality_flags  : u32,
+    pub loop_quality_flags  : u32,
+    pub release_quality_flags: u32,
+
+    // Stereo (0=Mono, 1=LeftRight, 2=MidSide)
+    // - second_channel: 2チャンネル目 (R または Side) の解析結果 (モノラルの場合は null)
+    pub channel_layout      : i32,
+    pub second_channel      : *mut AnalysisResultFFI,
+
+    // Core/Release のサンプルレート (ロード時に Context のサンプルレートへ変換する)
+    pub sample_rate         : u32,
+
+    // F0カーブ [Hz] と原音の周期境界 (開始位置, 周期長 の組を平坦に並べたもの, 長さは組の数)
+    pub f0_curve_ptr        : *mut f32,
+    pub f0_curve_len        : usize,
+    pub cycle_boundaries_ptr: *mut f32,
+    pub cycle_boundaries_len: usize,
 }
 
+fn quality_flag_bits(flags: &QualityFlags) -> u32 {
+    (flags.cycle_correlation_ok as u32)
+        | (flags.spectral_energy_ok as u32) << 1
+        | (flags.nan_ratio_ok as u32) << 2
+        | (flags.snr_ok as u32) << 3
+        | (flags.period_consistency_ok as u32) << 4
+}
+
+#[allow(clippy::redundant_field_names)]
 impl From<AnalysisResult> for AnalysisResultFFI {
     fn from(analysis: AnalysisResult) -> Self {
         
@@ -182,6 +255,20 @@ impl From<AnalysisResult> for AnalysisResultFFI {
         let release_gain_box = analysis.release_gain.into_boxed_slice();
         let release_gain_len = release_gain_box.len(); 
 
+        let sample_rate = analysis.sample_rate;
+
+        // Pitch Contour
+        let pitch_contour_box = analysis.pitch_contour.into_boxed_slice();
+        let pitch_contour_len = pitch_contour_box.len();
+
+        // F0 Curve / Cycle Boundaries
+        let f0_curve_box = analysis.f0_curve.into_boxed_slice();
+        let f0_curve_len = f0_curve_box.len();
+        let cycle_boundaries_len = analysis.cycle_boundaries.len();
+        let cycle_boundaries_box: Box<[f32]> = analysis.cycle_boundaries.iter()
+            .flat_map(|&(start, length)| [start, length])
+            .collect();
+
         // F0の平均信頼度を計算
         let avg_periodicity = if !analysis.confidence.is_empty() {
             analysis.confidence.iter().sum::<f32>() / analysis.confidence.len() as f32
@@ -208,6 +295,252 @@ impl From<AnalysisResult> for AnalysisResultFFI {
             
             avg_periodicity: avg_periodicity,
             quality_score: analysis.quality.correlation,
+
+            selected_mode: match analysis.mode_decision.mode {
+                AnalysisDomain::Time   => 0,
+                AnalysisDomain::Hybrid => 1,
+                AnalysisDomain::Freq   => 2,
+            },
+            mode_reason: match analysis.mode_decision.reason {
+                ModeReason::HighPeriodicity   => 0,
+                ModeReason::MediumPeriodicity => 1,
+                ModeReason::LowPeriodicity    => 2,
+                ModeReason::Forced            => 3,
+                ModeReason::BestQuality       => 4,
+            },
+
+            pitch_contour_ptr: Box::into_raw(pitch_contour_box) as *mut f32,
+            pitch_contour_len,
+            pitch_contour_rate: analysis.pitch_contour_rate,
+
+            cycle_correlation: analysis.quality.overall.cycle_correlation,
+            spectral_energy_diff: analysis.quality.overall.spectral_energy_diff,
+            snr_db: analysis.quality.overall.snr_db,
+            period_consistency: analysis.quality.overall.period_consistency,
+            nan_ratio: analysis.quality.nan_ratio,
+            quality_flags: quality_flag_bits(&analysis.quality.overall.flags),
+            core_quality_flags: quality_flag_bits(&analysis.quality.core.flags),
+            loop_quality_flags: quality_flag_bits(&analysis.quality.loop_section.flags),
+            release_quality_flags: quality_flag_bits(&analysis.quality.release.flags),
+
+            channel_layout: 0,
+            second_channel: std::ptr::null_mut(),
+
+            sample_rate,
+
+            f0_curve_ptr: Box::into_raw(f0_curve_box) as *mut f32,
+            f0_curve_len,
+            cycle_boundaries_ptr: Box::into_raw(cycle_boundaries_box) as *mut f32,
+            cycle_boundaries_len,
+        }
+    }
+}
+
+impl TryFrom<MultichannelAnalysisResult> for AnalysisResultFFI {
+    type Error = AnalyzeError;
+
+    /// 先頭チャンネルを本体とし、2チャンネル目を second_channel に入れる
+    /// - PerChannel で3チャンネル以上ある場合、ステレオ再生に使う先頭2チャンネルのみを渡す
+    /// - チャンネルが1つもない場合は AnalysisFailed を返す (FFI 越しにパニックさせない)
+    fn try_from(analysis: MultichannelAnalysisResult) -> Result<Self, AnalyzeError> {
+        let layout = match (analysis.channel_mode, analysis.channels.len()) {
+            (ChannelMode::PerChannel, n) if n >= 2 => 1,
+            (ChannelMode::MidSide, 2) => 2,
+            _ => 0,
+        };
+        let mut channels = analysis.channels.into_iter();
+        let mut result = channels.next().map(AnalysisResultFFI::from)
+            .ok_or_else(|| AnalyzeError::AnalysisFailed("Analysis produced no channels.".to_string()))?;
+        if layout != 0 {
+            if let Some(second) = channels.next() {
+                result.channel_layout = layout;
+                result.second_channel = Box::into_raw(Box::new(AnalysisResultFFI::from(second)));
+            }
+        }
+        Ok(result)
+    }
+}
+
+
+/// 解析パラメータ（FFIとしてC++に公開するため）
+/// - window_type: 0=Hann, 1=Hamming, 2=Blackman, 3=Rectangular
+/// - profile    : 0=Natural, 1=Electronic, 2=Auto (Natural / Electronic のプリセットは mm_analysis_params_for_profile で適用する)
+/// - mode_selection: 0=Auto, 1=Time, 2=Hybrid, 3=Freq, 4=RankAll
+/// - pitch_estimator_mask: bit0=YIN, bit1=Cepstrum, bit2=HPS
+/// - cycle_alignment: 0=ZeroCrossing, 1=Peak
+/// - channel_mode: 0=MidOnly, 1=PerChannel, 2=MidSide
+/// - analysis_sample_rate: ファイル解析時のリサンプリング先 (0=ファイルのまま)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct AnalysisParamsFFI {
+    pub fft_size              : usize,
+    pub hop_size              : usize,
+    pub window_type           : i32,
+    pub min_f0                : f32,
+    pub max_f0                : f32,
+    pub profile               : i32,
+    pub normalize_target_dbfs : f32,
+    pub dc_highpass           : bool,
+    pub spectral_gate         : bool,
+    pub noise_gate_threshold  : f32,
+    pub cycle_averaging       : bool,
+    pub mode_selection        : i32,
+    pub time_mode_threshold   : f32,
+    pub hybrid_mode_threshold : f32,
+    pub core_end_ratio        : f32,
+    pub release_start_ratio   : f32,
+    pub pitch_estimator_mask  : u32,
+    pub voicing_threshold     : f32,
+    pub yin_power_threshold   : f32,
+    pub yin_clarity_threshold : f32,
+    pub cycle_alignment       : i32,
+    pub target_cycle_len      : usize,
+    pub freq_table_count      : usize,
+    pub pitch_sync            : bool,
+    pub channel_mode          : i32,
+    pub analysis_sample_rate  : u32,
+}
+
+const PITCH_ESTIMATOR_BITS: [(u32, PitchEstimatorKind); 3] = [
+    (1 << 0, PitchEstimatorKind::Yin),
+    (1 << 1, PitchEstimatorKind::Cepstrum),
+    (1 << 2, PitchEstimatorKind::Hps),
+];
+
+fn profile_from_code(code: i32) -> Option<AnalysisProfile> {
+    match code {
+        0 => Some(AnalysisProfile::Natural),
+        1 => Some(AnalysisProfile::Electronic),
+        2 => Some(AnalysisProfile::Auto),
+        _ => None,
+    }
+}
+
+/// 不明な列挙値のコードに対するエラー
+fn unknown_code(field: &str, code: i32) -> AnalyzeError {
+    AnalyzeError::InvalidArgument(format!("Unknown {} code: {}.", field, code))
+}
+
+fn profile_to_code(profile: AnalysisProfile) -> i32 {
+    match profile {
+        AnalysisProfile::Natural    => 0,
+        AnalysisProfile::Electronic => 1,
+        AnalysisProfile::Auto       => 2,
+    }
+}
+
+impl TryFrom<&AnalysisParamsFFI> for AnalysisParams {
+    type Error = AnalyzeError;
+
+    /// 列挙値のコードが不明な場合は InvalidArgument を返す
+    fn try_from(p: &AnalysisParamsFFI) -> Result<Self, AnalyzeError> {
+        let window = match p.window_type {
+            1 => WindowType::Hamming,
+            2 => WindowType::Blackman,
+            3 => WindowType::Rectangular,
+            0 => WindowType::Hann,
+            code => return Err(unknown_code("window_type", code)),
+        };
+        Ok(AnalysisParams {
+            fft_size              : p.fft_size,
+            hop_size              : p.hop_size,
+            window,
+            min_f0                : p.min_f0,
+            max_f0                : p.max_f0,
+            pitch_estimators      : PITCH_ESTIMATOR_BITS.iter()
+                .filter(|(bit, _)| p.pitch_estimator_mask & bit != 0)
+                .map(|(_, kind)| *kind)
+                .collect(),
+            voicing_threshold     : p.voicing_threshold,
+            yin_power_threshold   : p.yin_power_threshold,
+            yin_clarity_threshold : p.yin_clarity_threshold,
+            cycle_alignment       : match p.cycle_alignment {
+                0 => CycleAlignment::ZeroCrossing,
+                1 => CycleAlignment::Peak,
+                code => return Err(unknown_code("cycle_alignment", code)),
+            },
+            target_cycle_len      : p.target_cycle_len,
+            freq_table_count      : p.freq_table_count,
+            pitch_sync            : p.pitch_sync,
+            profile               : profile_from_code(p.profile).ok_or_else(|| unknown_code("profile", p.profile))?,
+            normalize_target_dbfs : p.normalize_target_dbfs,
+            dc_highpass           : p.dc_highpass,
+            spectral_gate         : p.spectral_gate,
+            noise_gate_threshold  : p.noise_gate_threshold,
+            cycle_averaging       : p.cycle_averaging,
+            mode_selection        : match p.mode_selection {
+                1 => ModeSelection::Time,
+                2 => ModeSelection::Hybrid,
+                3 => ModeSelection::Freq,
+                0 => ModeSelection::Auto,
+                4 => ModeSelection::RankAll,
+                code => return Err(unknown_code("mode_selection", code)),
+            },
+            time_mode_threshold   : p.time_mode_threshold,
+            hybrid_mode_threshold : p.hybrid_mode_threshold,
+            core_end_ratio        : p.core_end_ratio,
+            release_start_ratio   : p.release_start_ratio,
+            channel_mode          : match p.channel_mode {
+                1 => ChannelMode::PerChannel,
+                0 => ChannelMode::MidOnly,
+                2 => ChannelMode::MidSide,
+                code => return Err(unknown_code("channel_mode", code)),
+            },
+            analysis_sample_rate  : if p.analysis_sample_rate > 0 { Some(p.analysis_sample_rate) } else { None },
+        })
+    }
+}
+
+impl From<&AnalysisParams> for AnalysisParamsFFI {
+    fn from(p: &AnalysisParams) -> Self {
+        let window_type = match p.window {
+            WindowType::Hann        => 0,
+            WindowType::Hamming     => 1,
+            WindowType::Blackman    => 2,
+            WindowType::Rectangular => 3,
+        };
+        AnalysisParamsFFI {
+            fft_size              : p.fft_size,
+            hop_size              : p.hop_size,
+            window_type,
+            min_f0                : p.min_f0,
+            max_f0                : p.max_f0,
+            profile               : profile_to_code(p.profile),
+            normalize_target_dbfs : p.normalize_target_dbfs,
+            dc_highpass           : p.dc_highpass,
+            spectral_gate         : p.spectral_gate,
+            noise_gate_threshold  : p.noise_gate_threshold,
+            cycle_averaging       : p.cycle_averaging,
+            mode_selection        : match p.mode_selection {
+                ModeSelection::Auto    => 0,
+                ModeSelection::Time    => 1,
+                ModeSelection::Hybrid  => 2,
+                ModeSelection::Freq    => 3,
+                ModeSelection::RankAll => 4,
+            },
+            time_mode_threshold   : p.time_mode_threshold,
+            hybrid_mode_threshold : p.hybrid_mode_threshold,
+            core_end_ratio        : p.core_end_ratio,
+            release_start_ratio   : p.release_start_ratio,
+            pitch_estimator_mask  : PITCH_ESTIMATOR_BITS.iter()
+                .filter(|(_, kind)| p.pitch_estimators.contains(kind))
+                .fold(0, |mask, (bit, _)| mask | bit),
+            voicing_threshold     : p.voicing_threshold,
+            yin_power_threshold   : p.yin_power_threshold,
+            yin_clarity_threshold : p.yin_clarity_threshold,
+            cycle_alignment       : match p.cycle_alignment {
+                CycleAlignment::ZeroCrossing => 0,
+                CycleAlignment::Peak         => 1,
+            },
+            target_cycle_len      : p.target_cycle_len,
+            freq_table_count      : p.freq_table_count,
+            pitch_sync            : p.pitch_sync,
+            channel_mode          : match p.channel_mode {
+                ChannelMode::MidOnly    => 0,
+                ChannelMode::PerChannel => 1,
+                ChannelMode::MidSide    => 2,
+            },
+            analysis_sample_rate  : p.analysis_sample_rate.unwrap_or(0),
         }
     }
 }
@@ -222,6 +555,8 @@ impl From<AnalysisResult> for AnalysisResultFFI {
 ///-----------------------------------------------------------------------------
 /// mm_log_message
 /// - C++側からのログ出力を受け付ける
+/// # Safety
+/// - message は null か、NUL 終端の文字列を指すこと
 ///-----------------------------------------------------------------------------
 #[no_mangle]
 pub unsafe extern "C" fn mm_log_message(message: *const c_char) {
@@ -236,6 +571,8 @@ pub unsafe extern "C" fn mm_log_message(message: *const c_char) {
 ///-----------------------------------------------------------------------------
 /// mm_create_context
 /// - プラグインの内部状態(Context)を初期化してポインタを返す
+/// # Safety
+/// - 戻り値は mm_destroy_context で一度だけ解放すること
 ///-----------------------------------------------------------------------------
 #[no_mangle]
 pub unsafe extern "C" fn mm_create_context(sample_rate: f32, block_size: i32, channels: i32) -> *mut Context {
@@ -250,41 +587,123 @@ pub unsafe extern "C" fn mm_create_context(sample_rate: f32, block_size: i32, ch
         amp        : 0.0,
         // ★ 修正点: OscillatorBank の初期化
         osc_bank   : Mutex::new(oscillator::OscillatorBank::new(sample_rate)),
+        preset     : Mutex::new(None),
+        running    : Mutex::new(Vec::new()),
+        jobs       : jobs::JobManager::new(),
+        last_error : Mutex::new(None),
     });
     Box::into_raw(ctx)
 }
 
+///-----------------------------------------------------------------------------
+/// mm_set_sample_rate
+/// - ホストのサンプルレートが変わったときに呼ぶ
+/// - ロード済みの Core/Release は解析時のレートの原本から変換し直す
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - Context を書き換えるため、mm_process と並行して呼ばないこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_set_sample_rate(ctx_ptr: *mut Context, sample_rate: f32) {
+    if ctx_ptr.is_null() || sample_rate <= 0.0 { return; }
+    let ctx = unsafe { &mut *ctx_ptr };
+    ctx.sample_rate = sample_rate;
+    if let Ok(mut osc_bank) = ctx.osc_bank.lock() {
+        osc_bank.set_sample_rate(sample_rate);
+        log_message_internal("Rust", &format!("Sample rate changed to {} Hz.", sample_rate));
+    } else {
+        log_message_internal("Rust", "mm_set_sample_rate failed: Mutex lock error.");
+    }
+}
+
+///-----------------------------------------------------------------------------
+/// mm_default_analysis_params
+/// - 解析パラメータのデフォルト値を返す (C++側はこれを書き換えて渡す)
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub extern "C" fn mm_default_analysis_params() -> AnalysisParamsFFI {
+    AnalysisParamsFFI::from(&AnalysisParams::default())
+}
+
+///-----------------------------------------------------------------------------
+/// mm_analysis_params_for_profile
+/// - 指定プロファイル (0=Natural, 1=Electronic, 2=Auto) のプリセットを返す
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub extern "C" fn mm_analysis_params_for_profile(profile: i32) -> AnalysisParamsFFI {
+    let profile = profile_from_code(profile).unwrap_or_else(|| {
+        log_message_internal("Rust", &format!("Unknown profile code {}, using Natural.", profile));
+        AnalysisProfile::Natural
+    });
+    AnalysisParamsFFI::from(&AnalysisParams::from_profile(profile))
+}
+
 ///-----------------------------------------------------------------------------
 /// mm_analyze_buffer
 /// - C++(JUCE)またはテストコードから生の音声バッファを受け取り解析する
+/// - セクション比率以外の解析パラメータはデフォルト値を使用する
 /// - 戻り値: AnalysisResultFFI のポインタ (解析に失敗した場合は null)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - buffer は null か、num_samples 個の f32 を読める領域を指すこと
 ///-----------------------------------------------------------------------------
 #[no_mangle]
 pub unsafe extern "C" fn mm_analyze_buffer(
-    _ctx_ptr    : *mut Context, // 解析結果を保存しないので一旦未使用
+    ctx_ptr     : *mut Context,
     buffer      : *const f32,
     num_samples : usize,
     sample_rate : u32,
     core_end_ratio    : f32, 
     release_start_ratio : f32, 
 ) -> *mut AnalysisResultFFI { // ★ 戻り値を変更
+    let mut params = mm_default_analysis_params();
+    params.core_end_ratio = core_end_ratio;
+    params.release_start_ratio = release_start_ratio;
+    mm_analyze_buffer_with_params(ctx_ptr, buffer, num_samples, sample_rate, &params)
+}
+
+///-----------------------------------------------------------------------------
+/// mm_analyze_buffer_with_params
+/// - mm_analyze_buffer の解析パラメータ指定版
+/// - params が null の場合はデフォルト値を使用する
+/// - 戻り値: AnalysisResultFFI のポインタ (解析に失敗した場合は null, 理由は mm_last_error_code で取り出す)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - buffer は null か、num_samples 個の f32 を読める領域を指すこと
+/// - params は null か、有効な AnalysisParamsFFI を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_analyze_buffer_with_params(
+    ctx_ptr     : *mut Context, // 失敗の理由の記録に使う (null 可)
+    buffer      : *const f32,
+    num_samples : usize,
+    sample_rate : u32,
+    params      : *const AnalysisParamsFFI,
+) -> *mut AnalysisResultFFI {
+    clear_error(ctx_ptr);
     if buffer.is_null() { 
-        log_message_internal("Rust", "mm_analyze_buffer failed: Input buffer is null.");
+        record_error(ctx_ptr, "mm_analyze_buffer", AnalyzeError::InvalidArgument("Input buffer is null.".to_string()));
         return std::ptr::null_mut(); 
     }
 
     let audio_slice = std::slice::from_raw_parts(buffer, num_samples);
+    let params = match params_from_ffi(params) {
+        Ok(params) => params,
+        Err(e) => {
+            record_error(ctx_ptr, "mm_analyze_buffer", e);
+            return std::ptr::null_mut();
+        }
+    };
 
     log_message_internal("Rust", &format!(
-        "mm_analyze_buffer called. Samples: {}, Rate: {}, Core End Ratio: {:.2}, Release Start Ratio: {:.2}",
-        num_samples, sample_rate, core_end_ratio, release_start_ratio
+        "mm_analyze_buffer called. Samples: {}, Rate: {}, Params: {:?}",
+        num_samples, sample_rate, params
     ));
     
     match analyzer::analyze_audio(
         audio_slice,
         sample_rate,
-        core_end_ratio, 
-        release_start_ratio, 
+        &params,
     ) {
         Ok(analysis_data) => {
             log_message_internal("Rust", &format!("Buffer analysis successful. Core len: {}, Loop len: {}, Release len: {}", 
@@ -299,66 +718,791 @@ pub unsafe extern "C" fn mm_analyze_buffer(
             Box::into_raw(boxed_result) // ポインタを返し、C++側にメモリ管理を委譲
         }
         Err(e) => {
-            log_message_internal("Rust", &format!("Buffer analysis failed: {}", e));
+            record_error(ctx_ptr, "mm_analyze_buffer", e);
             std::ptr::null_mut() // 失敗時はnull
         }
     }
 }
 
+///-----------------------------------------------------------------------------
+/// mm_analyze_async
+/// - バッファをコピーし、Rust のワーカースレッドで解析を始める (すぐに戻る)
+/// - 状態は mm_poll_job で確認し、終了後に mm_job_result で結果を OSC にロードする
+/// - params が null の場合はデフォルト値を使用する
+/// - 戻り値: ジョブID (1 以上, 開始できなかった場合は 0)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - buffer は null か、num_samples 個の f32 を読める領域を指すこと (呼び出し中にコピーする)
+/// - params は null か、有効な AnalysisParamsFFI を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_analyze_async(
+    ctx_ptr     : *mut Context,
+    buffer      : *const f32,
+    num_samples : usize,
+    sample_rate : u32,
+    params      : *const AnalysisParamsFFI,
+) -> u64 {
+    clear_error(ctx_ptr);
+    if ctx_ptr.is_null() || buffer.is_null() {
+        record_error(ctx_ptr, "mm_analyze_async", AnalyzeError::InvalidArgument("Null pointer.".to_string()));
+        return 0;
+    }
+    let ctx = &*ctx_ptr;
+    let audio = std::slice::from_raw_parts(buffer, num_samples).to_vec();
+    let params = match params_from_ffi(params) {
+        Ok(params) => params,
+        Err(e) => {
+            record_error(ctx_ptr, "mm_analyze_async", e);
+            return 0;
+        }
+    };
+    match ctx.jobs.spawn_analysis(audio, sample_rate, params) {
+        Ok(job_id) => {
+            log_message_internal("Rust", &format!(
+                "Analysis job {} started. Samples: {}, Rate: {}", job_id, num_samples, sample_rate
+            ));
+            job_id
+        }
+        Err(e) => {
+            record_error(ctx_ptr, "mm_analyze_async", e);
+            0
+        }
+    }
+}
+
+///-----------------------------------------------------------------------------
+/// mm_poll_job
+/// - ジョブの状態を返し、out_progress (null 可) に進捗 0.0〜1.0 を書き込む
+/// - 戻り値: 0=実行中, 1=完了, 2=失敗, 3=キャンセル済み, ジョブが存在しない場合は -エラーコード
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - out_progress は null か、f32 を1つ書ける領域を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_poll_job(ctx_ptr: *mut Context, job_id: u64, out_progress: *mut f32) -> i32 {
+    clear_error(ctx_ptr);
+    let ctx = match context_ref(ctx_ptr) {
+        Ok(ctx) => ctx,
+        Err(e) => return fail(ctx_ptr, "mm_poll_job", e),
+    };
+    let Some((status, progress)) = ctx.jobs.poll(job_id) else {
+        return fail(ctx_ptr, "mm_poll_job", AnalyzeError::JobNotFound);
+    };
+    if !out_progress.is_null() {
+        *out_progress = progress;
+    }
+    match status {
+        jobs::JobStatus::Running => 0,
+        jobs::JobStatus::Completed => 1,
+        jobs::JobStatus::Failed => 2,
+        jobs::JobStatus::Cancelled => 3,
+    }
+}
+
+///-----------------------------------------------------------------------------
+/// mm_cancel_job
+/// - 実行中のジョブにキャンセルを要求する (終了は mm_poll_job で確認する)
+/// - 戻り値: 0=要求した, 失敗時は -エラーコード (mm_last_error_code と同じ値)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_cancel_job(ctx_ptr: *mut Context, job_id: u64) -> i32 {
+    clear_error(ctx_ptr);
+    match context_ref(ctx_ptr) {
+        Ok(ctx) if ctx.jobs.cancel(job_id) => 0,
+        Ok(_) => fail(ctx_ptr, "mm_cancel_job", AnalyzeError::JobNotFound),
+        Err(e) => fail(ctx_ptr, "mm_cancel_job", e),
+    }
+}
+
+///-----------------------------------------------------------------------------
+/// mm_job_result
+/// - 終了したジョブの結果を受け取り、ジョブを破棄する
+/// - publish が 0 以外なら、解析結果を OSC に一度に差し替える (mm_load_analysis_result と同じ)
+/// - publish が 0 なら結果を捨てる (不要になったジョブの破棄)
+/// - 戻り値: 0=成功, 失敗時は -エラーコード (mm_last_error_code と同じ値, 解析の失敗・キャンセルはその理由のコード)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_job_result(ctx_ptr: *mut Context, job_id: u64, publish: i32) -> i32 {
+    clear_error(ctx_ptr);
+    let ctx = match context_ref(ctx_ptr) {
+        Ok(ctx) => ctx,
+        Err(e) => return fail(ctx_ptr, "mm_job_result", e),
+    };
+    match ctx.jobs.poll(job_id) {
+        None => return fail(ctx_ptr, "mm_job_result", AnalyzeError::JobNotFound),
+        Some((jobs::JobStatus::Running, _)) => return fail(ctx_ptr, "mm_job_result", AnalyzeError::JobRunning),
+        Some(_) => {}
+    }
+    let analysis = match ctx.jobs.take_result(job_id) {
+        None => return fail(ctx_ptr, "mm_job_result", AnalyzeError::JobNotFound),
+        Some(Ok(analysis)) => analysis,
+        Some(Err(e)) => return fail(ctx_ptr, &format!("Analysis job {}", job_id), e),
+    };
+    if publish == 0 {
+        return 0;
+    }
+    let preset = io::Preset::from_analysis(&analysis, current_params(ctx));
+    if let Err(e) = load_preset_into_context(ctx, preset) {
+        return fail(ctx_ptr, "mm_job_result", e);
+    }
+    log_message_internal("Rust", &format!("Analysis job {} published to the oscillator bank.", job_id));
+    0
+}
+
+///-----------------------------------------------------------------------------
+/// mm_analyze_multichannel
+/// - チャンネルごとのバッファ (planar) を受け取り、params.channel_mode に従って解析する
+/// - ステレオ (PerChannel / MidSide) の場合、2チャンネル目は戻り値の second_channel に入る
+/// - params が null の場合はデフォルト値 (MidOnly) を使用する
+/// - 戻り値: AnalysisResultFFI のポインタ (解析に失敗した場合は null, 理由は mm_last_error_code で取り出す)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - channels は null か、num_channels 個のポインタの配列を指し、各ポインタは null か num_samples 個の f32 を読める領域を指すこと
+/// - params は null か、有効な AnalysisParamsFFI を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_analyze_multichannel(
+    ctx_ptr      : *mut Context, // 失敗の理由の記録に使う (null 可)
+    channels     : *const *const f32,
+    num_channels : usize,
+    num_samples  : usize,
+    sample_rate  : u32,
+    params       : *const AnalysisParamsFFI,
+) -> *mut AnalysisResultFFI {
+    clear_error(ctx_ptr);
+    if channels.is_null() || num_channels == 0 {
+        record_error(ctx_ptr, "mm_analyze_multichannel", AnalyzeError::InvalidArgument("Input channels are null.".to_string()));
+        return std::ptr::null_mut();
+    }
+    let channel_ptrs = std::slice::from_raw_parts(channels, num_channels);
+    if channel_ptrs.iter().any(|ptr| ptr.is_null()) {
+        record_error(ctx_ptr, "mm_analyze_multichannel", AnalyzeError::InvalidArgument("A channel buffer is null.".to_string()));
+        return std::ptr::null_mut();
+    }
+    let channel_slices: Vec<&[f32]> = channel_ptrs.iter()
+        .map(|&ptr| std::slice::from_raw_parts(ptr, num_samples))
+        .collect();
+    let params = match params_from_ffi(params) {
+        Ok(params) => params,
+        Err(e) => {
+            record_error(ctx_ptr, "mm_analyze_multichannel", e);
+            return std::ptr::null_mut();
+        }
+    };
+
+    log_message_internal("Rust", &format!(
+        "mm_analyze_multichannel called. Channels: {}, Samples: {}, Rate: {}, Params: {:?}",
+        num_channels, num_samples, sample_rate, params
+    ));
+
+    let result = analyzer::analyze_multichannel(&channel_slices, sample_rate, &params).and_then(|analysis_data| {
+        log_message_internal("Rust", &format!(
+            "Multichannel analysis successful. Mode: {:?}, Tables: {}",
+            analysis_data.channel_mode,
+            analysis_data.channels.len(),
+        ));
+        AnalysisResultFFI::try_from(analysis_data)
+    });
+    match result {
+        Ok(ffi_result) => Box::into_raw(Box::new(ffi_result)),
+        Err(e) => {
+            record_error(ctx_ptr, "mm_analyze_multichannel", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// 解析の進捗コールバック (progress: 0.0〜1.0, user_data は呼び出し側が渡したもの)
+pub type MMProgressCb = Option<unsafe extern "C" fn(progress: f32, user_data: *mut c_void)>;
+
+/// コールバックに渡す user_data (解析スレッドから呼ぶため Send / Sync として扱う)
+struct ProgressUserData(*mut c_void);
+unsafe impl Send for ProgressUserData {}
+unsafe impl Sync for ProgressUserData {}
+
+impl ProgressUserData {
+    fn get(&self) -> *mut c_void { self.0 }
+}
+
+/// 1回の解析の間だけ Context に登録するキャンセルトークン (mm_cancel_analysis の対象)
+/// - 解析ごとに新しいトークンを使い、drop で登録を外す (同じ Context の他の解析のキャンセル状態には触れない)
+struct RunningAnalysis<'a> {
+    ctx: Option<&'a Context>,
+    cancel: analyzer::CancelToken,
+}
+
+impl<'a> RunningAnalysis<'a> {
+    fn start(ctx: Option<&'a Context>) -> Self {
+        let cancel = analyzer::CancelToken::new();
+        if let Some(running) = ctx.and_then(|ctx| ctx.running.lock().ok()).as_mut() {
+            running.push(cancel.clone());
+        }
+        RunningAnalysis { ctx, cancel }
+    }
+}
+
+impl Drop for RunningAnalysis<'_> {
+    fn drop(&mut self) {
+        if let Some(running) = self.ctx.and_then(|ctx| ctx.running.lock().ok()).as_mut() {
+            running.retain(|token| !token.ptr_eq(&self.cancel));
+        }
+    }
+}
+
+/// 解析のキャンセルトークンと C のコールバックから進捗通知を作る
+fn ffi_progress(run: &RunningAnalysis, progress_cb: MMProgressCb, user_data: *mut c_void) -> analyzer::AnalysisProgress {
+    let callback = progress_cb.map(|callback| {
+        let user_data = ProgressUserData(user_data);
+        std::sync::Arc::new(move |_stage: analyzer::AnalysisStage, progress: f32| unsafe {
+            callback(progress, user_data.get())
+        }) as analyzer::ProgressCallback
+    });
+    analyzer::AnalysisProgress::new(callback, run.cancel.clone())
+}
+
+///-----------------------------------------------------------------------------
+/// mm_cancel_analysis
+/// - 別スレッドで実行中の解析 (mm_analyze_file など) をすべて中断する
+/// - 呼び出した時点で始まっていない解析は中断しない (キャンセルは次の解析に持ち越さない)
+/// - 中断された解析は -11 (Cancelled のコード, mm_analyze_file_with_progress) または null を返す
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_cancel_analysis(ctx_ptr: *mut Context) {
+    if ctx_ptr.is_null() { return; }
+    let ctx = unsafe { &*ctx_ptr };
+    if let Ok(running) = ctx.running.lock() {
+        running.iter().for_each(analyzer::CancelToken::cancel);
+        log_message_internal("Rust", &format!("Analysis cancellation requested ({} running).", running.len()));
+    }
+}
+
+///-----------------------------------------------------------------------------
+/// mm_analyze_file
+/// - 音声ファイル (WAV / AIFF / FLAC) をデフォルトの解析パラメータで解析し、OSCにロードする
+/// - 戻り値: 0=成功, 失敗時は -エラーコード (mm_last_error_code と同じ値, キャンセルは -11)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - path は null か、NUL 終端の文字列を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_analyze_file(ctx_ptr: *mut Context, path: *const c_char) -> i32 {
+    mm_analyze_file_with_progress(ctx_ptr, path, None, std::ptr::null_mut())
+}
+
+///-----------------------------------------------------------------------------
+/// mm_analyze_file_with_progress
+/// - mm_analyze_file と同じく解析して OSC にロードし、各段階の進捗を progress_cb に通知する
+/// - progress_cb は解析を呼んだスレッドから呼ばれる (null なら通知しない)
+/// - 戻り値: 0=成功, 失敗時は -エラーコード (mm_last_error_code と同じ値, キャンセルは -11)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - path は null か、NUL 終端の文字列を指すこと
+/// - progress_cb は解析中に別スレッドから呼ばれてもよい関数であること (user_data はそのまま渡す)
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_analyze_file_with_progress(
+    ctx_ptr     : *mut Context,
+    path        : *const c_char,
+    progress_cb : MMProgressCb,
+    user_data   : *mut c_void,
+) -> i32 {
+    clear_error(ctx_ptr);
+    let ctx = match context_ref(ctx_ptr) {
+        Ok(ctx) => ctx,
+        Err(e) => return fail(ctx_ptr, "mm_analyze_file", e),
+    };
+    let run = RunningAnalysis::start(Some(ctx));
+    let progress = ffi_progress(&run, progress_cb, user_data);
+    let result = match analyze_file_ffi(ctx_ptr, path, std::ptr::null(), &progress) {
+        Ok(result) => result,
+        Err(e) => return -e.code(),
+    };
+    let status = mm_load_analysis_result(ctx_ptr, result);
+    mm_destroy_analysis_result(result);
+    if status != 0 {
+        return status;
+    }
+
+    // プリセット保存用に元のファイル名を記録する
+    let file_name = path_from_c_str(path)
+        .and_then(|path| std::path::Path::new(path).file_name())
+        .map(|name| name.to_string_lossy().into_owned());
+    if let (Some(file_name), Ok(mut preset)) = (file_name, ctx.preset.lock()) {
+        if let Some(preset) = preset.as_mut() {
+            preset.original_file_name = file_name;
+        }
+    }
+    0
+}
+
+///-----------------------------------------------------------------------------
+/// mm_analyze_file_with_params
+/// - 音声ファイル (WAV / AIFF / FLAC) を解析し、結果を返す (OSCへのロードは行わない)
+/// - 複数チャンネルのファイルは params.channel_mode に従い、2チャンネル目は second_channel に入る
+/// - params が null の場合はデフォルト値を使用する
+/// - 戻り値: AnalysisResultFFI のポインタ (失敗した場合・mm_cancel_analysis で中断された場合は null, 理由は mm_last_error_code で取り出す)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - path は null か、NUL 終端の文字列を指すこと
+/// - params は null か、有効な AnalysisParamsFFI を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_analyze_file_with_params(
+    ctx_ptr : *mut Context,
+    path    : *const c_char,
+    params  : *const AnalysisParamsFFI,
+) -> *mut AnalysisResultFFI {
+    clear_error(ctx_ptr);
+    // ctx があれば mm_cancel_analysis で中断できる
+    let run = RunningAnalysis::start(ctx_ptr.as_ref());
+    let progress = ffi_progress(&run, None, std::ptr::null_mut());
+    analyze_file_ffi(ctx_ptr, path, params, &progress).unwrap_or(std::ptr::null_mut())
+}
+
+/// ファイル解析の共通処理 (失敗・キャンセル時は理由を記録してエラーを返す)
+unsafe fn analyze_file_ffi(
+    ctx_ptr  : *mut Context,
+    path     : *const c_char,
+    params   : *const AnalysisParamsFFI,
+    progress : &analyzer::AnalysisProgress,
+) -> Result<*mut AnalysisResultFFI, AnalyzeError> {
+    let Some(path) = path_from_c_str(path) else {
+        let error = AnalyzeError::InvalidArgument("Path is null or not valid UTF-8.".to_string());
+        record_error(ctx_ptr, "mm_analyze_file", error.clone());
+        return Err(error);
+    };
+    let params = match params_from_ffi(params) {
+        Ok(params) => params,
+        Err(e) => {
+            record_error(ctx_ptr, "mm_analyze_file", e.clone());
+            return Err(e);
+        }
+    };
+    log_message_internal("Rust", &format!("mm_analyze_file called with path: {}", path));
+
+    let result = analyzer::analyze_file_with_progress(path, &params, progress).and_then(|analysis_data| {
+        log_message_internal("Rust", &format!(
+            "File analysis successful. Mode: {:?}, Tables: {}",
+            analysis_data.channel_mode,
+            analysis_data.channels.len(),
+        ));
+        AnalysisResultFFI::try_from(analysis_data)
+    });
+    match result {
+        Ok(ffi_result) => Ok(Box::into_raw(Box::new(ffi_result))),
+        Err(e) => {
+            record_error(ctx_ptr, "mm_analyze_file", e.clone());
+            Err(e)
+        }
+    }
+}
+
+/// FFIのポインタと長さから Vec を作る (null または長さ0なら空)
+unsafe fn ffi_slice_to_vec(ptr: *const f32, len: usize) -> Vec<f32> {
+    if ptr.is_null() || len == 0 {
+        vec![]
+    } else {
+        std::slice::from_raw_parts(ptr, len).to_vec()
+    }
+}
+
+/// 解析結果の1チャンネル分の波形とゲインカーブをコピーする
+unsafe fn sections_from_ffi(result: &AnalysisResultFFI) -> io::PresetSections {
+    io::PresetSections {
+        core_wave: ffi_slice_to_vec(result.core_section_ptr, result.core_num_samples),
+        loop_wave: ffi_slice_to_vec(result.loop_section_ptr, result.loop_num_samples),
+        release_wave: ffi_slice_to_vec(result.release_section_ptr, result.release_num_samples),
+        core_gain: ffi_slice_to_vec(result.core_gain_ptr, result.core_gain_len),
+        loop_gain: ffi_slice_to_vec(result.loop_gain_ptr, result.loop_gain_len),
+        release_gain: ffi_slice_to_vec(result.release_gain_ptr, result.release_gain_len),
+    }
+}
+
+/// FFIの解析結果を Context に保持するプリセットの形に変換する
+unsafe fn preset_from_ffi(result: &AnalysisResultFFI, params: ParamBundle) -> io::Preset {
+    let second_channel = match result.channel_layout {
+        _ if result.second_channel.is_null() => None,
+        1 => Some((oscillator::StereoLayout::LeftRight, sections_from_ffi(&*result.second_channel))),
+        2 => Some((oscillator::StereoLayout::MidSide, sections_from_ffi(&*result.second_channel))),
+        _ => None,
+    };
+    let boundaries = ffi_slice_to_vec(result.cycle_boundaries_ptr, result.cycle_boundaries_len * 2);
+    io::Preset {
+        sample_rate: result.sample_rate,
+        f0_curve: ffi_slice_to_vec(result.f0_curve_ptr, result.f0_curve_len),
+        pitch_contour: ffi_slice_to_vec(result.pitch_contour_ptr, result.pitch_contour_len),
+        pitch_contour_rate: result.pitch_contour_rate,
+        cycle_boundaries: boundaries.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect(),
+        sections: sections_from_ffi(result),
+        second_channel,
+        params,
+        original_file_name: String::new(),
+        created_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+    }
+}
+
+/// プリセットの波形・ゲインカーブ・ピッチ変化を OSC にロードし、Context に保持する
+/// - OSC の Mutex をロックできない場合は LockFailed を返す
+fn load_preset_into_context(ctx: &Context, preset: io::Preset) -> Result<(), AnalyzeError> {
+    // 複製と再生レートへの変換はロックの外で済ませる (mm_process を待たせないため)
+    let prepared = oscillator::OscillatorBank::prepare_preset(&preset, ctx.sample_rate);
+    let Ok(mut osc_bank) = ctx.osc_bank.lock() else {
+        return Err(AnalyzeError::LockFailed);
+    };
+    osc_bank.install_preset(prepared);
+    drop(osc_bank);
+
+    let sections = &preset.sections;
+    log_message_internal("Rust", &format!(
+        "Loaded Gains: Core Len={}, Loop Len={}, Release Len={}",
+        sections.core_gain.len(), sections.loop_gain.len(), sections.release_gain.len()
+    ));
+
+    if let Ok(mut current) = ctx.preset.lock() {
+        *current = Some(preset);
+    }
+    Ok(())
+}
+
+/// 現在のパラメータのコピーを返す
+fn current_params(ctx: &Context) -> ParamBundle {
+    let params_ptr = ctx.params_ptr.load(Ordering::SeqCst);
+    if params_ptr.is_null() { ParamBundle::default() } else { unsafe { *params_ptr } }
+}
+
 ///-----------------------------------------------------------------------------
 /// mm_load_analysis_result (新規追加)
 /// - mm_analyze_bufferが返したAnalysisResultFFIの波形データをContextのOSCにロードする
+/// - 戻り値: 0=成功, 失敗時は -エラーコード (mm_last_error_code と同じ値)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - result_ptr は null か、mm_analyze_* が返した破棄前のポインタであること
 ///-----------------------------------------------------------------------------
 #[no_mangle]
 pub unsafe extern "C" fn mm_load_analysis_result(
     ctx_ptr: *mut Context, 
     result_ptr: *const AnalysisResultFFI,
 ) -> i32 {
+    clear_error(ctx_ptr);
     if ctx_ptr.is_null() || result_ptr.is_null() {
-        log_message_internal("Rust", "mm_load_analysis_result failed: null pointer.");
-        return -1;
+        return fail(ctx_ptr, "mm_load_analysis_result", AnalyzeError::InvalidArgument("Null pointer.".to_string()));
     }
 
     let ctx = &*ctx_ptr;
-    let result = &*result_ptr;
-    
-    if let Ok(mut osc_bank) = ctx.osc_bank.lock() {
-        let osc = &mut osc_bank.oscillators[0];
-        
-        log_message_internal("Rust", &format!(
-            "Loaded Gains: Core Len={}, Loop Len={}, Release Len={}",
-            result.core_gain_len, result.loop_gain_len, result.release_gain_len
-        ));
-        
-        // Wave data と Gain data の両方を load_data_from_ffi に渡す
-        osc.load_data_from_ffi(
-            // Wave data
-            result.core_section_ptr, result.core_num_samples,
-            result.loop_section_ptr, result.loop_num_samples,
-            result.release_section_ptr, result.release_num_samples,
-            // Gain data
-            result.core_gain_ptr, result.core_gain_len,
-            result.loop_gain_ptr, result.loop_gain_len,
-            result.release_gain_ptr, result.release_gain_len,
-        );
-
-        log_message_internal("Rust", "Analysis result successfully loaded (Gains applied).");
+    let preset = preset_from_ffi(&*result_ptr, current_params(ctx));
+
+    if let Err(e) = load_preset_into_context(ctx, preset) {
+        return fail(ctx_ptr, "mm_load_analysis_result", e);
+    }
+    log_message_internal("Rust", "Analysis result successfully loaded (Gains applied).");
+    0
+}
+
+/// FFI の解析パラメータを取り出す (null の場合はデフォルト値)
+unsafe fn params_from_ffi(params: *const AnalysisParamsFFI) -> Result<AnalysisParams, AnalyzeError> {
+    if params.is_null() { Ok(AnalysisParams::default()) } else { AnalysisParams::try_from(&*params) }
+}
+
+/// C文字列のパスを取り出す (null または UTF-8 でない場合は None)
+unsafe fn path_from_c_str<'a>(path: *const c_char) -> Option<&'a str> {
+    if path.is_null() { None } else { CStr::from_ptr(path).to_str().ok() }
+}
+
+/// 失敗の理由をログに残し、Context に記録する (mm_last_error_code / mm_last_error_message で取り出す)
+/// - ctx_ptr が null の場合はログのみ
+unsafe fn record_error(ctx_ptr: *mut Context, function: &str, error: AnalyzeError) {
+    log_message_internal("Rust", &format!("{} failed: {}", function, error));
+    if let Some(ctx) = ctx_ptr.as_ref() {
+        if let Ok(mut last_error) = ctx.last_error.lock() {
+            *last_error = Some(error);
+        }
+    }
+}
+
+/// 失敗を記録し、i32 を返す FFI の戻り値 (-エラーコード) を返す
+unsafe fn fail(ctx_ptr: *mut Context, function: &str, error: AnalyzeError) -> i32 {
+    let status = -error.code();
+    record_error(ctx_ptr, function, error);
+    status
+}
+
+/// 前回の失敗の記録を消す
+/// - mm_last_error_code / mm_last_error_message が直近の呼び出しの結果だけを返すよう、失敗しうる FFI の最初に呼ぶ
+unsafe fn clear_error(ctx_ptr: *mut Context) {
+    if let Some(ctx) = ctx_ptr.as_ref() {
+        if let Ok(mut last_error) = ctx.last_error.lock() {
+            *last_error = None;
+        }
+    }
+}
+
+/// ctx_ptr を参照にする (null の場合は InvalidArgument)
+unsafe fn context_ref<'a>(ctx_ptr: *mut Context) -> Result<&'a Context, AnalyzeError> {
+    ctx_ptr.as_ref().ok_or_else(|| AnalyzeError::InvalidArgument("Context is null.".to_string()))
+}
+
+/// パスが null または UTF-8 でない場合のエラー
+fn invalid_path() -> AnalyzeError {
+    AnalyzeError::InvalidArgument("Path is null or not valid UTF-8.".to_string())
+}
+
+///-----------------------------------------------------------------------------
+/// mm_save_preset
+/// - ロード中の解析結果と現在のパラメータをプリセット (ZIP) として保存する
+/// - 戻り値: 0=成功, 失敗時は -エラーコード (mm_last_error_code と同じ値)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - path は null か、NUL 終端の文字列を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_save_preset(ctx_ptr: *mut Context, path: *const c_char) -> i32 {
+    clear_error(ctx_ptr);
+    let Some(path) = path_from_c_str(path).filter(|_| !ctx_ptr.is_null()) else {
+        return fail(ctx_ptr, "mm_save_preset", invalid_path());
+    };
+    let ctx = &*ctx_ptr;
+    let Some(mut preset) = ctx.preset.lock().ok().and_then(|preset| preset.clone()) else {
+        return fail(ctx_ptr, "mm_save_preset", AnalyzeError::NoResultLoaded);
+    };
+    preset.params = current_params(ctx);
+
+    match io::save_preset(path, &preset) {
+        Ok(()) => {
+            log_message_internal("Rust", &format!("Preset saved: {}", path));
+            0
+        }
+        Err(e) => fail(ctx_ptr, "mm_save_preset", e),
+    }
+}
+
+///-----------------------------------------------------------------------------
+/// mm_load_preset
+/// - mm_save_preset で保存したプリセットを読み込み、OSCとパラメータに適用する
+/// - 戻り値: 0=成功, 失敗時は -エラーコード (mm_last_error_code と同じ値)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - path は null か、NUL 終端の文字列を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_load_preset(ctx_ptr: *mut Context, path: *const c_char) -> i32 {
+    clear_error(ctx_ptr);
+    let Some(path) = path_from_c_str(path).filter(|_| !ctx_ptr.is_null()) else {
+        return fail(ctx_ptr, "mm_load_preset", invalid_path());
+    };
+    let preset = match io::load_preset(path) {
+        Ok(preset) => preset,
+        Err(e) => return fail(ctx_ptr, "mm_load_preset", e),
+    };
+    let ctx = &*ctx_ptr;
+    apply_params(ctx, preset.params);
+    if let Err(e) = load_preset_into_context(ctx, preset) {
+        return fail(ctx_ptr, "mm_load_preset", e);
+    }
+    log_message_internal("Rust", &format!("Preset loaded: {}", path));
+    0
+}
+
+///-----------------------------------------------------------------------------
+/// mm_export_sampler
+/// - ロード中の Core/Loop/Release をサンプラー用の SFZ と WAV (smpl チャンク付き) として書き出す
+/// - WAV は sfz_path と同じフォルダに `<名前>.wav` / `<名前>_release.wav` として書く
+/// - 戻り値: 0=成功, 失敗時は -エラーコード (mm_last_error_code と同じ値)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - sfz_path は null か、NUL 終端の文字列を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_export_sampler(ctx_ptr: *mut Context, sfz_path: *const c_char) -> i32 {
+    clear_error(ctx_ptr);
+    let Some(sfz_path) = path_from_c_str(sfz_path).filter(|_| !ctx_ptr.is_null()) else {
+        return fail(ctx_ptr, "mm_export_sampler", invalid_path());
+    };
+    let ctx = &*ctx_ptr;
+    let Some(mut preset) = ctx.preset.lock().ok().and_then(|preset| preset.clone()) else {
+        return fail(ctx_ptr, "mm_export_sampler", AnalyzeError::NoResultLoaded);
+    };
+    preset.params = current_params(ctx);
+
+    match io::export_sampler(sfz_path, &preset) {
+        Ok(()) => {
+            log_message_internal("Rust", &format!("Sampler instrument exported: {}", sfz_path));
+            0
+        }
+        Err(e) => fail(ctx_ptr, "mm_export_sampler", e),
+    }
+}
+
+///-----------------------------------------------------------------------------
+/// mm_import_wavetable
+/// - ウェーブテーブル WAV (Serum / Vital 等の `clm ` チャンク付き) の1フレームを Loop セクションに読み込む
+/// - frame_index は範囲外なら端のフレームに丸める
+/// - Core/Release はロード中の解析結果をそのまま使う (解析結果がなければ Loop のみで再生する)
+/// - 戻り値: 0=成功, 失敗時は -エラーコード (mm_last_error_code と同じ値)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - path は null か、NUL 終端の文字列を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_import_wavetable(ctx_ptr: *mut Context, path: *const c_char, frame_index: i32) -> i32 {
+    clear_error(ctx_ptr);
+    let Some(path) = path_from_c_str(path).filter(|_| !ctx_ptr.is_null()) else {
+        return fail(ctx_ptr, "mm_import_wavetable", invalid_path());
+    };
+    let mut wavetable = match io::import_wavetable(path) {
+        Ok(wavetable) => wavetable,
+        Err(e) => return fail(ctx_ptr, "mm_import_wavetable", e),
+    };
+    let frame_count = wavetable.frames.len();
+    let frame = wavetable.frames.swap_remove((frame_index.max(0) as usize).min(frame_count - 1));
+
+    let ctx = &*ctx_ptr;
+    let mut preset = ctx.preset.lock().ok().and_then(|preset| preset.clone())
+        .unwrap_or_else(|| io::Preset { params: current_params(ctx), ..io::Preset::default() });
+    if let Some((layout, second)) = preset.second_channel.as_mut() {
+        // 2チャンネル目は L/R なら同じ波形、Side なら無音にして中央に定位させる
+        second.loop_wave = match layout {
+            oscillator::StereoLayout::LeftRight => frame.clone(),
+            oscillator::StereoLayout::MidSide => vec![0.0; frame.len()],
+        };
+        second.loop_gain.clear();
+    }
+    preset.sections.loop_wave = frame;
+    preset.sections.loop_gain.clear();
+
+    if let Err(e) = load_preset_into_context(ctx, preset) {
+        return fail(ctx_ptr, "mm_import_wavetable", e);
+    }
+    log_message_internal("Rust", &format!(
+        "Wavetable imported: {} (frame {} of {}, {} samples/frame)",
+        path, frame_index, frame_count, wavetable.frame_size
+    ));
+    0
+}
+
+///-----------------------------------------------------------------------------
+/// mm_get_state
+/// - パラメータとロード中の解析結果を、ホストのプロジェクトに埋め込むバイナリとして書き出す
+/// - out_buf が null または len が足りない場合は書き込まず、必要なバイト数だけを返す
+/// - 戻り値: 状態のバイト数 (失敗した場合は 0)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - out_buf は null か、len バイトを書ける領域を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_get_state(ctx_ptr: *mut Context, out_buf: *mut u8, len: usize) -> usize {
+    if ctx_ptr.is_null() {
+        log_message_internal("Rust", "mm_get_state failed: null pointer.");
         return 0;
-    } else {
-        log_message_internal("Rust", "mm_load_analysis_result failed: Mutex lock error.");
-        return -2;
     }
+    let ctx = &*ctx_ptr;
+    let state = io::SessionState {
+        params: current_params(ctx),
+        preset: ctx.preset.lock().ok().and_then(|preset| preset.clone()),
+    };
+    let blob = io::encode_state(&state);
+    if !out_buf.is_null() && len >= blob.len() {
+        std::ptr::copy_nonoverlapping(blob.as_ptr(), out_buf, blob.len());
+    }
+    blob.len()
+}
+
+///-----------------------------------------------------------------------------
+/// mm_set_state
+/// - mm_get_state で書き出したバイナリからパラメータと解析結果を復元する
+/// - 戻り値: 0=成功, 失敗時は -エラーコード (mm_last_error_code と同じ値)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - buf は null か、len バイトを読める領域を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_set_state(ctx_ptr: *mut Context, buf: *const u8, len: usize) -> i32 {
+    clear_error(ctx_ptr);
+    if ctx_ptr.is_null() || buf.is_null() {
+        return fail(ctx_ptr, "mm_set_state", AnalyzeError::InvalidArgument("Null pointer.".to_string()));
+    }
+    let state = match io::decode_state(std::slice::from_raw_parts(buf, len)) {
+        Ok(state) => state,
+        Err(e) => return fail(ctx_ptr, "mm_set_state", e),
+    };
+    let ctx = &*ctx_ptr;
+    apply_params(ctx, state.params);
+    match state.preset {
+        Some(preset) => {
+            if let Err(e) = load_preset_into_context(ctx, preset) {
+                return fail(ctx_ptr, "mm_set_state", e);
+            }
+        }
+        // 解析結果のない状態 (サンプル未ロードで保存したプロジェクト)
+        None => {
+            if let Ok(mut preset) = ctx.preset.lock() {
+                *preset = None;
+            }
+        }
+    }
+    log_message_internal("Rust", &format!("State restored ({} bytes).", len));
+    0
+}
+
+///-----------------------------------------------------------------------------
+/// mm_last_error_code
+/// - 直近に失敗した呼び出しの理由を AnalyzeError の安定したコードで返す (1=引数が不正, 4=音声が短すぎる,
+///   5=F0が見つからない, 6=周期を切り出せない, 7=デコード失敗, 11=キャンセル など)
+/// - 失敗しうる FFI は呼び出しのたびに記録を消すため、直近の呼び出しが成功していれば 0 を返す
+/// - 戻り値: エラーコード (失敗した呼び出しがなければ 0)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_last_error_code(ctx_ptr: *mut Context) -> i32 {
+    let Some(ctx) = ctx_ptr.as_ref() else { return 0 };
+    ctx.last_error.lock().ok().and_then(|error| error.as_ref().map(AnalyzeError::code)).unwrap_or(0)
+}
+
+///-----------------------------------------------------------------------------
+/// mm_last_error_message
+/// - 直近に失敗した呼び出しの理由を、UI に表示するための UTF-8 文字列 (NUL 終端) として書き込む
+/// - buf が null または len が 0 の場合は書き込まない, len が足りない場合は文字の境界で切り詰めて書き込む
+/// - 戻り値: NUL を含めたメッセージ全体のバイト数 (失敗した呼び出しがなければ 0)
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - buf は null か、len バイトを書ける領域を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_last_error_message(ctx_ptr: *mut Context, buf: *mut c_char, len: usize) -> usize {
+    let Some(ctx) = ctx_ptr.as_ref() else { return 0 };
+    let Some(message) = ctx.last_error.lock().ok().and_then(|error| error.as_ref().map(|e| e.to_string())) else {
+        return 0;
+    };
+    if !buf.is_null() && len > 0 {
+        let mut copy_len = message.len().min(len - 1);
+        while !message.is_char_boundary(copy_len) {
+            copy_len -= 1;
+        }
+        std::ptr::copy_nonoverlapping(message.as_ptr() as *const c_char, buf, copy_len);
+        *buf.add(copy_len) = 0;
+    }
+    message.len() + 1
 }
 
 ///-----------------------------------------------------------------------------
 /// mm_destroy_analysis_result (新規追加)
 /// - C++側から呼ばれ、mm_analyze_buffer が返した AnalysisResultFFI を解放する
+/// # Safety
+/// - result_ptr は null か、mm_analyze_* が返した破棄前のポインタであること (解放後は使わないこと)
 ///-----------------------------------------------------------------------------
 #[no_mangle]
 pub unsafe extern "C" fn mm_destroy_analysis_result(result_ptr: *mut AnalysisResultFFI) {
     if !result_ptr.is_null() {
         let result = &*result_ptr;
         
+        #[allow(clippy::unnecessary_cast)]
         let free_f32_slice = |ptr: *mut f32, len: usize| {
             if !ptr.is_null() {
                 // ポインタと長さを指定してBoxに戻し、スコープを抜ける際に解放
@@ -378,6 +1522,18 @@ pub unsafe extern "C" fn mm_destroy_analysis_result(result_ptr: *mut AnalysisRes
         free_f32_slice(result.core_gain_ptr, result.core_gain_len);
         free_f32_slice(result.loop_gain_ptr, result.loop_gain_len);
         free_f32_slice(result.release_gain_ptr, result.release_gain_len);
+
+        // ピッチ変化を解放
+        free_f32_slice(result.pitch_contour_ptr, result.pitch_contour_len);
+
+        // F0カーブと周期境界を解放
+        free_f32_slice(result.f0_curve_ptr, result.f0_curve_len);
+        free_f32_slice(result.cycle_boundaries_ptr, result.cycle_boundaries_len * 2);
+
+        // 2チャンネル目を解放
+        if !result.second_channel.is_null() {
+            mm_destroy_analysis_result(result.second_channel);
+        }
         
         // AnalysisResultFFI 自体を解放
         let _ = Box::from_raw(result_ptr);
@@ -389,6 +1545,8 @@ pub unsafe extern "C" fn mm_destroy_analysis_result(result_ptr: *mut AnalysisRes
 ///-----------------------------------------------------------------------------
 /// mm_destroy_context
 /// - C++側から呼ばれ、Contextのメモリを解放する
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること (解放後は使わないこと)
 ///-----------------------------------------------------------------------------
 #[no_mangle]
 pub unsafe extern "C" fn mm_destroy_context(ctx_ptr: *mut Context) {
@@ -403,13 +1561,19 @@ pub unsafe extern "C" fn mm_destroy_context(ctx_ptr: *mut Context) {
 ///-----------------------------------------------------------------------------
 /// mm_set_params
 /// - C++側から送られてきたパラメータで内部状態をアトミックに更新する
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - params は null か、有効な ParamBundle を指すこと
 ///-----------------------------------------------------------------------------
 #[no_mangle]
-pub extern "C" fn mm_set_params(ctx_ptr: *mut Context, params: *const ParamBundle) {
+pub unsafe extern "C" fn mm_set_params(ctx_ptr: *mut Context, params: *const ParamBundle) {
     if ctx_ptr.is_null() || params.is_null() { return; }
     let ctx = unsafe { &*ctx_ptr };
-    
-    let new_params = unsafe { *params };
+    apply_params(ctx, unsafe { *params });
+}
+
+/// パラメータをアトミックに差し替え、OscillatorBank に適用する
+fn apply_params(ctx: &Context, new_params: ParamBundle) {
     let new_box = Box::new(new_params);
     let new_ptr = Box::into_raw(new_box);
     
@@ -421,37 +1585,20 @@ pub extern "C" fn mm_set_params(ctx_ptr: *mut Context, params: *const ParamBundl
     
     // 2. ★ 修正点: OscillatorBankにパラメータを適用
     if let Ok(mut osc_bank) = ctx.osc_bank.lock() {
-        // MixModeの設定 (簡易的に0.5未満をAdd, 0.5以上をFMとする)
-        osc_bank.mix_mode = if new_params.mix_mode_f < 0.5 {
-            oscillator::MixMode::Add
-        } else {
-            oscillator::MixMode::FM
-        };
-        osc_bank.fm_mix = new_params.blend; // BlendをFMミックスレベルに流用
-
-        // OSCごとのレベルと周波数比を設定
-        osc_bank.oscillators[0].level = new_params.osc1_level;
-        osc_bank.oscillators[1].level = new_params.osc2_level;
-        osc_bank.oscillators[2].level = new_params.osc3_level;
-        
-        osc_bank.oscillators[0].ratio = new_params.osc1_ratio;
-        osc_bank.oscillators[1].ratio = new_params.osc2_ratio;
-        osc_bank.oscillators[2].ratio = new_params.osc3_ratio;
-
-        // FM変調強度を設定 (OSC2のみが使用)
-        osc_bank.oscillators[1].modulation_index = new_params.fm_index;
+        osc_bank.apply_params(&new_params);
     }
 }
 
 ///-----------------------------------------------------------------------------
 /// mm_note_on
 /// - MIDIノートオンイベントを処理する
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
 ///-----------------------------------------------------------------------------
 #[no_mangle]
-pub extern "C" fn mm_note_on(ctx_ptr: *mut Context, note: i32, velocity: i32) {
+pub unsafe extern "C" fn mm_note_on(ctx_ptr: *mut Context, note: i32, velocity: i32) {
     if ctx_ptr.is_null() { return; }
     let ctx = unsafe { &mut *ctx_ptr };
-    println!("mm_note_on note={} vel={}", note, velocity);  // ←確認
     let freq = 440.0 * 2.0f32.powf((note as f32 - 69.0) / 12.0);
     // ctx.phase_incは使用しない（時間軸再生のため）
     ctx.phase = 0.0;
@@ -460,21 +1607,18 @@ pub extern "C" fn mm_note_on(ctx_ptr: *mut Context, note: i32, velocity: i32) {
     
     // OSC Bank の周波数を設定
     if let Ok(mut osc_bank) = ctx.osc_bank.lock() {
-        for osc in osc_bank.oscillators.iter_mut() {
-            osc.frequency = freq;
-            osc.position = 0.0; // 発音時にポジションをリセット
-            osc.play_mode = oscillator::PlayMode::Core; // Coreモードに設定
-            osc.fm_phase = 0.0; // FM位相をリセット
-        }
+        osc_bank.note_on(freq);
     }
 }
 
 ///-----------------------------------------------------------------------------
 /// mm_note_off
 /// - MIDIノートオフイベントを処理する
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
 ///-----------------------------------------------------------------------------
 #[no_mangle]
-pub extern "C" fn mm_note_off(ctx_ptr: *mut Context, _note: i32) {
+pub unsafe extern "C" fn mm_note_off(ctx_ptr: *mut Context, _note: i32) {
     if ctx_ptr.is_null() { return; }
     let ctx = unsafe { &mut *ctx_ptr };
     // mm_note_offではactiveをfalseにするだけで、OSCのPlayMode遷移はmm_processで行う
@@ -485,9 +1629,13 @@ pub extern "C" fn mm_note_off(ctx_ptr: *mut Context, _note: i32) {
 ///-----------------------------------------------------------------------------
 /// mm_process
 /// - オーディオバッファを処理し、音声信号を生成する
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - out_buffer は null か、num_samples 個の f32 を書ける領域を指すこと
 ///-----------------------------------------------------------------------------
 #[no_mangle]
-pub extern "C" fn mm_process(ctx_ptr: *mut Context, out_buffer: *mut f32, num_samples: i32, _num_channels: i32) {
+#[allow(clippy::needless_range_loop)]
+pub unsafe extern "C" fn mm_process(ctx_ptr: *mut Context, out_buffer: *mut f32, num_samples: i32, _num_channels: i32) {
     if ctx_ptr.is_null() || out_buffer.is_null() { return; }
     let ctx = unsafe { &mut *ctx_ptr };
 
@@ -499,14 +1647,7 @@ pub extern "C" fn mm_process(ctx_ptr: *mut Context, out_buffer: *mut f32, num_sa
     let params_ref = if !params_ptr.is_null() {
         unsafe { *params_ptr }
     } else {
-        // ★ 修正点: フォールバック用のデフォルト値に全てのフィールドを追加 (E0063の修正)
-        ParamBundle {
-            attack: 0.01, decay: 0.1, sustain: 0.8, release: 0.5,
-            blend: 0.5, cutoff: 20000.0, resonance: 1.0,
-            osc1_level: 1.0, osc2_level: 1.0, osc3_level: 0.0,
-            osc1_ratio: 1.0, osc2_ratio: 1.0, osc3_ratio: 2.0,
-            fm_index: 5.0, mix_mode_f: 0.0,
-        }
+        ParamBundle::default()
     };
     
     let samples = num_samples as usize;
@@ -526,4 +1667,111 @@ pub extern "C" fn mm_process(ctx_ptr: *mut Context, out_buffer: *mut f32, num_sa
     } else {
          log_message_internal("Rust", "mm_process failed: Mutex lock error for OscillatorBank.");
     }
-}
\ No newline at end of file
+}
+
+///-----------------------------------------------------------------------------
+/// mm_process_stereo
+/// - mm_process のステレオ版。L/R それぞれのバッファに num_samples 個の値を書き込む
+/// - ステレオ解析結果 (LeftRight / MidSide) をロードしている場合は音像を保って再生する
+/// # Safety
+/// - ctx_ptr は null か、mm_create_context が返した破棄前のポインタであること
+/// - out_left / out_right は null か、それぞれ num_samples 個の f32 を書ける領域を指すこと
+///-----------------------------------------------------------------------------
+#[no_mangle]
+pub unsafe extern "C" fn mm_process_stereo(ctx_ptr: *mut Context, out_left: *mut f32, out_right: *mut f32, num_samples: i32) {
+    if ctx_ptr.is_null() || out_left.is_null() || out_right.is_null() || num_samples <= 0 { return; }
+    let ctx = unsafe { &mut *ctx_ptr };
+
+    let params_ptr = ctx.params_ptr.load(Ordering::SeqCst);
+    let blend = if !params_ptr.is_null() { unsafe { (*params_ptr).blend } } else { 0.5 };
+
+    let samples = num_samples as usize;
+    let left = unsafe { std::slice::from_raw_parts_mut(out_left, samples) };
+    let right = unsafe { std::slice::from_raw_parts_mut(out_right, samples) };
+
+    let amp = ctx.amp * blend;
+
+    if let Ok(mut osc_bank) = ctx.osc_bank.lock() {
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            let (osc_l, osc_r) = osc_bank.process_bank_stereo(ctx.active, 0);
+            *l = osc_l * amp;
+            *r = osc_r * amp;
+        }
+    } else {
+        left.fill(0.0);
+        right.fill(0.0);
+        log_message_internal("Rust", "mm_process_stereo failed: Mutex lock error for OscillatorBank.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_ffi_returns_negated_code_and_clears_last_error() {
+        // 1. Arrange
+        let ctx = unsafe { mm_create_context(48000.0, 512, 2) };
+        let missing = CString::new("/nonexistent/marumaru_preset.zip").unwrap();
+
+        // 2. Act
+        let save_status = unsafe { mm_save_preset(ctx, missing.as_ptr()) };
+        let save_code = unsafe { mm_last_error_code(ctx) };
+        let load_status = unsafe { mm_load_preset(ctx, missing.as_ptr()) };
+        let load_code = unsafe { mm_last_error_code(ctx) };
+        let cancel_status = unsafe { mm_cancel_job(ctx, 42) };
+        let cancel_code = unsafe { mm_last_error_code(ctx) };
+        let mut state = vec![0u8; unsafe { mm_get_state(ctx, std::ptr::null_mut(), 0) }];
+        unsafe { mm_get_state(ctx, state.as_mut_ptr(), state.len()) };
+        let set_state_status = unsafe { mm_set_state(ctx, state.as_ptr(), state.len()) };
+        let cleared_code = unsafe { mm_last_error_code(ctx) };
+
+        // 3. Assert
+        assert_eq!(save_status, -AnalyzeError::NoResultLoaded.code());
+        assert_eq!(save_code, AnalyzeError::NoResultLoaded.code());
+        assert_eq!(load_status, -load_code);
+        assert_eq!(load_code, AnalyzeError::Io(String::new()).code());
+        assert_eq!(cancel_status, -AnalyzeError::JobNotFound.code());
+        assert_eq!(cancel_code, AnalyzeError::JobNotFound.code());
+        assert_eq!(set_state_status, 0);
+        assert_eq!(cleared_code, 0, "成功した呼び出しの後に前回の失敗が残っている");
+        unsafe { mm_destroy_context(ctx) };
+    }
+
+    #[test]
+    fn test_cancel_analysis_only_affects_running_analyses() {
+        // 1. Arrange: 同じ Context で2つの解析が実行中
+        let ctx_ptr = unsafe { mm_create_context(48000.0, 512, 2) };
+        let ctx = unsafe { &*ctx_ptr };
+        let first = RunningAnalysis::start(Some(ctx));
+        let second = RunningAnalysis::start(Some(ctx));
+
+        // 2. Act
+        unsafe { mm_cancel_analysis(ctx_ptr) };
+        let cancelled = [first.cancel.is_cancelled(), second.cancel.is_cancelled()];
+        drop(first);
+        drop(second);
+        let next = RunningAnalysis::start(Some(ctx));
+
+        // 3. Assert: 実行中の解析はすべて中断され、後から始めた解析には持ち越さない
+        assert_eq!(cancelled, [true, true]);
+        assert!(ctx.running.lock().unwrap().iter().all(|token| token.ptr_eq(&next.cancel)));
+        assert!(!next.cancel.is_cancelled());
+        drop(next);
+        assert!(ctx.running.lock().unwrap().is_empty());
+        unsafe { mm_destroy_context(ctx_ptr) };
+    }
+
+    #[test]
+    fn test_empty_multichannel_result_is_an_error() {
+        // 1. Arrange: チャンネルを1つも持たない解析結果
+        let analysis = MultichannelAnalysisResult { channel_mode: ChannelMode::MidOnly, channels: vec![] };
+
+        // 2. Act
+        let converted = AnalysisResultFFI::try_from(analysis);
+
+        // 3. Assert: パニックせずにエラーを返す
+        assert!(matches!(converted, Err(AnalyzeError::AnalysisFailed(_))));
+    }
+}
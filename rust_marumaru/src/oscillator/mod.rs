@@ -2,6 +2,10 @@
 
 // use std::ops::Rem; // ★ 削除 (std::ops::Remはグローバルではなく、f32のメソッドとして利用される)
 
+use crate::analyzer::resample::resample_rate;
+use crate::io::{Preset, PresetSections};
+use crate::ParamBundle;
+
 // 各セクションの実装モジュールを公開
 pub mod core;
 pub mod r#loop; // 'loop'はRustのキーワードのため r#loop と表記
@@ -78,6 +82,140 @@ fn sample_linear(wave: &[f32], index_f: f32) -> f32 {
 }
 
 
+/// ステレオ再生時の2チャンネル目の意味
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoLayout {
+    LeftRight, // 1ch目 = L, 2ch目 = R
+    MidSide,   // 1ch目 = Mid, 2ch目 = Side (L = M + S, R = M - S)
+}
+
+/// 2チャンネル目の Core/Loop/Release 波形とゲインカーブ
+/// - 1ch目と同じ再生位置で読み出す (セクション長は1ch目と同じ前提)
+#[derive(Debug, Clone)]
+pub struct SecondChannel {
+    pub layout: StereoLayout,
+    pub core: WaveSection,
+    pub loop_section: WaveSection,
+    pub release: WaveSection,
+    pub core_gain: Vec<f32>,
+    pub loop_gain: Vec<f32>,
+    pub release_gain: Vec<f32>,
+}
+
+impl SecondChannel {
+    /// 再生モードと位置に対応するサンプル (ゲイン適用済み) を返す
+    fn sample_at(&self, play_mode: PlayMode, position: f32) -> f32 {
+        let (wave, gain_curve) = match play_mode {
+            PlayMode::Core => (&self.core.wavetable, &self.core_gain),
+            PlayMode::Loop => (&self.loop_section.wavetable, &self.loop_gain),
+            PlayMode::Release => (&self.release.wavetable, &self.release_gain),
+            PlayMode::Off => return 0.0,
+        };
+        let wave_len = wave.len() as f32;
+        if wave_len < 2.0 {
+            return 0.0;
+        }
+        let position = if play_mode == PlayMode::Loop { position } else { position.min(wave_len - 1.0) };
+        let output = sample_linear(wave, position);
+        let gain = match play_mode {
+            // Coreのゲインは1ch目と同じくインデックスを四捨五入して読み出す
+            PlayMode::Core => gain_curve.get(position.round() as usize).copied().unwrap_or(1.0),
+            _ if !gain_curve.is_empty() => sample_linear(gain_curve, position / wave_len * gain_curve.len() as f32),
+            _ => 1.0,
+        };
+        output * gain
+    }
+}
+
+/// サンプル単位で再生する Core/Release の原本 (解析した音声のサンプルレート)
+/// - 再生レートが変わるたびにここから変換し直す (変換を重ねて劣化させないため)
+#[derive(Debug, Clone)]
+pub struct SourceSections {
+    pub sample_rate: f32,
+    pub core: Vec<f32>,
+    pub core_gain: Vec<f32>,
+    pub release: Vec<f32>,
+    pub second_channel: Option<SecondChannel>,
+}
+
+/// ゲインカーブを target_len サンプルへ線形補間で伸縮する (sincのリンギングで負にならないように)
+fn stretch_linear(curve: &[f32], target_len: usize) -> Vec<f32> {
+    if curve.len() < 2 || target_len < 2 {
+        return vec![curve.first().copied().unwrap_or(1.0); target_len];
+    }
+    let scale = (curve.len() - 1) as f32 / (target_len - 1) as f32;
+    (0..target_len)
+        .map(|i| {
+            let pos = i as f32 * scale;
+            let idx0 = (pos.floor() as usize).min(curve.len() - 1);
+            let idx1 = (idx0 + 1).min(curve.len() - 1);
+            let frac = pos - idx0 as f32;
+            curve[idx0] * (1.0 - frac) + curve[idx1] * frac
+        })
+        .collect()
+}
+
+/// 解析で取り除いたピッチ変化 (ビブラート等) を再生時に再適用するピッチ変調
+#[derive(Debug, Clone)]
+pub struct PitchModulation {
+    pub contour: Vec<f32>, // 平均からのずれ [cent] (フレーム単位)
+    pub frame_rate: f32,   // contour のフレームレート [frames/s]
+    pub depth: f32,        // 深さの倍率 (0.0 = 無効, 1.0 = 原音どおり)
+    pub rate: f32,         // 速さの倍率 (1.0 = 原音どおり)
+    pub position: f32,     // 現在の読み出し位置 [frame]
+}
+
+impl PitchModulation {
+    pub fn new() -> Self {
+        PitchModulation {
+            contour: vec![],
+            frame_rate: 0.0,
+            depth: 0.0,
+            rate: 1.0,
+            position: 0.0,
+        }
+    }
+
+    /// 解析結果のピッチ変化を設定する
+    /// - 前後の無声フレーム (0.0) を取り除き、平均が 0 cent になるよう正規化する
+    pub fn set_contour(&mut self, contour: &[f32], frame_rate: f32) {
+        let first = contour.iter().position(|&c| c != 0.0);
+        let last = contour.iter().rposition(|&c| c != 0.0);
+        self.contour = match (first, last) {
+            (Some(first), Some(last)) => {
+                let voiced = &contour[first..=last];
+                let mean = voiced.iter().sum::<f32>() / voiced.len() as f32;
+                voiced.iter().map(|&c| c - mean).collect()
+            }
+            _ => vec![],
+        };
+        self.frame_rate = frame_rate;
+        self.position = 0.0;
+    }
+
+    /// 発音開始時に先頭から読み直す
+    pub fn reset(&mut self) {
+        self.position = 0.0;
+    }
+
+    /// 現在の周波数倍率を返し、1サンプル分読み進める (ループ再生)
+    pub fn next_ratio(&mut self, sample_rate: f32) -> f32 {
+        if self.depth == 0.0 || self.contour.len() < 2 || self.frame_rate <= 0.0 {
+            return 1.0;
+        }
+        let len = self.contour.len() as f32;
+        let cents = sample_linear(&self.contour, self.position);
+        self.position = (self.position + self.frame_rate * self.rate / sample_rate).rem_euclid(len);
+        2.0f32.powf(self.depth * cents / 1200.0)
+    }
+}
+
+impl Default for PitchModulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// 単一OSCを表す構造体
 #[derive(Debug)]
 pub struct OscillatorUnit {
@@ -94,6 +232,9 @@ pub struct OscillatorUnit {
     pub position: f32, 
     pub frequency: f32,
     pub play_mode: PlayMode, 
+    pub pitch_mod: PitchModulation, // 原音のピッチ変化の再適用
+    pub second_channel: Option<SecondChannel>, // ステレオ解析結果の2チャンネル目 (モノラルなら None)
+    pub source: Option<SourceSections>, // Core/Release の原本 (再生レートと異なる場合のみ)
     
     // FM/Additive 合成用の追加パラメータ
     pub level: f32,      // OSCの音量レベル (0.0 - 1.0)
@@ -120,6 +261,9 @@ impl OscillatorUnit {
             position: 0.0,
             frequency: 440.0,
             play_mode: PlayMode::Off,
+            pitch_mod: PitchModulation::new(),
+            second_channel: None,
+            source: None,
             
             level: 1.0, 
             ratio: 1.0, 
@@ -132,6 +276,10 @@ impl OscillatorUnit {
     }
 
     /// FFIからデータをロードするヘルパー関数
+    ///
+    /// # Safety
+    /// - 各ポインタは null か、対応する長さの f32 を読める領域を指すこと
+    #[allow(clippy::too_many_arguments)]
     pub unsafe fn load_data_from_ffi(
         &mut self,
         core_ptr: *const f32,
@@ -147,6 +295,9 @@ impl OscillatorUnit {
         release_gain_ptr: *const f32,  
         release_gain_len: usize,       
     ) {
+        // 以前にロードした原本は破棄する (レートの指定は set_source_sample_rate で行う)
+        self.source = None;
+
         // --- Wave Data Load ---
         if !core_ptr.is_null() && core_len > 0 {
             let core_slice = std::slice::from_raw_parts(core_ptr, core_len);
@@ -193,14 +344,94 @@ impl OscillatorUnit {
         }
     }
 
+    /// プリセットの1チャンネル分の波形とゲインカーブをロードする (load_data_from_ffi と同じ扱い)
+    pub fn load_sections(&mut self, sections: &PresetSections) {
+        self.source = None;
+        self.core = WaveSection::new(sections.core_wave.clone());
+        self.loop_section = WaveSection::new(sections.loop_wave.clone());
+        self.loop_section.crossfade = !self.loop_section.is_empty(); // ループセクションのクロスフェードを有効化
+        self.release = WaveSection::new(sections.release_wave.clone());
+        self.core_gain = sections.core_gain.clone();
+        self.loop_gain = sections.loop_gain.clone();
+        self.release_gain = sections.release_gain.clone();
+    }
+
+    /// ロード済みの Core/Release が source_rate の時間軸であることを設定し、再生レートへ変換する
+    /// - Core/Release は1サンプルずつ進めて再生するため、レートが違うと速度とピッチがずれる
+    /// - Loop は周波数から位相を進めるため変換しない
+    pub fn set_source_sample_rate(&mut self, source_rate: f32) {
+        self.source = Some(SourceSections {
+            sample_rate: source_rate,
+            core: std::mem::take(&mut self.core.wavetable),
+            core_gain: std::mem::take(&mut self.core_gain),
+            release: std::mem::take(&mut self.release.wavetable),
+            second_channel: self.second_channel.take(),
+        });
+        self.apply_source_sections();
+    }
+
+    /// 再生レートを変更し、Core/Release を原本から変換し直す
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.apply_source_sections();
+    }
+
+    /// 原本の Core/Release (と2チャンネル目) を現在の再生レートに変換して再生用に設定する
+    fn apply_source_sections(&mut self) {
+        let Some(source) = &self.source else { return };
+        let from = source.sample_rate.round() as u32;
+        let to = self.sample_rate.round() as u32;
+        let convert = |wave: &[f32], gain: &[f32]| -> (Vec<f32>, Vec<f32>) {
+            let wave = resample_rate(wave, from, to);
+            let gain = if gain.is_empty() { vec![] } else { stretch_linear(gain, wave.len()) };
+            (wave, gain)
+        };
+
+        let (core, core_gain) = convert(&source.core, &source.core_gain);
+        self.core.wavetable = core;
+        self.core_gain = core_gain;
+        self.release.wavetable = resample_rate(&source.release, from, to);
+
+        self.second_channel = source.second_channel.clone().map(|mut second| {
+            let (core, core_gain) = convert(&second.core.wavetable, &second.core_gain);
+            second.core.wavetable = core;
+            second.core_gain = core_gain;
+            second.release.wavetable = resample_rate(&second.release.wavetable, from, to);
+            second
+        });
+    }
+
     /// サンプルの生成ロジック
-    pub fn generate_sample(&mut self, is_active: bool, _env_stage: usize) -> f32 {
+    /// - ステレオの場合は L/R の平均 (= Mid) を返す
+    pub fn generate_sample(&mut self, is_active: bool, env_stage: usize) -> f32 {
+        let (left, right) = self.generate_stereo_sample(is_active, env_stage);
+        0.5 * (left + right)
+    }
+
+    /// ステレオでサンプルを生成する (モノラルの場合は左右に同じ値を返す)
+    pub fn generate_stereo_sample(&mut self, is_active: bool, env_stage: usize) -> (f32, f32) {
+        let (primary, play_mode, position) = self.generate_primary_sample(is_active, env_stage);
+        match &self.second_channel {
+            None => (primary, primary),
+            Some(second) => {
+                let secondary = second.sample_at(play_mode, position);
+                match second.layout {
+                    StereoLayout::LeftRight => (primary, secondary),
+                    StereoLayout::MidSide => (primary + secondary, primary - secondary),
+                }
+            }
+        }
+    }
+
+    /// 1チャンネル目のサンプルを生成し、読み出したときの再生モードと位置も返す
+    fn generate_primary_sample(&mut self, is_active: bool, _env_stage: usize) -> (f32, PlayMode, f32) {
         // 状態遷移の更新
         match (is_active, self.play_mode) {
             (true, PlayMode::Off) => {
                 // 発音開始 (mm_note_onが呼ばれた直後)
                 self.play_mode = PlayMode::Core;
                 self.position = 0.0;
+                self.pitch_mod.reset();
             },
             (false, PlayMode::Core) | (false, PlayMode::Loop) => {
                 // ノートオフ (mm_note_offが呼ばれた直後)
@@ -210,6 +441,16 @@ impl OscillatorUnit {
             _ => {} // その他の状態は維持
         }
 
+        // ピッチ変調を適用した現在の周波数
+        let frequency = if self.play_mode == PlayMode::Off {
+            self.frequency
+        } else {
+            self.frequency * self.pitch_mod.next_ratio(self.sample_rate)
+        };
+
+        // 2チャンネル目を同じ位置で読み出すため、進める前の状態を記録する
+        let (read_mode, read_position) = (self.play_mode, self.position);
+
         // ★ 修正点: outputに初期値を割り当て
         let mut output: f32 = 0.0;
         let mut gain: f32 = 1.0; 
@@ -248,7 +489,7 @@ impl OscillatorUnit {
                     self.play_mode = PlayMode::Off; 
                 } else {
                     // Loop再生中は、周波数に基づいてポジションを進める (ウェーブテーブル的再生)
-                    let phase_inc_index = self.frequency * loop_len as f32 / self.sample_rate;
+                    let phase_inc_index = frequency * loop_len as f32 / self.sample_rate;
 
                     output = sample_linear(&self.loop_section.wavetable, self.position);
                     
@@ -294,18 +535,26 @@ impl OscillatorUnit {
         }
         
         // FM合成のベース位相計算と更新
-        let freq_ratio = self.frequency * self.ratio / self.sample_rate;
+        let freq_ratio = frequency * self.ratio / self.sample_rate;
         self.fm_phase += freq_ratio; 
         self.fm_phase = self.fm_phase.rem_euclid(1.0);
         
         // 最終出力にゲインカーブが適用される前の値を保存（フィードバック用）
         self.fm_output = output * gain;
         
-        output * gain
+        (output * gain, read_mode, read_position)
     }
 }
 
 
+/// OscillatorBank::prepare_preset で再生レートへ変換済みのプリセット
+/// - 変換に時間がかかるため、再生スレッドが使うバンクのロックの外で作る
+#[derive(Debug)]
+pub struct PreparedPreset {
+    unit: OscillatorUnit,                 // 変換済みの波形を持つ作業用のOSC1
+    pitch_mod: Option<PitchModulation>,   // 正規化済みのピッチ変化 (無ければ None)
+}
+
 /// 複数OSCを束ねて管理する
 #[derive(Debug)]
 pub struct OscillatorBank {
@@ -327,7 +576,120 @@ impl OscillatorBank {
         }
     }
 
+    /// パラメータをバンクに適用する
+    pub fn apply_params(&mut self, params: &ParamBundle) {
+        // MixModeの設定 (簡易的に0.5未満をAdd, 0.5以上をFMとする)
+        self.mix_mode = if params.mix_mode_f < 0.5 { MixMode::Add } else { MixMode::FM };
+        self.fm_mix = params.blend; // BlendをFMミックスレベルに流用
+
+        // OSCごとのレベルと周波数比を設定
+        self.oscillators[0].level = params.osc1_level;
+        self.oscillators[1].level = params.osc2_level;
+        self.oscillators[2].level = params.osc3_level;
+
+        self.oscillators[0].ratio = params.osc1_ratio;
+        self.oscillators[1].ratio = params.osc2_ratio;
+        self.oscillators[2].ratio = params.osc3_ratio;
+
+        // FM変調強度を設定 (OSC2のみが使用)
+        self.oscillators[1].modulation_index = params.fm_index;
+
+        // ビブラート再適用の深さと速さ (FM時に比率が崩れないよう全OSCに同じ値を設定)
+        for osc in self.oscillators.iter_mut() {
+            osc.pitch_mod.depth = params.vibrato_depth;
+            osc.pitch_mod.rate = params.vibrato_rate;
+        }
+    }
+
+    /// プリセットの波形・ゲインカーブをOSC1に、ピッチ変化を全OSCにロードする
+    pub fn load_preset(&mut self, preset: &Preset) {
+        let sample_rate = self.oscillators[0].sample_rate;
+        self.install_preset(Self::prepare_preset(preset, sample_rate));
+    }
+
+    /// プリセットの波形を sample_rate の再生用に変換しておく (バンクのロックを取らずに呼べる)
+    /// - 複製と再生レートへの変換はここで済ませ、install_preset では差し替えだけ行う
+    pub fn prepare_preset(preset: &Preset, sample_rate: f32) -> PreparedPreset {
+        let mut unit = OscillatorUnit::new(sample_rate);
+        unit.load_sections(&preset.sections);
+
+        // ステレオ解析結果なら2チャンネル目も読み込む (モノラルなら解除)
+        unit.second_channel = preset.second_channel.as_ref().map(|(layout, sections)| {
+            let mut loop_section = WaveSection::new(sections.loop_wave.clone());
+            loop_section.crossfade = !loop_section.is_empty();
+            SecondChannel {
+                layout: *layout,
+                core: WaveSection::new(sections.core_wave.clone()),
+                loop_section,
+                release: WaveSection::new(sections.release_wave.clone()),
+                core_gain: sections.core_gain.clone(),
+                loop_gain: sections.loop_gain.clone(),
+                release_gain: sections.release_gain.clone(),
+            }
+        });
+
+        // Core/Release を解析時のサンプルレートから再生レートへ変換する
+        if preset.sample_rate > 0 {
+            unit.set_source_sample_rate(preset.sample_rate as f32);
+        }
+
+        let pitch_mod = if preset.pitch_contour.is_empty() {
+            None
+        } else {
+            let mut pitch_mod = PitchModulation::new();
+            pitch_mod.set_contour(&preset.pitch_contour, preset.pitch_contour_rate);
+            Some(pitch_mod)
+        };
+        PreparedPreset { unit, pitch_mod }
+    }
+
+    /// prepare_preset で変換した波形をOSC1に、ピッチ変化を全OSCに差し替える
+    /// - 準備した後に再生レートが変わっていた場合だけ、ここで変換し直す
+    pub fn install_preset(&mut self, prepared: PreparedPreset) {
+        let PreparedPreset { unit, pitch_mod } = prepared;
+        let osc = &mut self.oscillators[0];
+        osc.core = unit.core;
+        osc.loop_section = unit.loop_section;
+        osc.release = unit.release;
+        osc.core_gain = unit.core_gain;
+        osc.loop_gain = unit.loop_gain;
+        osc.release_gain = unit.release_gain;
+        osc.second_channel = unit.second_channel;
+        osc.source = unit.source;
+        if unit.sample_rate != osc.sample_rate {
+            osc.apply_source_sections();
+        }
+
+        // ピッチ変化は全OSCに設定する (FM時もキャリアとモジュレータの比率を保つため)
+        if let Some(pitch_mod) = pitch_mod {
+            for osc in self.oscillators.iter_mut() {
+                osc.pitch_mod.contour = pitch_mod.contour.clone();
+                osc.pitch_mod.frame_rate = pitch_mod.frame_rate;
+                osc.pitch_mod.position = 0.0;
+            }
+        }
+    }
+
+    /// 発音開始: 全OSCの周波数を設定し、Core の先頭から再生する
+    pub fn note_on(&mut self, frequency: f32) {
+        for osc in self.oscillators.iter_mut() {
+            osc.frequency = frequency;
+            osc.position = 0.0; // 発音時にポジションをリセット
+            osc.play_mode = PlayMode::Core; // Coreモードに設定
+            osc.fm_phase = 0.0; // FM位相をリセット
+            osc.pitch_mod.reset(); // ビブラートを先頭から再生
+        }
+    }
+
+    /// 全OSCの再生レートを変更する (ロード済みの Core/Release も変換し直す)
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        for osc in self.oscillators.iter_mut() {
+            osc.set_sample_rate(sample_rate);
+        }
+    }
+
     /// バンク全体でサンプルを生成し、ミックスする
+    #[allow(clippy::let_and_return)]
     pub fn process_bank(&mut self, is_active: bool, _env_stage: usize) -> f32 {
         
         match self.mix_mode {
@@ -396,4 +758,141 @@ impl OscillatorBank {
             }
         }
     }
-}
\ No newline at end of file
+
+    /// バンク全体でステレオのサンプルを生成する
+    /// - Add: 各OSCのステレオ出力をレベル付きで加算する
+    /// - FM: 位相変調はモノラルで計算し、左右に同じ値を返す
+    pub fn process_bank_stereo(&mut self, is_active: bool, env_stage: usize) -> (f32, f32) {
+        match self.mix_mode {
+            MixMode::Add => {
+                let mut left = 0.0;
+                let mut right = 0.0;
+                for osc in self.oscillators.iter_mut() {
+                    let (l, r) = osc.generate_stereo_sample(is_active, env_stage);
+                    left += l * osc.level;
+                    right += r * osc.level;
+                }
+                (left / 3.0, right / 3.0)
+            },
+            MixMode::FM => {
+                let output = self.process_bank(is_active, env_stage);
+                (output, output)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mid_side_playback_decodes_left_right() {
+        // 1. Arrange: Mid = 一定値 0.5, Side = 一定値 0.25 の Core セクション
+        let mut osc = OscillatorUnit::new(48000.0);
+        osc.core = WaveSection::new(vec![0.5; 16]);
+        osc.second_channel = Some(SecondChannel {
+            layout: StereoLayout::MidSide,
+            core: WaveSection::new(vec![0.25; 16]),
+            loop_section: WaveSection::new(vec![]),
+            release: WaveSection::new(vec![]),
+            core_gain: vec![],
+            loop_gain: vec![],
+            release_gain: vec![],
+        });
+
+        // 2. Act
+        let (left, right) = osc.generate_stereo_sample(true, 0);
+
+        // 3. Assert: L = M + S, R = M - S、モノラル出力は Mid
+        assert!((left - 0.75).abs() < 1e-6);
+        assert!((right - 0.25).abs() < 1e-6);
+        assert!((osc.generate_sample(true, 0) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_core_section_follows_host_sample_rate() {
+        // 1. Arrange: 22.05kHz で100サンプルの Core を 44.1kHz の OSC にロードする
+        let mut osc = OscillatorUnit::new(44100.0);
+        osc.core = WaveSection::new(vec![0.5; 100]);
+        osc.core_gain = vec![1.0; 100];
+
+        // 2. Act
+        osc.set_source_sample_rate(22050.0);
+
+        // 3. Assert: 再生時間が変わらないよう2倍の長さになり、ゲインカーブも追従する
+        assert_eq!(osc.core.len(), 200);
+        assert_eq!(osc.core_gain.len(), 200);
+
+        // 再生レートが変わったら原本から変換し直す
+        osc.set_sample_rate(48000.0);
+        assert_eq!(osc.core.len(), 217);
+        assert_eq!(osc.core_gain.len(), 217);
+    }
+
+    #[test]
+    fn test_pitch_modulation_depth_and_rate() {
+        // 1. Arrange: 0.4秒周期で揺れるピッチ変化 (10フレーム/秒)
+        let contour = [50.0, 100.0, 50.0, -50.0, 50.0, 100.0, 50.0, -50.0, 50.0, 100.0];
+        let mut pitch_mod = PitchModulation::new();
+        pitch_mod.set_contour(&contour, 10.0);
+        let sample_rate = 100.0;
+
+        // 2. Act & 3. Assert: depth = 0 では変調しない
+        assert_eq!(pitch_mod.next_ratio(sample_rate), 1.0);
+
+        // depth = 0.5, rate = 2.0 では半分の深さで2倍速く進む
+        pitch_mod.depth = 0.5;
+        pitch_mod.rate = 2.0;
+        pitch_mod.reset();
+        let mean = contour.iter().sum::<f32>() / contour.len() as f32;
+        let ratios: Vec<f32> = (0..11).map(|_| pitch_mod.next_ratio(sample_rate)).collect();
+        let expected_peak = 2.0f32.powf(0.5 * (100.0 - mean) / 1200.0);
+        assert!((ratios[5] - expected_peak).abs() < 1e-4, "ratio after 1 frame = {}", ratios[5]);
+        assert!((pitch_mod.position - 2.2).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_prepared_preset_matches_direct_load() {
+        // 1. Arrange: 24kHz で解析したプリセットを 48kHz のバンクへロードする
+        let sections = PresetSections {
+            core_wave: (0..120).map(|i| (i as f32 * 0.1).sin()).collect(),
+            loop_wave: vec![0.0, 0.5, 0.0, -0.5],
+            release_wave: vec![0.3; 40],
+            core_gain: vec![1.0; 120],
+            loop_gain: vec![1.0],
+            release_gain: vec![1.0, 0.0],
+        };
+        let preset = Preset {
+            sample_rate: 24000,
+            f0_curve: vec![200.0],
+            pitch_contour: vec![0.0, 10.0, -10.0, 0.0],
+            pitch_contour_rate: 100.0,
+            cycle_boundaries: vec![],
+            sections: sections.clone(),
+            second_channel: Some((StereoLayout::LeftRight, sections)),
+            params: ParamBundle::default(),
+            original_file_name: String::new(),
+            created_at: 0,
+        };
+        let mut direct = OscillatorBank::new(48000.0);
+        direct.load_preset(&preset);
+
+        // 2. Act: ロック外で準備したあと、差し替える前に再生レートが変わった場合
+        let prepared = OscillatorBank::prepare_preset(&preset, 44100.0);
+        let mut installed = OscillatorBank::new(48000.0);
+        installed.install_preset(prepared);
+
+        // 3. Assert: 直接ロードした場合と同じ再生用の波形になる
+        let (a, b) = (&direct.oscillators[0], &installed.oscillators[0]);
+        assert_eq!(a.core.wavetable, b.core.wavetable);
+        assert_eq!(a.core_gain, b.core_gain);
+        assert_eq!(a.release.wavetable, b.release.wavetable);
+        assert_eq!(a.loop_section.wavetable, b.loop_section.wavetable);
+        assert_eq!(
+            a.second_channel.as_ref().map(|second| second.core.len()),
+            b.second_channel.as_ref().map(|second| second.core.len())
+        );
+        assert_eq!(direct.oscillators[2].pitch_mod.contour, installed.oscillators[2].pitch_mod.contour);
+    }
+}
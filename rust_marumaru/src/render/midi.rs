@@ -0,0 +1,150 @@
+// src/render/midi.rs
+
+use crate::analyzer::AnalyzeError;
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+/// テンポ指定がない場合の四分音符の長さ [μs] (= 120 BPM)
+const DEFAULT_TEMPO_US: u32 = 500_000;
+
+/// 秒単位に直したノートイベント
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteEvent {
+    pub time: f64, // 曲頭からの時刻 [s]
+    pub kind: NoteEventKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteEventKind {
+    On { note: u8, velocity: u8 },
+    Off { note: u8 },
+}
+
+/// テンポマップ (テンポ変更のティック位置と、その時点までの経過秒)
+struct TempoMap {
+    ticks_per_quarter: f64,
+    changes: Vec<(u64, f64, u32)>, // (ティック, その時点の秒, 四分音符の長さ [μs])
+}
+
+impl TempoMap {
+    fn new(ticks_per_quarter: u16, mut tempo_events: Vec<(u64, u32)>) -> Self {
+        tempo_events.sort_by_key(|&(tick, _)| tick);
+        let mut changes = vec![(0, 0.0, DEFAULT_TEMPO_US)];
+        for (tick, tempo) in tempo_events {
+            let seconds = Self::seconds_with(&changes, ticks_per_quarter as f64, tick);
+            if changes.last().is_some_and(|&(last_tick, _, _)| last_tick == tick) {
+                changes.pop(); // 同じティックのテンポ変更は後のものを使う
+            }
+            changes.push((tick, seconds, tempo));
+        }
+        TempoMap { ticks_per_quarter: ticks_per_quarter as f64, changes }
+    }
+
+    fn seconds_with(changes: &[(u64, f64, u32)], ticks_per_quarter: f64, tick: u64) -> f64 {
+        let &(start_tick, start_seconds, tempo) = changes.iter()
+            .rev()
+            .find(|&&(change_tick, _, _)| change_tick <= tick)
+            .unwrap_or(&changes[0]);
+        start_seconds + (tick - start_tick) as f64 / ticks_per_quarter * tempo as f64 * 1e-6
+    }
+
+    fn seconds(&self, tick: u64) -> f64 {
+        Self::seconds_with(&self.changes, self.ticks_per_quarter, tick)
+    }
+}
+
+/// MIDI ファイル (SMF) を読み、全トラックのノートイベントを時刻順に並べる
+/// - テンポ変更 (どのトラックにあっても) を反映して秒に変換する
+/// - ベロシティ 0 のノートオンはノートオフとして扱う
+pub fn parse_note_events(bytes: &[u8]) -> Result<Vec<NoteEvent>, AnalyzeError> {
+    let smf = Smf::parse(bytes).map_err(|e| AnalyzeError::Midi(format!("Failed to parse MIDI file: {}", e)))?;
+
+    // 1. 各トラックのイベントを絶対ティックに直す
+    let mut tempo_events = Vec::new();
+    let mut note_events = Vec::new();
+    for track in &smf.tracks {
+        let mut tick = 0u64;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            match event.kind {
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => tempo_events.push((tick, tempo.as_int())),
+                TrackEventKind::Midi { message, .. } => {
+                    let kind = match message {
+                        MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                            NoteEventKind::On { note: key.as_int(), velocity: vel.as_int() }
+                        }
+                        MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                            NoteEventKind::Off { note: key.as_int() }
+                        }
+                        _ => continue,
+                    };
+                    note_events.push((tick, kind));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // 2. ティックを秒に変換する (同じ時刻ではノートオフを先に処理する)
+    let to_seconds: Box<dyn Fn(u64) -> f64> = match smf.header.timing {
+        Timing::Metrical(ticks_per_quarter) => {
+            let tempo_map = TempoMap::new(ticks_per_quarter.as_int().max(1), tempo_events);
+            Box::new(move |tick| tempo_map.seconds(tick))
+        }
+        Timing::Timecode(fps, subframes) => {
+            let ticks_per_second = fps.as_f32() as f64 * subframes.max(1) as f64;
+            Box::new(move |tick| tick as f64 / ticks_per_second)
+        }
+    };
+    note_events.sort_by_key(|&(tick, kind)| (tick, matches!(kind, NoteEventKind::On { .. })));
+    Ok(note_events.into_iter().map(|(tick, kind)| NoteEvent { time: to_seconds(tick), kind }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use midly::num::{u15, u24, u28, u4, u7};
+    use midly::{Header, Format, TrackEvent};
+
+    #[test]
+    fn test_tempo_change_is_applied_to_later_notes() {
+        // 1. Arrange: 四分音符ごとにノート、2拍目から 60 BPM (1拍 = 1秒)
+        let event = |delta: u32, kind| TrackEvent { delta: u28::new(delta), kind };
+        let note_on = |key: u8| TrackEventKind::Midi {
+            channel: u4::new(0),
+            message: MidiMessage::NoteOn { key: u7::new(key), vel: u7::new(100) },
+        };
+        let note_off = |key: u8| TrackEventKind::Midi {
+            channel: u4::new(0),
+            message: MidiMessage::NoteOff { key: u7::new(key), vel: u7::new(0) },
+        };
+        let tempo_track = vec![
+            event(480, TrackEventKind::Meta(MetaMessage::Tempo(u24::new(1_000_000)))),
+            event(0, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+        ];
+        let note_track = vec![
+            event(0, note_on(60)),
+            event(480, note_off(60)),
+            event(0, note_on(62)),
+            event(480, note_off(62)),
+            event(0, TrackEventKind::Meta(MetaMessage::EndOfTrack)),
+        ];
+        let smf = Smf {
+            header: Header::new(Format::Parallel, Timing::Metrical(u15::new(480))),
+            tracks: vec![tempo_track, note_track],
+        };
+        let mut bytes = Vec::new();
+        smf.write_std(&mut bytes).unwrap();
+
+        // 2. Act
+        let events = parse_note_events(&bytes).unwrap();
+
+        // 3. Assert: 1拍目は 120 BPM で 0.5秒、2拍目は 60 BPM で 1秒
+        let times: Vec<(f64, NoteEventKind)> = events.iter().map(|e| (e.time, e.kind)).collect();
+        assert_eq!(times, vec![
+            (0.0, NoteEventKind::On { note: 60, velocity: 100 }),
+            (0.5, NoteEventKind::Off { note: 60 }),
+            (0.5, NoteEventKind::On { note: 62, velocity: 100 }),
+            (1.5, NoteEventKind::Off { note: 62 }),
+        ]);
+    }
+}
@@ -0,0 +1,182 @@
+// src/render/mod.rs
+
+// 各モジュールを公開
+pub mod midi;
+
+pub use self::midi::{parse_note_events, NoteEvent, NoteEventKind};
+
+use crate::log_message_internal;
+use crate::analyzer::AnalyzeError;
+use crate::io::Preset;
+use crate::oscillator::OscillatorBank;
+use std::path::Path;
+
+/// オフラインレンダリングの設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub sample_rate: u32,
+    pub tail_seconds: f32, // 最後のイベントの後に書き出す長さ (Release の余韻)
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings { sample_rate: 44100, tail_seconds: 2.0 }
+    }
+}
+
+/// レンダリング結果 (ステレオのプリセットなら L/R の2チャンネル, それ以外はモノラル)
+#[derive(Debug, Clone)]
+pub struct RenderedAudio {
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+/// MIDI ノート番号を周波数 [Hz] に変換する (A4 = 440Hz)
+pub fn note_to_frequency(note: u8) -> f32 {
+    440.0 * 2.0f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+/// ノートイベントをプラグインと同じ OscillatorBank で再生し、音声を返す
+/// - 単音発音 (後着優先): 鳴っているノート以外のノートオフは無視する
+/// - 音量はプラグインと同じく velocity / 127 × blend
+pub fn render_events(events: &[NoteEvent], preset: &Preset, settings: &RenderSettings) -> RenderedAudio {
+    let sample_rate = settings.sample_rate.max(1);
+    let mut bank = OscillatorBank::new(sample_rate as f32);
+    bank.apply_params(&preset.params);
+    bank.load_preset(preset);
+
+    let end_time = events.last().map_or(0.0, |event| event.time) + settings.tail_seconds.max(0.0) as f64;
+    let total_samples = (end_time * sample_rate as f64).ceil() as usize;
+    let stereo = preset.second_channel.is_some();
+    let mut left = Vec::with_capacity(total_samples);
+    let mut right = Vec::with_capacity(if stereo { total_samples } else { 0 });
+
+    let mut active = false;
+    let mut amp = 0.0;
+    let mut current_note = None;
+    let mut next_event = 0;
+    for i in 0..total_samples {
+        let time = i as f64 / sample_rate as f64;
+        while let Some(event) = events.get(next_event).filter(|event| event.time <= time) {
+            match event.kind {
+                NoteEventKind::On { note, velocity } => {
+                    bank.note_on(note_to_frequency(note));
+                    active = true;
+                    amp = velocity as f32 / 127.0;
+                    current_note = Some(note);
+                }
+                NoteEventKind::Off { note } if current_note == Some(note) => {
+                    active = false;
+                    current_note = None;
+                }
+                NoteEventKind::Off { .. } => {}
+            }
+            next_event += 1;
+        }
+
+        let gain = amp * preset.params.blend;
+        if stereo {
+            let (l, r) = bank.process_bank_stereo(active, 0);
+            left.push(l * gain);
+            right.push(r * gain);
+        } else {
+            left.push(bank.process_bank(active, 0) * gain);
+        }
+    }
+
+    let channels = if stereo { vec![left, right] } else { vec![left] };
+    RenderedAudio { sample_rate, channels }
+}
+
+/// MIDI ファイルをプリセットで再生し、32bit float WAV に書き出す (テンポマップに対応)
+pub fn render_midi_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    midi_path: P,
+    preset: &Preset,
+    wav_path: Q,
+    settings: &RenderSettings,
+) -> Result<RenderedAudio, AnalyzeError> {
+    let bytes = std::fs::read(midi_path.as_ref())
+        .map_err(|e| AnalyzeError::Io(format!("Failed to read MIDI file {:?}: {}", midi_path.as_ref(), e)))?;
+    let events = parse_note_events(&bytes)?;
+    log_message_internal("Rust", &format!("Rendering {} note events.", events.len()));
+    let rendered = render_events(&events, preset, settings);
+
+    let spec = hound::WavSpec {
+        channels: rendered.channels.len() as u16,
+        sample_rate: rendered.sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(wav_path.as_ref(), spec)
+        .map_err(|e| AnalyzeError::Io(format!("Failed to create {:?}: {}", wav_path.as_ref(), e)))?;
+    let num_frames = rendered.channels[0].len();
+    for i in 0..num_frames {
+        for channel in &rendered.channels {
+            writer.write_sample(channel[i]).map_err(|e| AnalyzeError::Io(format!("Failed to write WAV: {}", e)))?;
+        }
+    }
+    writer.finalize().map_err(|e| AnalyzeError::Io(format!("Failed to finish WAV: {}", e)))?;
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::PresetSections;
+
+    fn sine_preset() -> Preset {
+        let cycle: Vec<f32> = (0..256).map(|i| (i as f32 / 256.0 * std::f32::consts::TAU).sin()).collect();
+        Preset {
+            sample_rate: 44100,
+            sections: PresetSections {
+                core_wave: (0..441).map(|i| (i as f32 * 0.05).sin()).collect(),
+                loop_wave: cycle,
+                release_wave: (0..4410).map(|i| (i as f32 * 0.05).sin() * (1.0 - i as f32 / 4410.0)).collect(),
+                ..PresetSections::default()
+            },
+            ..Preset::default()
+        }
+    }
+
+    #[test]
+    fn test_render_events_follow_note_timing() {
+        // 1. Arrange: 0.1秒から 0.3秒間 A4 を鳴らす
+        let events = [
+            NoteEvent { time: 0.1, kind: NoteEventKind::On { note: 69, velocity: 127 } },
+            NoteEvent { time: 0.4, kind: NoteEventKind::Off { note: 69 } },
+        ];
+        let settings = RenderSettings { sample_rate: 44100, tail_seconds: 0.5 };
+
+        // 2. Act
+        let rendered = render_events(&events, &sine_preset(), &settings);
+
+        // 3. Assert: 発音前は無音、発音中は音があり、Release が終わると無音に戻る
+        let output = &rendered.channels[0];
+        let rms = |range: std::ops::Range<usize>| {
+            (output[range.clone()].iter().map(|s| s * s).sum::<f32>() / range.len() as f32).sqrt()
+        };
+        assert_eq!(rendered.channels.len(), 1);
+        assert_eq!(output.len(), (0.9 * 44100.0f64).ceil() as usize);
+        assert_eq!(rms(0..4400), 0.0);
+        assert!(rms(8820..17640) > 0.1);
+        assert!(rms(output.len() - 4410..output.len()) < 1e-6);
+    }
+
+    #[test]
+    fn test_render_midi_asset() {
+        // 1. Arrange
+        let midi_path = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/spaceii.mid");
+        let wav_path = std::env::temp_dir().join(format!("marumaru_render_{}.wav", std::process::id()));
+        let settings = RenderSettings { sample_rate: 22050, tail_seconds: 0.5 };
+
+        // 2. Act
+        let rendered = render_midi_file(midi_path, &sine_preset(), &wav_path, &settings);
+        let written = hound::WavReader::open(&wav_path).map(|reader| reader.duration());
+        let _ = std::fs::remove_file(&wav_path);
+
+        // 3. Assert
+        let rendered = rendered.unwrap();
+        assert!(rendered.channels[0].iter().any(|s| s.abs() > 0.01));
+        assert_eq!(written.unwrap() as usize, rendered.channels[0].len());
+    }
+}
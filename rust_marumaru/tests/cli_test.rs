@@ -0,0 +1,23 @@
+// tests/cli_test.rs
+
+// marumaru CLI の統合テスト (ビルドしたバイナリを実行する)
+use std::process::Command;
+
+#[test]
+fn test_cli_analyze_prints_json_report() {
+    // 1. Arrange
+    let asset = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/piano.wav");
+
+    // 2. Act
+    let output = Command::new(env!("CARGO_BIN_EXE_marumaru"))
+        .args(["analyze", asset, "--json"])
+        .output()
+        .expect("failed to run marumaru");
+
+    // 3. Assert: stdout は解析ログを含まない JSON のみ
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).expect("stdout must be JSON");
+    assert_eq!(report["sample_rate"], 22050);
+    assert!(report["f0"]["target_f0"].as_f64().unwrap() > 400.0);
+    assert!(report["cycles_count"].as_u64().unwrap() > 0);
+}
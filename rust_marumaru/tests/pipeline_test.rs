@@ -4,6 +4,7 @@
 // そのため、ライブラリ名（Cargo.tomlの[package] name）を使ってモジュールをインポートします。
 // ここでは仮に `rust_marumaru` としています。
 use rust_marumaru::analyzer;
+use std::sync::{Arc, Mutex};
 
 #[test]
 fn test_full_pipeline_on_pure_sine() {
@@ -20,7 +21,7 @@ fn test_full_pipeline_on_pure_sine() {
     }
 
     // 2. Act: メインの `analyze_audio` 関数を実行！
-    let result = analyzer::analyze_audio(&signal, SAMPLE_RATE);
+    let result = analyzer::analyze_audio(&signal, SAMPLE_RATE, &analyzer::AnalysisParams::default());
     assert!(result.is_ok(), "解析パイプライン全体がエラーを返しました: {:?}", result.err());
     let analysis_result = result.unwrap();
 
@@ -29,6 +30,8 @@ fn test_full_pipeline_on_pure_sine() {
     // a) F0カーブが正しく推定されているか
     let valid_f0s: Vec<f32> = analysis_result.f0_curve.iter().filter(|&&f| f > 0.0).cloned().collect();
     assert!(!valid_f0s.is_empty(), "有効なF0が一つも見つかりませんでした");
+    assert_eq!(analysis_result.voicing.len(), analysis_result.f0_curve.len());
+    assert!(analysis_result.voicing.iter().all(|&v| v), "サイン波はすべて有声と判定されるべきです");
     let average_f0: f32 = valid_f0s.iter().sum::<f32>() / valid_f0s.len() as f32;
     assert!(
         (average_f0 - SIGNAL_FREQ).abs() < 2.0,
@@ -42,7 +45,7 @@ fn test_full_pipeline_on_pure_sine() {
     // c) 生成されたウェーブテーブルが正しいか
     assert_eq!(analysis_result.tables.len(), 1, "ウェーブテーブルは1つだけ生成されるべきです");
     let table = &analysis_result.tables[0];
-    let expected_len = (SAMPLE_RATE as f32 / average_f0).round() as usize;
+    let expected_len = analyzer::AnalysisParams::default().target_cycle_len;
     assert_eq!(table.len(), expected_len, "ウェーブテーブルの長さが正しくありません");
     
     // d) 波形の形がサイン波に近いか
@@ -56,4 +59,162 @@ fn test_full_pipeline_on_pure_sine() {
         (table[trough_idx] - -1.0).abs() < 0.1,
         "波形のトラフが正しくありません"
     );
-}
\ No newline at end of file
+}
+
+/// テスト用のサイン波を生成する
+fn make_sine(sample_rate: u32, freq: f32, len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / sample_rate as f32).sin() * 0.7)
+        .collect()
+}
+
+#[test]
+fn test_forced_mode_selection() {
+    const SAMPLE_RATE: u32 = 48000;
+    let signal = make_sine(SAMPLE_RATE, 440.0, SAMPLE_RATE as usize);
+
+    // 周期性が高いサイン波でも、Freqモードを強制できること
+    let params = analyzer::AnalysisParams {
+        mode_selection: analyzer::ModeSelection::Freq,
+        ..Default::default()
+    };
+    let result = analyzer::analyze_audio(&signal, SAMPLE_RATE, &params).expect("解析に失敗しました");
+
+    assert_eq!(result.mode_decision.mode, analyzer::AnalysisDomain::Freq);
+    assert_eq!(result.mode_decision.reason, analyzer::ModeReason::Forced);
+    assert_eq!(result.mode_candidates.len(), 1);
+}
+
+#[test]
+fn test_explicit_profile_keeps_caller_thresholds() {
+    const SAMPLE_RATE: u32 = 48000;
+    let signal = make_sine(SAMPLE_RATE, 440.0, SAMPLE_RATE as usize);
+
+    // Natural のプリセット (0.6 / 0.35) ではなく、呼び出し側が設定した閾値でモードを選ぶこと
+    let params = analyzer::AnalysisParams {
+        profile: analyzer::AnalysisProfile::Natural,
+        time_mode_threshold: 1.0,
+        hybrid_mode_threshold: 1.0,
+        ..Default::default()
+    };
+    let result = analyzer::analyze_audio(&signal, SAMPLE_RATE, &params).expect("解析に失敗しました");
+
+    assert_eq!(result.mode_decision.mode, analyzer::AnalysisDomain::Freq);
+    assert_eq!(result.mode_decision.reason, analyzer::ModeReason::LowPeriodicity);
+}
+
+#[test]
+fn test_rank_all_modes() {
+    const SAMPLE_RATE: u32 = 48000;
+    let signal = make_sine(SAMPLE_RATE, 440.0, SAMPLE_RATE as usize);
+
+    let params = analyzer::AnalysisParams {
+        mode_selection: analyzer::ModeSelection::RankAll,
+        ..Default::default()
+    };
+    let result = analyzer::analyze_audio(&signal, SAMPLE_RATE, &params).expect("解析に失敗しました");
+
+    // 3モードすべてが評価され、スコア降順に並んでいること
+    assert_eq!(result.mode_candidates.len(), 3);
+    for pair in result.mode_candidates.windows(2) {
+        assert!(pair[0].quality.score() >= pair[1].quality.score(), "候補がスコア順に並んでいません");
+    }
+    assert_eq!(result.mode_decision.reason, analyzer::ModeReason::BestQuality);
+    assert_eq!(result.mode_decision.mode, result.mode_candidates[0].mode);
+    assert_eq!(result.tables, result.mode_candidates[0].tables);
+}
+
+#[test]
+fn test_mid_side_analysis_keeps_channel_phase() {
+    // 1. Arrange: 左右で音量の異なるステレオのサイン波 (Side = Mid と同相)
+    const SAMPLE_RATE: u32 = 48000;
+    let left = make_sine(SAMPLE_RATE, 220.0, SAMPLE_RATE as usize);
+    let right: Vec<f32> = left.iter().map(|&s| s * 0.5).collect();
+    let params = analyzer::AnalysisParams {
+        channel_mode: analyzer::ChannelMode::MidSide,
+        ..Default::default()
+    };
+
+    // 2. Act
+    let result = analyzer::analyze_multichannel(&[&left, &right], SAMPLE_RATE, &params)
+        .expect("解析に失敗しました");
+
+    // 3. Assert: Mid と Side のテーブルが生成され、同じF0・同じ位相で切り出されている
+    assert_eq!(result.channels.len(), 2);
+    let mid = &result.channels[0];
+    let side = &result.channels[1];
+    assert_eq!(mid.f0_curve, side.f0_curve);
+    let dot: f32 = mid.tables[0].iter().zip(&side.tables[0]).map(|(a, b)| a * b).sum();
+    let norm = (mid.tables[0].iter().map(|a| a * a).sum::<f32>() * side.tables[0].iter().map(|b| b * b).sum::<f32>()).sqrt();
+    assert!(dot / norm > 0.95, "Mid と Side の位相がずれています: {}", dot / norm);
+
+    // MidSide は2チャンネル入力のみ
+    let err = analyzer::analyze_multichannel(&[&left, &right, &left], SAMPLE_RATE, &params);
+    assert!(err.is_err());
+}
+
+#[test]
+fn test_analyze_file_decodes_wav_asset() {
+    // 1. Arrange: リポジトリ同梱のピアノ音 (16bit, モノラル)
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/piano.wav");
+
+    // 2. Act
+    let result = analyzer::analyze_file(path, &analyzer::AnalysisParams::default())
+        .expect("ファイルの解析に失敗しました");
+
+    // 3. Assert
+    assert_eq!(result.channels.len(), 1);
+    assert!(!result.channels[0].tables.is_empty());
+    assert!(analyzer::analyze_file("does/not/exist.wav", &analyzer::AnalysisParams::default()).is_err());
+}
+
+#[test]
+fn test_progress_is_reported_per_stage_and_cancellation_aborts() {
+    // 1. Arrange: 進捗を記録し、F0推定の途中でキャンセルするコールバック
+    const SAMPLE_RATE: u32 = 48000;
+    let signal = make_sine(SAMPLE_RATE, 220.0, SAMPLE_RATE as usize);
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let record = |cancel_at: Option<f32>, token: analyzer::CancelToken| {
+        let sink = reports.clone();
+        let callback: analyzer::ProgressCallback = Arc::new(move |stage, progress| {
+            sink.lock().unwrap().push((stage, progress));
+            if cancel_at.is_some_and(|at| progress >= at) {
+                token.cancel();
+            }
+        });
+        callback
+    };
+
+    // 2. Act
+    let token = analyzer::CancelToken::new();
+    let progress = analyzer::AnalysisProgress::new(Some(record(None, token.clone())), token);
+    let completed = analyzer::analyze_audio_with_progress(&signal, SAMPLE_RATE, &analyzer::AnalysisParams::default(), &progress);
+    let completed_reports = std::mem::take(&mut *reports.lock().unwrap());
+
+    let token = analyzer::CancelToken::new();
+    let progress = analyzer::AnalysisProgress::new(Some(record(Some(0.2), token.clone())), token);
+    let cancelled = analyzer::analyze_audio_with_progress(&signal, SAMPLE_RATE, &analyzer::AnalysisParams::default(), &progress);
+    let cancelled_reports = reports.lock().unwrap().clone();
+
+    // 3. Assert: 段階は順に進み、進捗は単調増加で 1.0 で終わる
+    assert!(completed.is_ok());
+    let stages: Vec<analyzer::AnalysisStage> = completed_reports.iter().map(|&(stage, _)| stage)
+        .fold(Vec::new(), |mut stages, stage| {
+            if stages.last() != Some(&stage) { stages.push(stage); }
+            stages
+        });
+    assert_eq!(stages, vec![
+        analyzer::AnalysisStage::Preprocess,
+        analyzer::AnalysisStage::F0,
+        analyzer::AnalysisStage::PitchSync,
+        analyzer::AnalysisStage::ModeAnalysis,
+        analyzer::AnalysisStage::Quality,
+    ]);
+    assert!(completed_reports.windows(2).all(|pair| pair[1].1 >= pair[0].1));
+    assert_eq!(completed_reports.last().unwrap().1, 1.0);
+
+    // キャンセルは専用のエラーになり、F0推定の途中で止まる
+    assert_eq!(cancelled.unwrap_err(), analyzer::AnalyzeError::Cancelled);
+    assert_eq!(cancelled_reports.last().unwrap().0, analyzer::AnalysisStage::F0);
+    assert!(cancelled_reports.last().unwrap().1 < 0.25);
+}